@@ -0,0 +1,252 @@
+//! A pool of read buffers shared between framed transports.
+//!
+//! Each framed transport normally owns a read buffer for its entire
+//! lifetime, even while the connection is idle, which for servers with many
+//! mostly idle connections both wastes memory and fragments the heap. A
+//! `PooledFramedRead` instead only holds on to a buffer from a `BufPool`
+//! while it has data to decode.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use bytes::BytesMut;
+use futures::{Async, Poll, Stream};
+use tokio_io::AsyncRead;
+use tokio_io::codec::Decoder;
+
+/// A pool of read buffers which can be shared between many transports.
+///
+/// A `BufPool` is cheaply cloneable and clones refer to the same set of
+/// buffers. It is also `Send` and `Sync`, so one pool can be shared between
+/// connections running on separate event loops.
+#[derive(Clone)]
+pub struct BufPool {
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    bufs: Vec<BytesMut>,
+    buf_capacity: usize,
+    max_idle: usize,
+}
+
+impl BufPool {
+    /// Creates a new, empty pool.
+    ///
+    /// Buffers allocated by this pool will have an initial capacity of
+    /// `buf_capacity` bytes, and at most `max_idle` buffers are kept around
+    /// while not in use. Buffers returned to a pool which is already full,
+    /// or which have grown to more than twice `buf_capacity`, are
+    /// deallocated.
+    pub fn new(buf_capacity: usize, max_idle: usize) -> BufPool {
+        BufPool {
+            inner: Arc::new(Mutex::new(Inner {
+                bufs: Vec::new(),
+                buf_capacity: buf_capacity,
+                max_idle: max_idle,
+            })),
+        }
+    }
+
+    /// Takes an empty buffer with room for at least `buf_capacity` bytes out
+    /// of this pool, allocating a new one if no idle buffers are available.
+    pub fn get(&self) -> BytesMut {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.bufs.pop() {
+            Some(mut buf) => {
+                // Storage still shared with frames split off the buffer is
+                // reclaimed here if they have been dropped since, and
+                // replaced otherwise.
+                buf.reserve(inner.buf_capacity);
+                buf
+            }
+            None => BytesMut::with_capacity(inner.buf_capacity),
+        }
+    }
+
+    /// Returns a buffer to this pool so it can be handed out again.
+    ///
+    /// The contents of `buf` are cleared. If the pool already holds the
+    /// maximum number of idle buffers, or `buf` has grown to more than twice
+    /// the capacity of the pool's buffers, then `buf` is dropped instead.
+    pub fn put(&self, mut buf: BytesMut) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.bufs.len() < inner.max_idle &&
+           buf.capacity() <= inner.buf_capacity.saturating_mul(2) {
+            buf.clear();
+            inner.bufs.push(buf);
+        }
+    }
+
+    /// Returns the number of idle buffers currently held by this pool.
+    pub fn idle(&self) -> usize {
+        self.inner.lock().unwrap().bufs.len()
+    }
+}
+
+impl fmt::Debug for BufPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("BufPool")
+         .field("idle", &inner.bufs.len())
+         .field("buf_capacity", &inner.buf_capacity)
+         .field("max_idle", &inner.max_idle)
+         .finish()
+    }
+}
+
+/// A `Stream` of frames decoded from an `AsyncRead`, like `FramedRead` from
+/// `tokio-io`, which reads into buffers taken from a `BufPool`.
+///
+/// A buffer is taken out of the pool when the transport is about to read,
+/// and handed back once all of its data has been decoded and the transport
+/// is waiting for more, or when the transport is dropped. Only connections
+/// actively receiving data then hold a buffer.
+///
+/// For a transport which is also written to, split the I/O object and wrap
+/// the write half in a `FramedWrite` from `tokio-io`.
+pub struct PooledFramedRead<T, D> {
+    inner: T,
+    decoder: D,
+    buffer: Buffer,
+    eof: bool,
+    is_readable: bool,
+}
+
+/// The read buffer of a `PooledFramedRead`, if it holds one.
+struct Buffer {
+    buf: Option<BytesMut>,
+    pool: BufPool,
+}
+
+impl<T, D> PooledFramedRead<T, D>
+    where T: AsyncRead,
+          D: Decoder,
+{
+    /// Creates a new `PooledFramedRead` decoding frames read from `inner`
+    /// with `decoder`, into buffers from `pool`.
+    pub fn new(inner: T, decoder: D, pool: BufPool) -> PooledFramedRead<T, D> {
+        PooledFramedRead {
+            inner: inner,
+            decoder: decoder,
+            buffer: Buffer { buf: None, pool: pool },
+            eof: false,
+            is_readable: false,
+        }
+    }
+}
+
+impl<T, D> PooledFramedRead<T, D> {
+    /// Returns a reference to the underlying I/O object.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying I/O object.
+    ///
+    /// Note that care should be taken not to tamper with the underlying
+    /// stream of data coming in, as it may corrupt the stream of frames.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Returns a reference to the decoder.
+    pub fn decoder(&self) -> &D {
+        &self.decoder
+    }
+
+    /// Returns a mutable reference to the decoder.
+    pub fn decoder_mut(&mut self) -> &mut D {
+        &mut self.decoder
+    }
+
+    /// Consumes the `PooledFramedRead`, returning the underlying I/O object.
+    ///
+    /// Note that any data which has been read but not yet decoded is lost,
+    /// and the buffer holding it goes back to the pool.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T, D> Stream for PooledFramedRead<T, D>
+    where T: AsyncRead,
+          D: Decoder,
+{
+    type Item = D::Item;
+    type Error = D::Error;
+
+    fn poll(&mut self) -> Poll<Option<D::Item>, D::Error> {
+        loop {
+            if self.is_readable {
+                let frame = {
+                    let buf = self.buffer.get();
+                    if self.eof {
+                        try!(self.decoder.decode_eof(buf))
+                    } else {
+                        try!(self.decoder.decode(buf))
+                    }
+                };
+                if frame.is_some() || self.eof {
+                    if frame.is_none() {
+                        self.buffer.release();
+                    }
+                    return Ok(Async::Ready(frame))
+                }
+                self.is_readable = false;
+            }
+
+            assert!(!self.eof);
+
+            // Make sure there's room for at least one byte, so that a read
+            // of zero bytes means EOF.
+            let buf = self.buffer.get();
+            buf.reserve(1);
+            match AsyncRead::read_buf(&mut self.inner, buf) {
+                Ok(Async::Ready(0)) => self.eof = true,
+                Ok(Async::Ready(_)) => {}
+                Ok(Async::NotReady) => {
+                    self.buffer.release();
+                    return Ok(Async::NotReady)
+                }
+                Err(e) => return Err(e.into()),
+            }
+            self.is_readable = true;
+        }
+    }
+}
+
+impl Buffer {
+    /// Returns the buffer, taking one out of the pool if there isn't one.
+    fn get(&mut self) -> &mut BytesMut {
+        let pool = &self.pool;
+        self.buf.get_or_insert_with(|| pool.get())
+    }
+
+    /// Hands the buffer back to the pool if there's nothing left in it.
+    fn release(&mut self) {
+        let empty = self.buf.as_ref().map_or(false, |buf| buf.is_empty());
+        if empty {
+            self.pool.put(self.buf.take().unwrap());
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            self.pool.put(buf);
+        }
+    }
+}
+
+impl<T: fmt::Debug, D: fmt::Debug> fmt::Debug for PooledFramedRead<T, D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PooledFramedRead")
+         .field("inner", &self.inner)
+         .field("decoder", &self.decoder)
+         .field("pool", &self.buffer.pool)
+         .field("eof", &self.eof)
+         .finish()
+    }
+}
@@ -0,0 +1,48 @@
+//! Buffered asynchronous readers.
+//!
+//! Unlike the rest of the deprecated `io` module, which has moved to
+//! `tokio-io`, `AsyncBufRead` has no equivalent there yet and lives here.
+
+use std::io::{self, BufRead, BufReader, Cursor};
+
+use futures::{Async, Poll};
+use tokio_io::AsyncRead;
+
+/// A buffered asynchronous reader, whose internal buffer can be inspected
+/// and consumed directly.
+///
+/// This is `BufRead` with the same contract `AsyncRead` has for `Read`: if
+/// `fill_buf` would block it returns a "would block" error, and the current
+/// task is scheduled to be notified once more data is available. A parser
+/// can then work on the bytes returned by `fill_buf` in place, and `consume`
+/// exactly as many of them as it used, rather than copying them into a
+/// buffer of its own first.
+///
+/// Note that `fill_buf` only reads more data once the buffer is empty, so a
+/// parser must consume whatever it can parse of the buffer, keeping the
+/// state of a partially parsed item itself.
+pub trait AsyncBufRead: AsyncRead + BufRead {
+    /// Returns the contents of the internal buffer, filling it with more data
+    /// if it's empty.
+    ///
+    /// An empty buffer is returned once the reader has reached EOF. If no
+    /// data is available yet `Async::NotReady` is returned, and the current
+    /// task is scheduled to be notified once there is.
+    fn poll_fill_buf(&mut self) -> Poll<&[u8], io::Error> {
+        match self.fill_buf() {
+            Ok(buf) => Ok(Async::Ready(buf)),
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(Async::NotReady),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl<R: AsyncRead> AsyncBufRead for BufReader<R> {}
+
+impl<T: AsRef<[u8]>> AsyncBufRead for Cursor<T> {}
+
+impl<'a> AsyncBufRead for &'a [u8] {}
+
+impl<T: ?Sized + AsyncBufRead> AsyncBufRead for Box<T> {}
+
+impl<'a, T: ?Sized + AsyncBufRead> AsyncBufRead for &'a mut T {}
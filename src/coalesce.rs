@@ -0,0 +1,144 @@
+//! Coalescing the flushes of a sink.
+//!
+//! Flushing a framed transport writes out whatever it has buffered, so a task
+//! which sends many small frames in response to one event, flushing after
+//! each, pays a write for every frame. Wrapping the transport in a
+//! `Coalesce` gathers the frames sent in one turn of the event loop into a
+//! single flush instead.
+
+use futures::{Async, AsyncSink, Poll, Sink, StartSend, Stream};
+use futures::task::{self, Task};
+
+/// A sink which defers flushing the sink it wraps, so that items sent around
+/// the same time are written out together.
+///
+/// The first time `poll_complete` is called after new items were sent, it
+/// schedules the current task to be polled again and returns
+/// `Async::NotReady` without flushing. Only the next call flushes the inner
+/// sink, by which time any other items produced in the same turn of the
+/// event loop have been sent as well. `poll_complete` never returns
+/// `Async::Ready` while items sent so far haven't been flushed.
+///
+/// The sink can also be corked explicitly, in which case it isn't flushed at
+/// all until it's uncorked or closed. The inner sink may still write items
+/// out of its own accord while corked, as a framed transport does once its
+/// write buffer is full.
+///
+/// If the inner sink is also a stream it's passed through unchanged.
+#[derive(Debug)]
+pub struct Coalesce<S> {
+    inner: S,
+    /// Whether items were sent since the inner sink was last flushed
+    dirty: bool,
+    /// Whether the next `poll_complete` flushes the inner sink
+    deferred: bool,
+    corked: bool,
+    /// The task which found the sink corked, notified when it's uncorked
+    task: Option<Task>,
+}
+
+impl<S: Sink> Coalesce<S> {
+    /// Creates a new sink deferring the flushes of `inner`.
+    pub fn new(inner: S) -> Coalesce<S> {
+        Coalesce {
+            inner: inner,
+            dirty: false,
+            deferred: false,
+            corked: false,
+            task: None,
+        }
+    }
+
+    /// Corks this sink, so that `poll_complete` doesn't flush the inner sink
+    /// and returns `Async::NotReady` while there are items it hasn't flushed.
+    pub fn cork(&mut self) {
+        self.corked = true;
+    }
+
+    /// Uncorks this sink, see `cork`.
+    ///
+    /// The inner sink is flushed by the next call to `poll_complete`, and a
+    /// task which found the sink corked is notified to make it.
+    pub fn uncork(&mut self) {
+        self.corked = false;
+        self.deferred = true;
+        if let Some(task) = self.task.take() {
+            task.notify();
+        }
+    }
+
+    /// Returns whether this sink is corked.
+    pub fn is_corked(&self) -> bool {
+        self.corked
+    }
+
+    /// Returns a reference to the inner sink.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner sink.
+    ///
+    /// Note that items sent directly to the inner sink aren't known to this
+    /// sink, and are only flushed along with those sent through it.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Consumes this sink, returning the inner sink.
+    ///
+    /// Note that items which haven't been flushed are left to the inner sink.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Sink> Sink for Coalesce<S> {
+    type SinkItem = S::SinkItem;
+    type SinkError = S::SinkError;
+
+    fn start_send(&mut self, item: S::SinkItem) -> StartSend<S::SinkItem, S::SinkError> {
+        let res = try!(self.inner.start_send(item));
+        if let AsyncSink::Ready = res {
+            self.dirty = true;
+        }
+        Ok(res)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), S::SinkError> {
+        if self.dirty {
+            if self.corked {
+                trace!("sink corked, not flushing");
+                self.task = Some(task::current());
+                return Ok(Async::NotReady)
+            }
+            if !self.deferred {
+                trace!("deferring flush to the next poll");
+                self.deferred = true;
+                task::current().notify();
+                return Ok(Async::NotReady)
+            }
+        }
+        try_ready!(self.inner.poll_complete());
+        self.dirty = false;
+        self.deferred = false;
+        Ok(Async::Ready(()))
+    }
+
+    fn close(&mut self) -> Poll<(), S::SinkError> {
+        self.corked = false;
+        try_ready!(self.inner.close());
+        self.dirty = false;
+        self.deferred = false;
+        Ok(Async::Ready(()))
+    }
+}
+
+impl<S: Stream> Stream for Coalesce<S> {
+    type Item = S::Item;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, S::Error> {
+        self.inner.poll()
+    }
+}
@@ -0,0 +1,298 @@
+//! Interoperability between futures 0.1 and `std::future`.
+//!
+//! This module is only available when the `std-future` feature of this crate
+//! is enabled. It makes it possible to migrate a code base to
+//! `std::future::Future` piece by piece while everything keeps running on the
+//! same `Core`:
+//!
+//! * `Core::block_on` runs a `std::future::Future` to completion on the event
+//!   loop, just like `Core::run` does for futures 0.1.
+//! * `Compat03As01` wraps a `std::future::Future` producing a `Result` so it
+//!   can be used wherever a futures 0.1 future is expected, for example with
+//!   `Handle::spawn` or any of the I/O types in this crate.
+//! * `Compat01As03` goes the other way, wrapping a futures 0.1 future so it
+//!   can be `.await`ed.
+//! * `waker` and `WakerNotify` convert between futures 0.1 tasks and std
+//!   `Waker`s for code which needs to bridge the two notification systems
+//!   manually.
+//!
+//! The same two adapters also bridge I/O objects, between the `AsyncRead` and
+//! `AsyncWrite` traits of `tokio-io` and those of the `futures-io` crate which
+//! `std::future` code uses. Wrapping a `TcpStream` in `Compat01As03` makes it
+//! usable with, for example, `futures::io::AsyncReadExt`, while wrapping a
+//! `futures-io` object in `Compat03As01` makes it usable with `tokio-io`
+//! combinators and codecs.
+
+use std::fmt;
+use std::future::Future as StdFuture;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll as StdPoll, RawWaker, RawWakerVTable, Waker};
+
+use futures::{task, Async, Future, Poll};
+use futures::executor::{self, Notify, Spawn};
+use futures_io;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Wraps a `std::future::Future` so it can be used as a futures 0.1 `Future`.
+///
+/// The wrapped future must resolve to a `Result`, whose `Ok` and `Err`
+/// variants become the 0.1 future's item and error. The std future is woken
+/// through the futures 0.1 task which is polling this adapter.
+///
+/// Wrapping a `futures-io` reader or writer instead gives a `tokio-io`
+/// `AsyncRead` or `AsyncWrite`, which reports `WouldBlock` while the wrapped
+/// object is pending. Like other `tokio-io` objects it must then be used from
+/// within a futures 0.1 task.
+#[must_use = "futures do nothing unless polled"]
+pub struct Compat03As01<F> {
+    inner: Pin<Box<F>>,
+}
+
+impl<F> Compat03As01<F> {
+    /// Wraps the given `std::future::Future`.
+    pub fn new(future: F) -> Compat03As01<F> {
+        Compat03As01 { inner: Box::pin(future) }
+    }
+}
+
+impl<F, T, E> Future for Compat03As01<F>
+    where F: StdFuture<Output = Result<T, E>>,
+{
+    type Item = T;
+    type Error = E;
+
+    fn poll(&mut self) -> Poll<T, E> {
+        let waker = waker(task::current());
+        let mut cx = Context::from_waker(&waker);
+        match self.inner.as_mut().poll(&mut cx) {
+            StdPoll::Ready(Ok(t)) => Ok(Async::Ready(t)),
+            StdPoll::Ready(Err(e)) => Err(e),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl<F> fmt::Debug for Compat03As01<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Compat03As01").finish()
+    }
+}
+
+/// Adapter used by `Core::block_on` to run a std future with any output.
+pub(crate) struct BlockOn<F> {
+    inner: Pin<Box<F>>,
+}
+
+impl<F> BlockOn<F> {
+    pub(crate) fn new(future: F) -> BlockOn<F> {
+        BlockOn { inner: Box::pin(future) }
+    }
+}
+
+impl<F: StdFuture> Future for BlockOn<F> {
+    type Item = F::Output;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<F::Output, ()> {
+        let waker = waker(task::current());
+        let mut cx = Context::from_waker(&waker);
+        match self.inner.as_mut().poll(&mut cx) {
+            StdPoll::Ready(output) => Ok(Async::Ready(output)),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+/// Wraps a futures 0.1 `Future` so it can be used as a `std::future::Future`.
+///
+/// The resulting future resolves to a `Result` of the wrapped future's item
+/// and error. The 0.1 future is polled inside a task which wakes the `Waker`
+/// of the most recent poll when notified, so it can be `.await`ed from any
+/// executor, though I/O objects from this crate still need a running `Core`
+/// to make progress.
+///
+/// Wrapping a `tokio-io` reader or writer instead gives a `futures-io`
+/// `AsyncRead` or `AsyncWrite`, on which `WouldBlock` becomes `Pending`.
+#[must_use = "futures do nothing unless polled"]
+pub struct Compat01As03<F> {
+    inner: Spawn<F>,
+}
+
+impl<F> Compat01As03<F> {
+    /// Wraps the given futures 0.1 future.
+    pub fn new(future: F) -> Compat01As03<F> {
+        Compat01As03 { inner: executor::spawn(future) }
+    }
+}
+
+// The 0.1 future is never pinned, it's free to move just like it was before
+// being wrapped.
+impl<F> Unpin for Compat01As03<F> {}
+
+impl<F: Future> StdFuture for Compat01As03<F> {
+    type Output = Result<F::Item, F::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Self::Output> {
+        let notify = Arc::new(WakerNotify::new(cx.waker().clone()));
+        match self.inner.poll_future_notify(&notify, 0) {
+            Ok(Async::Ready(t)) => StdPoll::Ready(Ok(t)),
+            Ok(Async::NotReady) => StdPoll::Pending,
+            Err(e) => StdPoll::Ready(Err(e)),
+        }
+    }
+}
+
+impl<F> fmt::Debug for Compat01As03<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Compat01As03").finish()
+    }
+}
+
+impl<R: AsyncRead> futures_io::AsyncRead for Compat01As03<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context, buf: &mut [u8])
+                 -> StdPoll<io::Result<usize>> {
+        let notify = Arc::new(WakerNotify::new(cx.waker().clone()));
+        nb_to_std(self.inner.poll_fn_notify(&notify, 0, |io| io.read(buf)))
+    }
+}
+
+impl<W: AsyncWrite> futures_io::AsyncWrite for Compat01As03<W> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context, buf: &[u8])
+                  -> StdPoll<io::Result<usize>> {
+        let notify = Arc::new(WakerNotify::new(cx.waker().clone()));
+        nb_to_std(self.inner.poll_fn_notify(&notify, 0, |io| io.write(buf)))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<io::Result<()>> {
+        let notify = Arc::new(WakerNotify::new(cx.waker().clone()));
+        nb_to_std(self.inner.poll_fn_notify(&notify, 0, |io| io.flush()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<io::Result<()>> {
+        let notify = Arc::new(WakerNotify::new(cx.waker().clone()));
+        match self.inner.poll_fn_notify(&notify, 0, |io| io.shutdown()) {
+            Ok(Async::Ready(())) => StdPoll::Ready(Ok(())),
+            Ok(Async::NotReady) => StdPoll::Pending,
+            Err(e) => StdPoll::Ready(Err(e)),
+        }
+    }
+}
+
+fn nb_to_std<T>(res: io::Result<T>) -> StdPoll<io::Result<T>> {
+    match res {
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => StdPoll::Pending,
+        res => StdPoll::Ready(res),
+    }
+}
+
+impl<R: futures_io::AsyncRead> Read for Compat03As01<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let waker = waker(task::current());
+        let mut cx = Context::from_waker(&waker);
+        std_to_nb(self.inner.as_mut().poll_read(&mut cx, buf))
+    }
+}
+
+impl<R: futures_io::AsyncRead> AsyncRead for Compat03As01<R> {}
+
+impl<W: futures_io::AsyncWrite> Write for Compat03As01<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let waker = waker(task::current());
+        let mut cx = Context::from_waker(&waker);
+        std_to_nb(self.inner.as_mut().poll_write(&mut cx, buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let waker = waker(task::current());
+        let mut cx = Context::from_waker(&waker);
+        std_to_nb(self.inner.as_mut().poll_flush(&mut cx))
+    }
+}
+
+impl<W: futures_io::AsyncWrite> AsyncWrite for Compat03As01<W> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        let waker = waker(task::current());
+        let mut cx = Context::from_waker(&waker);
+        match self.inner.as_mut().poll_close(&mut cx) {
+            StdPoll::Ready(Ok(())) => Ok(Async::Ready(())),
+            StdPoll::Ready(Err(e)) => Err(e),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+fn std_to_nb<T>(poll: StdPoll<io::Result<T>>) -> io::Result<T> {
+    match poll {
+        StdPoll::Ready(res) => res,
+        StdPoll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+    }
+}
+
+/// A futures 0.1 `Notify` implementation which wakes a std `Waker`.
+///
+/// This can be used with the `poll_*_notify` methods of
+/// `futures::executor::Spawn` to poll futures 0.1 code from within a
+/// `std::future::Future`.
+pub struct WakerNotify {
+    waker: Waker,
+}
+
+impl WakerNotify {
+    /// Creates a new `Notify` handle which wakes `waker` when notified.
+    pub fn new(waker: Waker) -> WakerNotify {
+        WakerNotify { waker: waker }
+    }
+}
+
+impl Notify for WakerNotify {
+    fn notify(&self, _id: usize) {
+        self.waker.wake_by_ref();
+    }
+}
+
+impl fmt::Debug for WakerNotify {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WakerNotify")
+         .field("waker", &self.waker)
+         .finish()
+    }
+}
+
+/// Creates a std `Waker` which notifies the given futures 0.1 task when woken.
+///
+/// This can be used to poll a `std::future::Future` from within a futures 0.1
+/// `poll` function, passing it a waker for `futures::task::current()`.
+pub fn waker(task: task::Task) -> Waker {
+    let raw = into_raw(Arc::new(task));
+    unsafe { Waker::from_raw(raw) }
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop_raw);
+
+fn into_raw(task: Arc<task::Task>) -> RawWaker {
+    RawWaker::new(Arc::into_raw(task) as *const (), &VTABLE)
+}
+
+unsafe fn clone(ptr: *const ()) -> RawWaker {
+    let task = Arc::from_raw(ptr as *const task::Task);
+    let clone = task.clone();
+    mem::forget(task);
+    into_raw(clone)
+}
+
+unsafe fn wake(ptr: *const ()) {
+    let task = Arc::from_raw(ptr as *const task::Task);
+    task.notify();
+}
+
+unsafe fn wake_by_ref(ptr: *const ()) {
+    let task = &*(ptr as *const task::Task);
+    task.notify();
+}
+
+unsafe fn drop_raw(ptr: *const ()) {
+    drop(Arc::from_raw(ptr as *const task::Task));
+}
@@ -97,8 +97,12 @@
 extern crate bytes;
 #[macro_use]
 extern crate futures;
+#[cfg(feature = "std-future")]
+extern crate futures_io;
 extern crate iovec;
+extern crate libc;
 extern crate mio;
+extern crate net2;
 extern crate tokio;
 extern crate tokio_executor;
 extern crate tokio_io;
@@ -117,5 +121,11 @@ pub mod io;
 
 #[doc(hidden)]
 pub mod channel;
+pub mod buf_pool;
+pub mod buf_read;
+pub mod coalesce;
 pub mod net;
 pub mod reactor;
+pub mod testing;
+#[cfg(feature = "std-future")]
+pub mod compat;
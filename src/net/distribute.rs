@@ -0,0 +1,204 @@
+use std::fmt;
+use std::io;
+use std::net::{self, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::{Future, IntoFuture, Poll};
+use futures::future::{self, Either, FutureResult};
+
+use net::{TcpListener, TcpStream};
+use reactor::{Error, Handle, Remote};
+
+/// Strategy used by `Distribute` to pick the event loop which receives each
+/// accepted connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Balance {
+    /// Hand connections to each event loop in turn.
+    RoundRobin,
+
+    /// Hand each connection to the event loop with the fewest connections
+    /// that are still being processed, preferring earlier event loops when
+    /// several are equally loaded.
+    LeastLoaded,
+}
+
+/// Future returned by `TcpListener::distribute`, which accepts connections on
+/// one event loop and processes them on a set of others.
+///
+/// An event loop which can no longer take connections, because it has been
+/// dropped, is skipped from then on, and connections are handed to the
+/// others instead.
+///
+/// This future never resolves successfully; it only completes if accepting a
+/// connection fails, or with an error converting to `reactor::Error::ReactorGone`
+/// once none of the event loops are left.
+#[must_use = "futures do nothing unless polled"]
+pub struct Distribute<F> {
+    listener: TcpListener,
+    shards: Vec<Shard>,
+    balance: Balance,
+    next: usize,
+    f: Arc<F>,
+}
+
+struct Shard {
+    remote: Remote,
+    load: Arc<AtomicUsize>,
+    // Set once handing a connection to the event loop failed
+    dead: bool,
+}
+
+/// Keeps a connection counted against the load of its event loop until it's
+/// dropped.
+struct LoadGuard(Arc<AtomicUsize>);
+
+struct Tracked<F> {
+    inner: F,
+    _guard: LoadGuard,
+}
+
+pub fn new<F>(listener: TcpListener,
+              remotes: Vec<Remote>,
+              balance: Balance,
+              f: F) -> Distribute<F> {
+    assert!(!remotes.is_empty(), "cannot distribute connections over no event loops");
+    Distribute {
+        listener: listener,
+        shards: remotes.into_iter().map(|remote| {
+            Shard { remote: remote, load: Arc::new(AtomicUsize::new(0)), dead: false }
+        }).collect(),
+        balance: balance,
+        next: 0,
+        f: Arc::new(f),
+    }
+}
+
+impl<F> Distribute<F> {
+    /// Returns the number of connections each event loop is currently
+    /// processing, in the order the event loops were provided.
+    pub fn loads(&self) -> Vec<usize> {
+        self.shards.iter().map(|s| s.load.load(Ordering::SeqCst)).collect()
+    }
+
+    /// Picks the event loop to hand the next connection to, if any is left.
+    fn pick(&mut self) -> Option<usize> {
+        match self.balance {
+            Balance::RoundRobin => {
+                for _ in 0..self.shards.len() {
+                    let i = self.next;
+                    self.next = (self.next + 1) % self.shards.len();
+                    if !self.shards[i].dead {
+                        return Some(i)
+                    }
+                }
+                None
+            }
+            Balance::LeastLoaded => {
+                let mut best = None;
+                let mut best_load = usize::max_value();
+                for (i, shard) in self.shards.iter().enumerate() {
+                    let load = shard.load.load(Ordering::SeqCst);
+                    if !shard.dead && load < best_load {
+                        best = Some(i);
+                        best_load = load;
+                    }
+                }
+                best
+            }
+        }
+    }
+}
+
+impl<F, R> Distribute<F>
+    where F: Fn(TcpStream, SocketAddr, &Handle) -> R + Send + Sync + 'static,
+          R: IntoFuture<Item = (), Error = ()>,
+          R::Future: 'static,
+{
+    /// Spawns the processing of the connection in `slot` onto event loop
+    /// `i`. If that fails the connection is left in `slot`.
+    fn hand_off(&self,
+                i: usize,
+                slot: &Arc<Mutex<Option<net::TcpStream>>>,
+                addr: SocketAddr) -> Result<(), Error> {
+        let shard = &self.shards[i];
+        shard.load.fetch_add(1, Ordering::SeqCst);
+        let guard = LoadGuard(shard.load.clone());
+        let f = self.f.clone();
+        let slot = slot.clone();
+
+        shard.remote.try_spawn(move |handle| -> Either<Tracked<R::Future>, FutureResult<(), ()>> {
+            let stream = match slot.lock().unwrap().take() {
+                Some(stream) => stream,
+                None => return Either::B(future::ok(())),
+            };
+            match TcpStream::from_stream(stream, handle) {
+                Ok(stream) => {
+                    Either::A(Tracked {
+                        inner: f(stream, addr, handle).into_future(),
+                        _guard: guard,
+                    })
+                }
+                Err(e) => {
+                    debug!("failed to register connection from {}: {}", addr, e);
+                    Either::B(future::ok(()))
+                }
+            }
+        })
+    }
+}
+
+impl<F, R> Future for Distribute<F>
+    where F: Fn(TcpStream, SocketAddr, &Handle) -> R + Send + Sync + 'static,
+          R: IntoFuture<Item = (), Error = ()>,
+          R::Future: 'static,
+{
+    type Item = ();
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(), io::Error> {
+        loop {
+            let (stream, addr) = try_nb!(self.listener.accept_std());
+            let slot = Arc::new(Mutex::new(Some(stream)));
+            loop {
+                let i = match self.pick() {
+                    Some(i) => i,
+                    None => return Err(Error::ReactorGone.into()),
+                };
+                match self.hand_off(i, &slot, addr) {
+                    Ok(()) => break,
+                    Err(e) => {
+                        debug!("event loop {} can't take connections: {}", i, e);
+                        self.shards[i].dead = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<F> fmt::Debug for Distribute<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Distribute")
+         .field("listener", &self.listener)
+         .field("balance", &self.balance)
+         .field("loads", &self.loads())
+         .finish()
+    }
+}
+
+impl<F: Future<Item = (), Error = ()>> Future for Tracked<F> {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        self.inner.poll()
+    }
+}
+
+impl Drop for LoadGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
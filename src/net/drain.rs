@@ -0,0 +1,228 @@
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures::{task, Async, Future, Poll, Stream};
+use futures::task::Task;
+
+use net::{Incoming, TcpListener, TcpStream};
+use reactor::{Handle, Timeout};
+
+/// Stream returned by `TcpListener::incoming_guarded`, yielding each accepted
+/// connection along with a guard tracking it.
+///
+/// Once the server is to shut down, `drain` stops accepting connections and
+/// waits for those already accepted to close. While the stream is driven by
+/// another future, such as `for_each`, the same is done through a
+/// `DrainHandle` taken out beforehand.
+#[must_use = "streams do nothing unless polled"]
+pub struct GuardedIncoming {
+    inner: Option<Incoming>,
+    tracker: Arc<Mutex<Tracker>>,
+}
+
+/// A handle to a `GuardedIncoming` stream returned by `drain_handle`, which
+/// can start draining while the stream itself is owned elsewhere.
+///
+/// Handles are cheaply cloneable, and all of them refer to the same stream.
+#[derive(Clone)]
+pub struct DrainHandle {
+    tracker: Arc<Mutex<Tracker>>,
+}
+
+/// Handed out with each connection accepted by `GuardedIncoming`, which
+/// counts as open until this is dropped.
+///
+/// The guard is normally moved into the future handling the connection, so
+/// that it's dropped along with the stream.
+pub struct ConnectionGuard {
+    tracker: Arc<Mutex<Tracker>>,
+}
+
+/// Future returned by `GuardedIncoming::drain` and `DrainHandle::drain`, which resolves once all
+/// connections accepted by the listener have closed or its deadline has
+/// passed.
+///
+/// It resolves to the number of connections which were still open, which is
+/// zero unless the deadline passed.
+#[must_use = "futures do nothing unless polled"]
+pub struct Drain {
+    tracker: Arc<Mutex<Tracker>>,
+    deadline: Timeout,
+}
+
+struct Tracker {
+    open: usize,
+    /// Set once draining has started, after which no more connections are
+    /// accepted
+    stopped: bool,
+    /// The task polling the `GuardedIncoming` stream
+    accept: Option<Task>,
+    /// The tasks polling `Drain` futures
+    drains: Vec<Task>,
+}
+
+pub fn new(listener: TcpListener) -> GuardedIncoming {
+    GuardedIncoming {
+        inner: Some(listener.incoming()),
+        tracker: Arc::new(Mutex::new(Tracker {
+            open: 0,
+            stopped: false,
+            accept: None,
+            drains: Vec::new(),
+        })),
+    }
+}
+
+/// Marks the stream as draining, waking up the task polling it so that it
+/// stops accepting, and returns a future waiting for its connections.
+fn drain(tracker: &Arc<Mutex<Tracker>>, deadline: Instant, handle: &Handle)
+         -> io::Result<Drain> {
+    let deadline = try!(Timeout::new_at(deadline, handle));
+    let accept = {
+        let mut tracker = tracker.lock().unwrap();
+        tracker.stopped = true;
+        tracker.accept.take()
+    };
+    if let Some(task) = accept {
+        task.notify();
+    }
+    Ok(Drain {
+        tracker: tracker.clone(),
+        deadline: deadline,
+    })
+}
+
+impl GuardedIncoming {
+    /// Returns the number of accepted connections whose guards are still
+    /// alive.
+    pub fn open_connections(&self) -> usize {
+        self.tracker.lock().unwrap().open
+    }
+
+    /// Stops accepting connections, returning a future which resolves once
+    /// all connections accepted so far have closed, or once `deadline` has
+    /// passed.
+    ///
+    /// The listening socket is closed right away, so new connections are
+    /// refused while the existing ones finish.
+    pub fn drain(self, deadline: Instant, handle: &Handle) -> io::Result<Drain> {
+        drain(&self.tracker, deadline, handle)
+    }
+
+    /// Returns a handle which can drain this stream later on, once it's been
+    /// handed to a future driving it.
+    pub fn drain_handle(&self) -> DrainHandle {
+        DrainHandle { tracker: self.tracker.clone() }
+    }
+}
+
+impl DrainHandle {
+    /// Returns the number of connections accepted by the stream whose guards
+    /// are still alive.
+    pub fn open_connections(&self) -> usize {
+        self.tracker.lock().unwrap().open
+    }
+
+    /// Stops the stream from accepting connections, returning a future which
+    /// resolves once all connections accepted so far have closed, or once
+    /// `deadline` has passed.
+    ///
+    /// The stream ends the next time it's polled, which the task polling it
+    /// is notified to do, and the listening socket is closed once the stream
+    /// is dropped. When it's driven by `for_each` this happens as soon as the
+    /// `for_each` future completes.
+    pub fn drain(&self, deadline: Instant, handle: &Handle) -> io::Result<Drain> {
+        drain(&self.tracker, deadline, handle)
+    }
+}
+
+impl Stream for GuardedIncoming {
+    type Item = (TcpStream, SocketAddr, ConnectionGuard);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+        {
+            let mut tracker = self.tracker.lock().unwrap();
+            if tracker.stopped {
+                tracker.accept = None;
+                self.inner = None;
+                return Ok(Async::Ready(None))
+            }
+            tracker.accept = Some(task::current());
+        }
+        let (stream, addr) = match self.inner.as_mut() {
+            Some(inner) => match try_ready!(inner.poll()) {
+                Some(conn) => conn,
+                None => return Ok(Async::Ready(None)),
+            },
+            None => return Ok(Async::Ready(None)),
+        };
+        self.tracker.lock().unwrap().open += 1;
+        let guard = ConnectionGuard { tracker: self.tracker.clone() };
+        Ok(Async::Ready(Some((stream, addr, guard))))
+    }
+}
+
+impl fmt::Debug for GuardedIncoming {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GuardedIncoming")
+         .field("open_connections", &self.open_connections())
+         .finish()
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut tracker = self.tracker.lock().unwrap();
+        tracker.open -= 1;
+        if tracker.open == 0 {
+            for task in tracker.drains.drain(..) {
+                task.notify();
+            }
+        }
+    }
+}
+
+impl fmt::Debug for DrainHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DrainHandle")
+         .field("open_connections", &self.open_connections())
+         .finish()
+    }
+}
+
+impl fmt::Debug for ConnectionGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConnectionGuard").finish()
+    }
+}
+
+impl Future for Drain {
+    type Item = usize;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<usize, io::Error> {
+        {
+            let mut tracker = self.tracker.lock().unwrap();
+            if tracker.open == 0 {
+                return Ok(Async::Ready(0))
+            }
+            if !tracker.drains.iter().any(|task| task.will_notify_current()) {
+                tracker.drains.push(task::current());
+            }
+        }
+        try_ready!(self.deadline.poll());
+        Ok(Async::Ready(self.tracker.lock().unwrap().open))
+    }
+}
+
+impl fmt::Debug for Drain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Drain")
+         .field("open_connections", &self.tracker.lock().unwrap().open)
+         .finish()
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+
+use futures::{Async, IntoFuture, Poll, Stream};
+use futures::stream::FuturesUnordered;
+
+use net::{Incoming, TcpStream};
+
+/// Stream returned by `Incoming::hook`, which runs a per-connection hook on
+/// each accepted socket before yielding it.
+///
+/// Up to `max_in_flight` hooks run concurrently, and connections are yielded
+/// in the order their hooks complete. Connections whose hook fails are logged
+/// and dropped, while errors accepting connections are returned from the
+/// stream.
+#[must_use = "streams do nothing unless polled"]
+pub struct Hooked<F, R> {
+    incoming: Incoming,
+    f: F,
+    in_flight: FuturesUnordered<R>,
+    max_in_flight: usize,
+    done: bool,
+}
+
+pub fn new<F, R>(incoming: Incoming, max_in_flight: usize, f: F) -> Hooked<F, R::Future>
+    where F: FnMut(TcpStream, SocketAddr) -> R,
+          R: IntoFuture<Error = io::Error>,
+{
+    assert!(max_in_flight > 0, "at least one hook must be allowed to run");
+    Hooked {
+        incoming: incoming,
+        f: f,
+        in_flight: FuturesUnordered::new(),
+        max_in_flight: max_in_flight,
+        done: false,
+    }
+}
+
+impl<F, R> Hooked<F, R> {
+    /// Returns the number of accepted connections whose hook hasn't completed
+    /// yet.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+}
+
+impl<F, R> Stream for Hooked<F, R::Future>
+    where F: FnMut(TcpStream, SocketAddr) -> R,
+          R: IntoFuture<Error = io::Error>,
+{
+    type Item = R::Item;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<R::Item>, io::Error> {
+        loop {
+            while !self.done && self.in_flight.len() < self.max_in_flight {
+                match try!(self.incoming.poll()) {
+                    Async::Ready(Some((stream, addr))) => {
+                        self.in_flight.push((self.f)(stream, addr).into_future());
+                    }
+                    Async::Ready(None) => self.done = true,
+                    Async::NotReady => break,
+                }
+            }
+
+            match self.in_flight.poll() {
+                Ok(Async::Ready(Some(item))) => return Ok(Async::Ready(Some(item))),
+                Ok(Async::Ready(None)) => {
+                    return Ok(if self.done { Async::Ready(None) } else { Async::NotReady })
+                }
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => {
+                    // A slot has been freed up, so go back to accepting.
+                    debug!("connection hook failed: {}", e);
+                }
+            }
+        }
+    }
+}
+
+impl<F, R> fmt::Debug for Hooked<F, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Hooked")
+         .field("in_flight", &self.in_flight.len())
+         .field("max_in_flight", &self.max_in_flight)
+         .finish()
+    }
+}
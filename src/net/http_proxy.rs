@@ -0,0 +1,159 @@
+//! A client for HTTP proxies supporting the `CONNECT` method.
+//!
+//! `connect` asks an HTTP proxy to open a tunnel to a host, as described in
+//! RFC 7231, and resolves to a `TcpStream` which is connected to that host
+//! through the proxy once the proxy has accepted the request. Proxies which
+//! require authentication can be used with `connect_with_basic_auth`.
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+
+use futures::{Future, Poll};
+use futures::future::{self, Loop};
+use tokio_io::io::{read_exact, write_all};
+
+use net::TcpStream;
+use reactor::Handle;
+
+/// The longest response header accepted from a proxy.
+const MAX_HEADER_LEN: usize = 8 * 1024;
+
+/// Future returned by `connect` and `connect_with_basic_auth`, which
+/// resolves to a stream tunnelled to the target through the proxy.
+#[must_use = "futures do nothing unless polled"]
+pub struct Connect {
+    inner: Box<Future<Item = TcpStream, Error = io::Error>>,
+}
+
+impl Future for Connect {
+    type Item = TcpStream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<TcpStream, io::Error> {
+        self.inner.poll()
+    }
+}
+
+impl fmt::Debug for Connect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Connect").finish()
+    }
+}
+
+/// Opens a tunnel to `host` and `port` through the HTTP proxy at `proxy`.
+///
+/// `host` can be a domain name, which the proxy resolves, or an IP address.
+/// The returned future fails if the proxy answers the request with anything
+/// other than a 2xx status, with an error of kind `PermissionDenied` if the
+/// proxy requires authentication.
+pub fn connect(proxy: &SocketAddr, host: &str, port: u16, handle: &Handle) -> Connect {
+    new(proxy, host, port, None, handle)
+}
+
+/// Opens a tunnel to `host` and `port` through the HTTP proxy at `proxy`,
+/// authenticating with the given username and password using the basic
+/// authentication scheme.
+///
+/// Note that the credentials are sent in the clear unless the connection to
+/// the proxy is otherwise protected.
+pub fn connect_with_basic_auth(proxy: &SocketAddr,
+                               host: &str,
+                               port: u16,
+                               username: &str,
+                               password: &str,
+                               handle: &Handle) -> Connect {
+    let credentials = base64(format!("{}:{}", username, password).as_bytes());
+    new(proxy, host, port, Some(credentials), handle)
+}
+
+fn new(proxy: &SocketAddr,
+       host: &str,
+       port: u16,
+       credentials: Option<String>,
+       handle: &Handle) -> Connect {
+    if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c.is_control()) {
+        let e = io::Error::new(io::ErrorKind::InvalidInput, "invalid proxy target host");
+        return Connect { inner: Box::new(future::err(e)) }
+    }
+
+    // IPv6 addresses have to be enclosed in brackets to be told apart from
+    // the port.
+    let authority = if host.contains(':') && !host.starts_with('[') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if let Some(credentials) = credentials {
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", credentials));
+    }
+    request.push_str("\r\n");
+
+    let tunnel = TcpStream::connect(proxy, handle).and_then(move |stream| {
+        write_all(stream, request.into_bytes())
+    }).and_then(|(stream, _)| {
+        // The header is read a byte at a time so none of the data following
+        // it, which comes from the target, is consumed.
+        future::loop_fn((stream, Vec::new()), |(stream, mut header)| {
+            read_exact(stream, [0; 1]).and_then(move |(stream, byte)| {
+                header.push(byte[0]);
+                if header.ends_with(b"\r\n\r\n") {
+                    Ok(Loop::Break((stream, header)))
+                } else if header.len() >= MAX_HEADER_LEN {
+                    Err(io::Error::new(io::ErrorKind::InvalidData,
+                                       "proxy response header too long"))
+                } else {
+                    Ok(Loop::Continue((stream, header)))
+                }
+            })
+        })
+    }).and_then(|(stream, header)| {
+        try!(check_status(&header));
+        Ok(stream)
+    });
+
+    Connect { inner: Box::new(tunnel) }
+}
+
+fn check_status(header: &[u8]) -> io::Result<()> {
+    let line = header.split(|&b| b == b'\r').next().unwrap_or(&[]);
+    let line = String::from_utf8_lossy(line);
+    let mut parts = line.splitn(3, ' ');
+    let version = parts.next().unwrap_or("");
+    let status = parts.next().and_then(|s| s.parse::<u16>().ok());
+
+    match status {
+        _ if !version.starts_with("HTTP/1.") => {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "invalid proxy response"))
+        }
+        Some(200..=299) => Ok(()),
+        Some(407) => {
+            Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                               format!("proxy requires authentication: {}", line)))
+        }
+        Some(_) => {
+            Err(io::Error::new(io::ErrorKind::Other,
+                               format!("proxy refused to connect: {}", line)))
+        }
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid proxy response")),
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    const CHARS: &'static [u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0],
+                 if chunk.len() > 1 { chunk[1] } else { 0 },
+                 if chunk.len() > 2 { chunk[2] } else { 0 }];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        out.push(CHARS[n >> 18 & 63] as char);
+        out.push(CHARS[n >> 12 & 63] as char);
+        out.push(if chunk.len() > 1 { CHARS[n >> 6 & 63] as char } else { '=' });
+        out.push(if chunk.len() > 2 { CHARS[n & 63] as char } else { '=' });
+    }
+    out
+}
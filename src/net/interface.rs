@@ -0,0 +1,477 @@
+use std::io;
+use std::net::IpAddr;
+
+use futures::{Poll, Stream};
+
+use reactor::Handle;
+
+/// A network interface of the local system, as returned by `interfaces`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Interface {
+    name: String,
+    index: u32,
+    flags: u32,
+    addresses: Vec<IpAddr>,
+}
+
+impl Interface {
+    /// Returns the name of this interface, such as `eth0`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the index the operating system uses to identify this
+    /// interface.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns whether this interface has been administratively enabled.
+    pub fn is_up(&self) -> bool {
+        self.flags & sys::IFF_UP != 0
+    }
+
+    /// Returns whether this interface has a working link, for example
+    /// because a cable is plugged in or a wireless network is associated.
+    pub fn is_running(&self) -> bool {
+        self.flags & sys::IFF_RUNNING != 0
+    }
+
+    /// Returns whether this is a loopback interface.
+    pub fn is_loopback(&self) -> bool {
+        self.flags & sys::IFF_LOOPBACK != 0
+    }
+
+    /// Returns the IPv4 and IPv6 addresses assigned to this interface.
+    pub fn addresses(&self) -> &[IpAddr] {
+        &self.addresses
+    }
+}
+
+/// Lists the network interfaces of the local system along with their
+/// addresses.
+///
+/// Interfaces without any addresses are included as well. This fails with an
+/// error of kind `Other` on platforms which don't support listing interfaces.
+pub fn interfaces() -> io::Result<Vec<Interface>> {
+    sys::interfaces()
+}
+
+/// A change to the network interfaces of the local system, as reported by
+/// `InterfaceMonitor`.
+///
+/// Interfaces are identified by their index, which can be looked up in the
+/// list returned by `interfaces`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InterfaceChange {
+    /// The interface with the given index became both up and running.
+    Up(u32),
+
+    /// The interface with the given index stopped being up and running, or
+    /// was removed.
+    Down(u32),
+
+    /// An address was assigned to the interface with the given index.
+    AddressAdded(u32, IpAddr),
+
+    /// An address was removed from the interface with the given index.
+    AddressRemoved(u32, IpAddr),
+}
+
+/// A stream of changes to the network interfaces of the local system.
+///
+/// The stream yields an event whenever an interface goes up or down and
+/// whenever an address is added to or removed from an interface, from the
+/// moment it's created. The state at that moment is what `interfaces`
+/// returns, so the two can be combined to keep track of which interfaces are
+/// usable, for example to find out whether the system has network
+/// connectivity at all.
+///
+/// If interfaces change faster than events are read from the stream the
+/// kernel may drop notifications, in which case the stream returns an error
+/// and keeps going. Callers should then call `interfaces` again.
+///
+/// Interface monitoring is supported on Linux, where it's built on
+/// rtnetlink, and on macOS and FreeBSD, where it's built on routing sockets.
+#[must_use = "streams do nothing unless polled"]
+pub struct InterfaceMonitor {
+    inner: monitor::InterfaceMonitor,
+}
+
+impl InterfaceMonitor {
+    /// Starts monitoring network interfaces, registering with the event loop
+    /// that `handle` is associated with.
+    ///
+    /// This fails with an error of kind `Other` on platforms which don't
+    /// support interface monitoring.
+    pub fn new(handle: &Handle) -> io::Result<InterfaceMonitor> {
+        Ok(InterfaceMonitor { inner: try!(monitor::InterfaceMonitor::new(handle)) })
+    }
+}
+
+impl Stream for InterfaceMonitor {
+    type Item = InterfaceChange;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<InterfaceChange>, io::Error> {
+        self.inner.poll()
+    }
+}
+
+impl ::std::fmt::Debug for InterfaceMonitor {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("InterfaceMonitor").finish()
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::CStr;
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::ptr;
+
+    use libc;
+
+    use super::Interface;
+
+    pub const IFF_UP: u32 = libc::IFF_UP as u32;
+    pub const IFF_RUNNING: u32 = libc::IFF_RUNNING as u32;
+    pub const IFF_LOOPBACK: u32 = libc::IFF_LOOPBACK as u32;
+
+    pub fn interfaces() -> io::Result<Vec<Interface>> {
+        let mut addrs = ptr::null_mut();
+        if unsafe { libc::getifaddrs(&mut addrs) } < 0 {
+            return Err(io::Error::last_os_error())
+        }
+
+        // getifaddrs returns one entry per address, plus one per interface
+        // for its link layer on some platforms, so group them by name.
+        let mut list: Vec<Interface> = Vec::new();
+        let mut cur = addrs;
+        while !cur.is_null() {
+            let ifa = unsafe { &*cur };
+            cur = ifa.ifa_next;
+
+            let name = unsafe { CStr::from_ptr(ifa.ifa_name) };
+            let pos = list.iter().position(|i| i.name.as_bytes() == name.to_bytes());
+            let interface = match pos {
+                Some(pos) => &mut list[pos],
+                None => {
+                    list.push(Interface {
+                        name: name.to_string_lossy().into_owned(),
+                        index: unsafe { libc::if_nametoindex(ifa.ifa_name) },
+                        flags: ifa.ifa_flags as u32,
+                        addresses: Vec::new(),
+                    });
+                    list.last_mut().unwrap()
+                }
+            };
+            if let Some(addr) = unsafe { ip_addr(ifa.ifa_addr) } {
+                interface.addresses.push(addr);
+            }
+        }
+
+        unsafe {
+            libc::freeifaddrs(addrs);
+        }
+        Ok(list)
+    }
+
+    unsafe fn ip_addr(addr: *const libc::sockaddr) -> Option<IpAddr> {
+        if addr.is_null() {
+            return None
+        }
+        match (*addr).sa_family as i32 {
+            libc::AF_INET => {
+                let addr = &*(addr as *const libc::sockaddr_in);
+                Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr))))
+            }
+            libc::AF_INET6 => {
+                let addr = &*(addr as *const libc::sockaddr_in6);
+                Some(IpAddr::V6(Ipv6Addr::from(addr.sin6_addr.s6_addr)))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use std::io;
+
+    use super::Interface;
+
+    pub const IFF_UP: u32 = 0x1;
+    pub const IFF_RUNNING: u32 = 0x2;
+    pub const IFF_LOOPBACK: u32 = 0x4;
+
+    pub fn interfaces() -> io::Result<Vec<Interface>> {
+        Err(io::Error::new(io::ErrorKind::Other,
+                           "listing interfaces is not supported on this platform"))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod monitor {
+    use std::collections::{HashMap, HashSet, VecDeque};
+    use std::io::{self, Read};
+    use std::net::IpAddr;
+
+    use futures::{Async, Poll};
+
+    use net::netlink::{self, RTM_NEWLINK, RTM_DELLINK, RTM_NEWADDR, RTM_DELADDR};
+    use net::netlink::{RTMGRP_LINK, RTMGRP_IPV4_IFADDR, RTMGRP_IPV6_IFADDR};
+    use reactor::{Handle, PollEvented2};
+    use super::{sys, InterfaceChange};
+
+    const IFA_ADDRESS: u16 = 1;
+    const IFA_LOCAL: u16 = 2;
+
+    pub struct InterfaceMonitor {
+        io: PollEvented2<netlink::Socket>,
+        buf: Vec<u8>,
+        pending: VecDeque<InterfaceChange>,
+        // The kernel sends a message whenever anything about a link or
+        // address changes, so the last known state is kept around to only
+        // report actual changes.
+        up: HashMap<u32, bool>,
+        addresses: HashSet<(u32, IpAddr)>,
+    }
+
+    impl InterfaceMonitor {
+        pub fn new(handle: &Handle) -> io::Result<InterfaceMonitor> {
+            let groups = RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR;
+            let socket = try!(netlink::Socket::new(groups));
+
+            // Subscribe before taking the snapshot so changes in between
+            // aren't missed.
+            let mut up = HashMap::new();
+            let mut addresses = HashSet::new();
+            for interface in try!(sys::interfaces()) {
+                up.insert(interface.index(), is_up(interface.flags));
+                for addr in interface.addresses() {
+                    addresses.insert((interface.index(), *addr));
+                }
+            }
+
+            Ok(InterfaceMonitor {
+                io: try!(PollEvented2::new_with_handle(socket, handle)),
+                buf: vec![0; netlink::RECV_BUF_LEN],
+                pending: VecDeque::new(),
+                up: up,
+                addresses: addresses,
+            })
+        }
+
+        pub fn poll(&mut self) -> Poll<Option<InterfaceChange>, io::Error> {
+            loop {
+                if let Some(change) = self.pending.pop_front() {
+                    return Ok(Async::Ready(Some(change)))
+                }
+
+                let n = try_nb!(self.io.read(&mut self.buf));
+                for msg in netlink::messages(&self.buf[..n]) {
+                    match msg.kind {
+                        RTM_NEWLINK | RTM_DELLINK => {
+                            if let Some((index, flags)) = parse_link(msg.payload) {
+                                let now = msg.kind == RTM_NEWLINK && is_up(flags);
+                                let before = if now {
+                                    self.up.insert(index, true)
+                                } else {
+                                    self.up.remove(&index)
+                                };
+                                match (before.unwrap_or(false), now) {
+                                    (false, true) => {
+                                        self.pending.push_back(InterfaceChange::Up(index))
+                                    }
+                                    (true, false) => {
+                                        self.pending.push_back(InterfaceChange::Down(index))
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        RTM_NEWADDR => {
+                            if let Some(key) = parse_addr(msg.payload) {
+                                if self.addresses.insert(key) {
+                                    let change = InterfaceChange::AddressAdded(key.0, key.1);
+                                    self.pending.push_back(change);
+                                }
+                            }
+                        }
+                        RTM_DELADDR => {
+                            if let Some(key) = parse_addr(msg.payload) {
+                                if self.addresses.remove(&key) {
+                                    let change = InterfaceChange::AddressRemoved(key.0, key.1);
+                                    self.pending.push_back(change);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_up(flags: u32) -> bool {
+        flags & sys::IFF_UP != 0 && flags & sys::IFF_RUNNING != 0
+    }
+
+    /// Parses the index and flags out of the payload of an `RTM_NEWLINK` or
+    /// `RTM_DELLINK` message.
+    fn parse_link(payload: &[u8]) -> Option<(u32, u32)> {
+        // struct ifinfomsg
+        if payload.len() < 16 {
+            return None
+        }
+        Some((netlink::read_u32(payload, 4), netlink::read_u32(payload, 8)))
+    }
+
+    /// Parses the interface index and address out of the payload of an
+    /// `RTM_NEWADDR` or `RTM_DELADDR` message.
+    fn parse_addr(payload: &[u8]) -> Option<(u32, IpAddr)> {
+        // struct ifaddrmsg
+        if payload.len() < 8 {
+            return None
+        }
+        let family = payload[0];
+        let index = netlink::read_u32(payload, 4);
+
+        // On point-to-point links the address attribute holds the peer's
+        // address, and the local one is in a separate attribute.
+        let mut address = None;
+        let mut local = None;
+        for (kind, data) in netlink::attrs(&payload[8..]) {
+            match kind {
+                IFA_ADDRESS => address = netlink::ip_addr(family, data),
+                IFA_LOCAL => local = netlink::ip_addr(family, data),
+                _ => {}
+            }
+        }
+        local.or(address).map(|addr| (index, addr))
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+mod monitor {
+    use std::collections::{HashMap, HashSet, VecDeque};
+    use std::io::{self, Read};
+    use std::net::IpAddr;
+
+    use futures::{Async, Poll};
+
+    use net::pf_route::{self, RTM_IFINFO, RTM_NEWADDR, RTM_DELADDR, RTAX_IFA};
+    use reactor::{Handle, PollEvented2};
+    use super::{sys, InterfaceChange};
+
+    pub struct InterfaceMonitor {
+        io: PollEvented2<pf_route::Socket>,
+        buf: Vec<u8>,
+        pending: VecDeque<InterfaceChange>,
+        // As on Linux, the kernel reports every change to an interface's
+        // flags, so the last known state is kept to only report changes.
+        up: HashMap<u32, bool>,
+        addresses: HashSet<(u32, IpAddr)>,
+    }
+
+    impl InterfaceMonitor {
+        pub fn new(handle: &Handle) -> io::Result<InterfaceMonitor> {
+            let socket = try!(pf_route::Socket::new());
+
+            // Open the socket before taking the snapshot so changes in
+            // between aren't missed.
+            let mut up = HashMap::new();
+            let mut addresses = HashSet::new();
+            for interface in try!(sys::interfaces()) {
+                up.insert(interface.index(), is_up(interface.flags));
+                for addr in interface.addresses() {
+                    addresses.insert((interface.index(), *addr));
+                }
+            }
+
+            Ok(InterfaceMonitor {
+                io: try!(PollEvented2::new_with_handle(socket, handle)),
+                buf: vec![0; pf_route::RECV_BUF_LEN],
+                pending: VecDeque::new(),
+                up: up,
+                addresses: addresses,
+            })
+        }
+
+        pub fn poll(&mut self) -> Poll<Option<InterfaceChange>, io::Error> {
+            loop {
+                if let Some(change) = self.pending.pop_front() {
+                    return Ok(Async::Ready(Some(change)))
+                }
+
+                let n = try_nb!(self.io.read(&mut self.buf));
+                for msg in pf_route::messages(&self.buf[..n]) {
+                    let index = msg.index as u32;
+                    match msg.kind {
+                        RTM_IFINFO => {
+                            // Interfaces are brought down before they're
+                            // detached, so removals are reported here too.
+                            let now = is_up(msg.flags as u32);
+                            match (self.up.insert(index, now).unwrap_or(false), now) {
+                                (false, true) => {
+                                    self.pending.push_back(InterfaceChange::Up(index))
+                                }
+                                (true, false) => {
+                                    self.pending.push_back(InterfaceChange::Down(index))
+                                }
+                                _ => {}
+                            }
+                        }
+                        RTM_NEWADDR => {
+                            if let Some(addr) = msg.addrs[RTAX_IFA].and_then(pf_route::ip_addr) {
+                                if self.addresses.insert((index, addr)) {
+                                    let change = InterfaceChange::AddressAdded(index, addr);
+                                    self.pending.push_back(change);
+                                }
+                            }
+                        }
+                        RTM_DELADDR => {
+                            if let Some(addr) = msg.addrs[RTAX_IFA].and_then(pf_route::ip_addr) {
+                                if self.addresses.remove(&(index, addr)) {
+                                    let change = InterfaceChange::AddressRemoved(index, addr);
+                                    self.pending.push_back(change);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_up(flags: u32) -> bool {
+        flags & sys::IFF_UP != 0 && flags & sys::IFF_RUNNING != 0
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios",
+              target_os = "freebsd")))]
+mod monitor {
+    use std::io;
+
+    use futures::Poll;
+
+    use reactor::Handle;
+    use super::InterfaceChange;
+
+    pub enum InterfaceMonitor {}
+
+    impl InterfaceMonitor {
+        pub fn new(_handle: &Handle) -> io::Result<InterfaceMonitor> {
+            Err(io::Error::new(io::ErrorKind::Other,
+                               "interface monitoring is not supported on this platform"))
+        }
+
+        pub fn poll(&mut self) -> Poll<Option<InterfaceChange>, io::Error> {
+            match *self {}
+        }
+    }
+}
@@ -3,9 +3,41 @@
 //! This module contains the TCP/UDP networking types, similar to the standard
 //! library, which can be used to implement networking protocols.
 
+mod distribute;
+mod drain;
+mod hook;
+pub mod http_proxy;
+mod interface;
+#[cfg(target_os = "linux")]
+mod netlink;
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+mod pf_route;
+pub mod pool;
+mod rate_limit;
+mod route;
+mod secure;
+#[cfg(target_os = "linux")]
+mod sctp;
+pub mod socks;
+#[cfg(unix)]
+mod sockopt;
 mod tcp;
+mod transport;
 mod udp;
 
 pub use self::tcp::{TcpStream, TcpStreamNew};
 pub use self::tcp::{TcpListener, Incoming};
+pub use self::distribute::{Distribute, Balance};
+pub use self::drain::{GuardedIncoming, ConnectionGuard, Drain, DrainHandle};
+pub use self::hook::Hooked;
+pub use self::rate_limit::RateLimited;
+pub use self::interface::{interfaces, Interface, InterfaceChange, InterfaceMonitor};
+pub use self::route::{Route, RouteChange, RouteMonitor};
+pub use self::route::{DefaultGateways, DefaultGatewayMonitor};
+pub use self::secure::{ConnectSecured, Handshake, Secured};
+#[cfg(target_os = "linux")]
+pub use self::sctp::{SctpListener, SctpIncoming, SctpStream, SctpStreamNew};
+pub use self::transport::{NetworkStream, Listener, ListenerIncoming};
 pub use self::udp::{UdpSocket, UdpCodec, UdpFramed, SendDgram, RecvDgram};
+#[cfg(target_os = "linux")]
+pub use self::udp::IcmpError;
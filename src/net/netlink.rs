@@ -0,0 +1,265 @@
+//! Minimal support for rtnetlink sockets, which the kernel uses to report
+//! changes to routes, links and addresses on Linux.
+
+use std::io::{self, Read};
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::prelude::*;
+use std::ptr;
+
+use libc;
+use mio::{Evented, Poll, PollOpt, Ready, Token};
+use mio::unix::EventedFd;
+
+pub const NLMSG_ERROR: u16 = 2;
+pub const NLMSG_DONE: u16 = 3;
+
+pub const NLM_F_REQUEST: u16 = 0x1;
+pub const NLM_F_REPLACE: u16 = 0x100;
+pub const NLM_F_DUMP: u16 = 0x300;
+
+pub const RTM_NEWLINK: u16 = 16;
+pub const RTM_DELLINK: u16 = 17;
+pub const RTM_NEWADDR: u16 = 20;
+pub const RTM_DELADDR: u16 = 21;
+pub const RTM_NEWROUTE: u16 = 24;
+pub const RTM_DELROUTE: u16 = 25;
+pub const RTM_GETROUTE: u16 = 26;
+
+pub const RTMGRP_LINK: u32 = 0x1;
+pub const RTMGRP_IPV4_IFADDR: u32 = 0x10;
+pub const RTMGRP_IPV4_ROUTE: u32 = 0x40;
+pub const RTMGRP_IPV6_IFADDR: u32 = 0x100;
+pub const RTMGRP_IPV6_ROUTE: u32 = 0x400;
+
+const HEADER_LEN: usize = 16;
+
+/// Size of the buffer messages are received into, large enough that the
+/// kernel never has to truncate a batch of messages.
+pub const RECV_BUF_LEN: usize = 64 * 1024;
+
+/// A nonblocking `NETLINK_ROUTE` socket.
+pub struct Socket {
+    fd: RawFd,
+}
+
+impl Socket {
+    /// Opens a socket subscribed to the given multicast groups.
+    pub fn new(groups: u32) -> io::Result<Socket> {
+        let fd = unsafe {
+            libc::socket(libc::AF_NETLINK,
+                         libc::SOCK_RAW | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+                         libc::NETLINK_ROUTE)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let socket = Socket { fd: fd };
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = groups;
+        let r = unsafe {
+            libc::bind(fd,
+                       &addr as *const _ as *const libc::sockaddr,
+                       mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t)
+        };
+        if r < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(socket)
+    }
+
+    /// Sends a request of the given kind to the kernel.
+    pub fn request(&self, kind: u16, flags: u16, payload: &[u8]) -> io::Result<()> {
+        let len = HEADER_LEN + payload.len();
+        let mut msg = vec![0; len];
+        write_u32(&mut msg, 0, len as u32);
+        write_u16(&mut msg, 4, kind);
+        write_u16(&mut msg, 6, NLM_F_REQUEST | flags);
+        msg[HEADER_LEN..].copy_from_slice(payload);
+
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        let r = unsafe {
+            libc::sendto(self.fd,
+                         msg.as_ptr() as *const libc::c_void,
+                         msg.len(),
+                         0,
+                         &addr as *const _ as *const libc::sockaddr,
+                         mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t)
+        };
+        if r < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(())
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let r = unsafe {
+            libc::recv(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+        };
+        if r < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(r as usize)
+    }
+}
+
+impl Evented for Socket {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+                -> io::Result<()> {
+        EventedFd(&self.fd).register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+                  -> io::Result<()> {
+        EventedFd(&self.fd).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        EventedFd(&self.fd).deregister(poll)
+    }
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// A message received from a netlink socket.
+pub struct Message<'a> {
+    pub kind: u16,
+    pub flags: u16,
+    pub payload: &'a [u8],
+}
+
+/// Iterates over the messages in a buffer received from a netlink socket.
+pub fn messages(buf: &[u8]) -> Messages<'_> {
+    Messages { buf: buf }
+}
+
+pub struct Messages<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = Message<'a>;
+
+    fn next(&mut self) -> Option<Message<'a>> {
+        if self.buf.len() < HEADER_LEN {
+            return None
+        }
+        let len = read_u32(self.buf, 0) as usize;
+        if len < HEADER_LEN || len > self.buf.len() {
+            return None
+        }
+        let msg = Message {
+            kind: read_u16(self.buf, 4),
+            flags: read_u16(self.buf, 6),
+            payload: &self.buf[HEADER_LEN..len],
+        };
+        let next = ::std::cmp::min(align(len), self.buf.len());
+        self.buf = &self.buf[next..];
+        Some(msg)
+    }
+}
+
+/// Iterates over the route attributes in `buf`, yielding their type and
+/// data.
+pub fn attrs(buf: &[u8]) -> Attrs<'_> {
+    Attrs { buf: buf }
+}
+
+pub struct Attrs<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for Attrs<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<(u16, &'a [u8])> {
+        if self.buf.len() < 4 {
+            return None
+        }
+        let len = read_u16(self.buf, 0) as usize;
+        if len < 4 || len > self.buf.len() {
+            return None
+        }
+        let attr = (read_u16(self.buf, 2), &self.buf[4..len]);
+        let next = ::std::cmp::min(align(len), self.buf.len());
+        self.buf = &self.buf[next..];
+        Some(attr)
+    }
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+pub fn read_u16(buf: &[u8], at: usize) -> u16 {
+    assert!(buf.len() >= at + 2);
+    unsafe { ptr::read_unaligned(buf[at..].as_ptr() as *const u16) }
+}
+
+pub fn read_u32(buf: &[u8], at: usize) -> u32 {
+    assert!(buf.len() >= at + 4);
+    unsafe { ptr::read_unaligned(buf[at..].as_ptr() as *const u32) }
+}
+
+fn write_u16(buf: &mut [u8], at: usize, val: u16) {
+    assert!(buf.len() >= at + 2);
+    unsafe { ptr::write_unaligned(buf[at..].as_mut_ptr() as *mut u16, val) }
+}
+
+fn write_u32(buf: &mut [u8], at: usize, val: u32) {
+    assert!(buf.len() >= at + 4);
+    unsafe { ptr::write_unaligned(buf[at..].as_mut_ptr() as *mut u32, val) }
+}
+
+/// Returns the error reported by an `NLMSG_ERROR` message, or `None` if it's
+/// an acknowledgement.
+pub fn error(payload: &[u8]) -> Option<io::Error> {
+    if payload.len() < 4 {
+        return None
+    }
+    match read_u32(payload, 0) as i32 {
+        0 => None,
+        code => Some(io::Error::from_raw_os_error(-code)),
+    }
+}
+
+/// Parses an address attribute of the given address family.
+pub fn ip_addr(family: u8, data: &[u8]) -> Option<IpAddr> {
+    match (family as i32, data.len()) {
+        (libc::AF_INET, 4) => {
+            Some(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3])))
+        }
+        (libc::AF_INET6, 16) => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(data);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the unspecified address of the given address family.
+pub fn unspecified(family: u8) -> Option<IpAddr> {
+    match family as i32 {
+        libc::AF_INET => Some(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0))),
+        libc::AF_INET6 => Some(IpAddr::V6(Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 0))),
+        _ => None,
+    }
+}
@@ -0,0 +1,300 @@
+//! Minimal support for `PF_ROUTE` sockets, which the kernel uses to report
+//! changes to routes and network interfaces on macOS and FreeBSD.
+
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::prelude::*;
+use std::ptr;
+
+use libc;
+use mio::{Evented, Poll, PollOpt, Ready, Token};
+use mio::unix::EventedFd;
+
+pub const RTM_VERSION: u8 = 5;
+
+pub const RTM_ADD: u8 = 0x1;
+pub const RTM_DELETE: u8 = 0x2;
+pub const RTM_CHANGE: u8 = 0x3;
+pub const RTM_GET: u8 = 0x4;
+pub const RTM_NEWADDR: u8 = 0xc;
+pub const RTM_DELADDR: u8 = 0xd;
+pub const RTM_IFINFO: u8 = 0xe;
+
+pub const RTF_GATEWAY: i32 = 0x2;
+pub const RTF_HOST: i32 = 0x4;
+pub const RTF_LLINFO: i32 = 0x400;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub const RTF_IFSCOPE: i32 = 0x1000000;
+
+pub const RTAX_DST: usize = 0;
+pub const RTAX_GATEWAY: usize = 1;
+pub const RTAX_NETMASK: usize = 2;
+pub const RTAX_IFA: usize = 5;
+const RTAX_MAX: usize = 8;
+
+const CTL_NET: libc::c_int = 4;
+const NET_RT_DUMP: libc::c_int = 1;
+
+// sizeof(struct rt_msghdr)
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const HEADER_LEN: usize = 92;
+#[cfg(target_os = "freebsd")]
+const HEADER_LEN: usize = 32 + 15 * ::std::mem::size_of::<libc::c_ulong>();
+// sizeof(struct ifa_msghdr), the same on both
+const IFA_HEADER_LEN: usize = 20;
+// The fields of struct if_msghdr up to ifm_index, which is followed by
+// statistics rather than socket addresses.
+const IF_HEADER_LEN: usize = 14;
+
+/// Size of the buffer messages are received into, which is more than the
+/// largest message the kernel sends.
+pub const RECV_BUF_LEN: usize = 2048;
+
+/// A nonblocking `PF_ROUTE` socket, which receives every change made to
+/// the routing table.
+pub struct Socket {
+    fd: RawFd,
+}
+
+impl Socket {
+    pub fn new() -> io::Result<Socket> {
+        let fd = unsafe { libc::socket(libc::PF_ROUTE, libc::SOCK_RAW, libc::AF_UNSPEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let socket = Socket { fd: fd };
+
+        let r = unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags < 0 {
+                flags
+            } else if libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+                -1
+            } else {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC)
+            }
+        };
+        if r < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(socket)
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let r = unsafe {
+            libc::recv(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+        };
+        if r < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(r as usize)
+    }
+}
+
+impl Evented for Socket {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+                -> io::Result<()> {
+        EventedFd(&self.fd).register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+                  -> io::Result<()> {
+        EventedFd(&self.fd).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        EventedFd(&self.fd).deregister(poll)
+    }
+}
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Reads the whole routing table, returned in the same format as messages
+/// received from a `PF_ROUTE` socket, each with a kind of `RTM_GET`.
+pub fn dump() -> io::Result<Vec<u8>> {
+    let mut mib = [CTL_NET, libc::PF_ROUTE, 0, libc::AF_UNSPEC, NET_RT_DUMP, 0];
+    loop {
+        let mut len = 0;
+        let r = unsafe {
+            libc::sysctl(mib.as_mut_ptr(), mib.len() as _,
+                         ptr::null_mut(), &mut len, ptr::null_mut(), 0)
+        };
+        if r < 0 {
+            return Err(io::Error::last_os_error())
+        }
+
+        // Leave room for routes added in between the two calls.
+        len += len / 8;
+        let mut buf = vec![0u8; len];
+        let r = unsafe {
+            libc::sysctl(mib.as_mut_ptr(), mib.len() as _,
+                         buf.as_mut_ptr() as *mut libc::c_void, &mut len,
+                         ptr::null_mut(), 0)
+        };
+        if r < 0 {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() == Some(libc::ENOMEM) {
+                continue
+            }
+            return Err(e)
+        }
+        buf.truncate(len);
+        return Ok(buf)
+    }
+}
+
+/// A message received from a `PF_ROUTE` socket.
+///
+/// Besides route messages, this may be an `RTM_IFINFO` message reporting the
+/// flags of an interface, which carries no addresses, or an `RTM_NEWADDR` or
+/// `RTM_DELADDR` message, which have the interface address at `RTAX_IFA`.
+pub struct Message<'a> {
+    pub kind: u8,
+    /// The index of the interface the message is about.
+    pub index: u16,
+    pub flags: i32,
+    /// Always zero for interface messages.
+    pub errno: i32,
+    /// The socket addresses following the header, indexed by `RTAX_*`.
+    pub addrs: [Option<&'a [u8]>; RTAX_MAX],
+}
+
+/// Iterates over the messages in a buffer received from a `PF_ROUTE`
+/// socket, skipping those of an unknown version.
+pub fn messages(buf: &[u8]) -> Messages<'_> {
+    Messages { buf: buf }
+}
+
+pub struct Messages<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for Messages<'a> {
+    type Item = Message<'a>;
+
+    fn next(&mut self) -> Option<Message<'a>> {
+        loop {
+            if self.buf.len() < 4 {
+                return None
+            }
+            let len = read_u16(self.buf, 0) as usize;
+            if len < 4 || len > self.buf.len() {
+                return None
+            }
+            let (msg, rest) = self.buf.split_at(len);
+            self.buf = rest;
+            if msg[2] != RTM_VERSION {
+                continue
+            }
+
+            let kind = msg[3];
+            let header_len = match kind {
+                RTM_NEWADDR | RTM_DELADDR => IFA_HEADER_LEN,
+                RTM_IFINFO => IF_HEADER_LEN,
+                _ => HEADER_LEN,
+            };
+            if msg.len() < header_len {
+                continue
+            }
+            let (present, index, errno) = match kind {
+                RTM_NEWADDR | RTM_DELADDR => (read_u32(msg, 4), read_u16(msg, 12), 0),
+                RTM_IFINFO => (0, read_u16(msg, 12), 0),
+                _ => (read_u32(msg, 12), read_u16(msg, 4), read_u32(msg, 24) as i32),
+            };
+
+
+            let mut addrs = [None; RTAX_MAX];
+            let mut buf = &msg[header_len..];
+            for (i, addr) in addrs.iter_mut().enumerate() {
+                if present & (1 << i) == 0 {
+                    continue
+                }
+                if buf.is_empty() {
+                    break
+                }
+                let len = ::std::cmp::min(buf[0] as usize, buf.len());
+                *addr = Some(&buf[..len]);
+                let next = ::std::cmp::min(align(len), buf.len());
+                buf = &buf[next..];
+            }
+
+            return Some(Message {
+                kind: kind,
+                index: index,
+                flags: read_u32(msg, 8) as i32,
+                errno: errno,
+                addrs: addrs,
+            })
+        }
+    }
+}
+
+/// Rounds the length of a socket address up to where the next one starts.
+fn align(len: usize) -> usize {
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    const ALIGN: usize = 4;
+    #[cfg(target_os = "freebsd")]
+    const ALIGN: usize = ::std::mem::size_of::<libc::c_long>();
+
+    if len == 0 {
+        ALIGN
+    } else {
+        (len + ALIGN - 1) & !(ALIGN - 1)
+    }
+}
+
+fn read_u16(buf: &[u8], at: usize) -> u16 {
+    assert!(buf.len() >= at + 2);
+    unsafe { ptr::read_unaligned(buf[at..].as_ptr() as *const u16) }
+}
+
+fn read_u32(buf: &[u8], at: usize) -> u32 {
+    assert!(buf.len() >= at + 4);
+    unsafe { ptr::read_unaligned(buf[at..].as_ptr() as *const u32) }
+}
+
+/// Parses a `sockaddr_in` or `sockaddr_in6`, returning `None` for any other
+/// kind of socket address, such as the `sockaddr_dl` of a link.
+pub fn ip_addr(addr: &[u8]) -> Option<IpAddr> {
+    if addr.len() < 2 {
+        return None
+    }
+    match addr[1] as i32 {
+        libc::AF_INET if addr.len() >= 8 => {
+            Some(IpAddr::V4(Ipv4Addr::new(addr[4], addr[5], addr[6], addr[7])))
+        }
+        libc::AF_INET6 if addr.len() >= 24 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(&addr[8..24]);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+/// Returns the prefix length of a netmask for addresses like `dst`.
+///
+/// The kernel leaves out trailing zero bytes of netmasks, and doesn't always
+/// fill in their address family, so it's taken from the destination.
+pub fn prefix_len(dst: &IpAddr, mask: &[u8]) -> u8 {
+    let start = if dst.is_ipv4() { 4 } else { 8 };
+    let end = ::std::cmp::min(mask.len(), if dst.is_ipv4() { 8 } else { 24 });
+    if end <= start {
+        return 0
+    }
+    mask[start..end].iter().map(|b| b.count_ones() as u8).sum()
+}
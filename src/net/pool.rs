@@ -0,0 +1,429 @@
+//! A pool of idle TCP connections, for clients talking to the same servers
+//! over and over.
+//!
+//! A `Pool` keeps connections which are done with a request around, keyed by
+//! the server they're connected to, so that the next request to the same
+//! server can reuse one instead of connecting again. Connections are checked
+//! out of the pool as `Pooled` streams, which return to the pool when
+//! dropped. Connections which have been idle for too long are closed by a
+//! task driven by the event loop's timers, and each connection is checked
+//! for having been closed by the server before it's reused.
+//!
+//! A pool and its connections belong to the event loop of the handle the
+//! pool was created with, so the pool can't be shared between threads. Each
+//! event loop of a multithreaded client has a pool of its own instead.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::SocketAddr;
+use std::ops::{Deref, DerefMut};
+use std::rc::{Rc, Weak};
+use std::time::Duration;
+
+use bytes::{Buf, BufMut};
+use futures::{task, Async, Future, Poll, Stream};
+use futures::task::Task;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use net::{TcpStream, TcpStreamNew};
+use reactor::{Handle, IdleTracker};
+
+/// A pool of idle TCP connections, keyed by `K`, which is the address of the
+/// server by default.
+///
+/// Cloning a pool gives another handle to the same pool.
+pub struct Pool<K = SocketAddr> {
+    inner: Rc<RefCell<Inner<K>>>,
+    handle: Handle,
+}
+
+/// A connection checked out of a `Pool`, which returns to it when dropped.
+///
+/// This can be used just like a `TcpStream`, which it dereferences to. A
+/// connection whose protocol state doesn't allow another request, say one
+/// which failed half-way through a response, must not return to the pool,
+/// and should be taken out of it with `detach` instead.
+pub struct Pooled<K: Hash + Eq + Clone = SocketAddr> {
+    stream: Option<TcpStream>,
+    key: K,
+    reused: bool,
+    pool: Weak<RefCell<Inner<K>>>,
+}
+
+/// Future returned by `Pool::checkout`, resolving to an idle connection if
+/// there is one.
+#[must_use = "futures do nothing unless polled"]
+pub struct Checkout<K = SocketAddr> {
+    key: Option<K>,
+    pool: Pool<K>,
+}
+
+/// Future returned by `Pool::connect`, resolving to an idle connection to
+/// the address, or a new one if there is none.
+#[must_use = "futures do nothing unless polled"]
+pub struct Connect {
+    state: ConnectState,
+    addr: SocketAddr,
+    pool: Pool<SocketAddr>,
+}
+
+enum ConnectState {
+    Checkout(Checkout<SocketAddr>),
+    Connecting(TcpStreamNew),
+}
+
+struct Inner<K> {
+    // Idle connections of each key, most recently used last
+    idle: HashMap<K, Vec<(u64, TcpStream)>>,
+    tracker: IdleTracker<(K, u64)>,
+    next_id: u64,
+    max_idle: usize,
+    evictor: Option<Task>,
+}
+
+impl<K: Hash + Eq + Clone> Pool<K> {
+    /// Creates a new pool on the event loop of `handle`.
+    ///
+    /// At most `max_idle` connections are kept for each key, and connections
+    /// which have been idle for `idle_timeout` are closed.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max_idle` is zero.
+    pub fn new(max_idle: usize, idle_timeout: Duration, handle: &Handle)
+               -> io::Result<Pool<K>>
+        where K: 'static,
+    {
+        assert!(max_idle > 0, "a pool must be able to keep connections");
+        let inner = Rc::new(RefCell::new(Inner {
+            idle: HashMap::new(),
+            tracker: try!(IdleTracker::new(idle_timeout, handle)),
+            next_id: 0,
+            max_idle: max_idle,
+            evictor: None,
+        }));
+        handle.spawn(Evict { pool: Rc::downgrade(&inner) });
+        Ok(Pool {
+            inner: inner,
+            handle: handle.clone(),
+        })
+    }
+
+    /// Checks out an idle connection of `key`, if there is one.
+    ///
+    /// Connections which have been closed by the server, or which have
+    /// unexpectedly received data while idle, are closed rather than handed
+    /// out.
+    pub fn checkout(&self, key: K) -> Checkout<K> {
+        Checkout {
+            key: Some(key),
+            pool: self.clone(),
+        }
+    }
+
+    /// Wraps a new connection of `key` so that it returns to this pool when
+    /// dropped.
+    pub fn pooled(&self, key: K, stream: TcpStream) -> Pooled<K> {
+        Pooled {
+            stream: Some(stream),
+            key: key,
+            reused: false,
+            pool: Rc::downgrade(&self.inner),
+        }
+    }
+
+    /// Returns the number of idle connections of `key`.
+    pub fn idle(&self, key: &K) -> usize {
+        self.inner.borrow().idle.get(key).map(|idle| idle.len()).unwrap_or(0)
+    }
+
+    /// Closes all idle connections.
+    pub fn clear(&self) {
+        let idle = {
+            let mut inner = self.inner.borrow_mut();
+            let idle = mem::replace(&mut inner.idle, HashMap::new());
+            for (key, streams) in &idle {
+                for &(id, _) in streams {
+                    inner.tracker.remove(&(key.clone(), id));
+                }
+            }
+            idle
+        };
+        drop(idle);
+    }
+}
+
+impl Pool<SocketAddr> {
+    /// Checks out an idle connection to `addr`, or connects to it if there's
+    /// none.
+    pub fn connect(&self, addr: &SocketAddr) -> Connect {
+        Connect {
+            state: ConnectState::Checkout(self.checkout(*addr)),
+            addr: *addr,
+            pool: self.clone(),
+        }
+    }
+}
+
+impl<K> Clone for Pool<K> {
+    fn clone(&self) -> Pool<K> {
+        Pool {
+            inner: self.inner.clone(),
+            handle: self.handle.clone(),
+        }
+    }
+}
+
+impl<K: Hash + Eq> fmt::Debug for Pool<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("Pool")
+         .field("idle", &inner.idle.values().map(|idle| idle.len()).sum::<usize>())
+         .field("max_idle", &inner.max_idle)
+         .finish()
+    }
+}
+
+impl<K> Drop for Inner<K> {
+    fn drop(&mut self) {
+        if let Some(task) = self.evictor.take() {
+            task.notify();
+        }
+    }
+}
+
+/// Closes idle connections once they time out.
+struct Evict<K> {
+    pool: Weak<RefCell<Inner<K>>>,
+}
+
+impl<K: Hash + Eq + Clone> Future for Evict<K> {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        let pool = match self.pool.upgrade() {
+            Some(pool) => pool,
+            None => return Ok(Async::Ready(())),
+        };
+        let mut expired = Vec::new();
+        {
+            let mut inner = pool.borrow_mut();
+            inner.evictor = Some(task::current());
+            while let Ok(Async::Ready(Some((key, id)))) = inner.tracker.poll() {
+                let stream = match inner.idle.get_mut(&key) {
+                    Some(idle) => {
+                        idle.iter()
+                            .position(|&(i, _)| i == id)
+                            .map(|i| idle.remove(i).1)
+                    }
+                    None => None,
+                };
+                if inner.idle.get(&key).map(|idle| idle.is_empty()).unwrap_or(false) {
+                    inner.idle.remove(&key);
+                }
+                expired.extend(stream);
+            }
+        }
+        // Closed outside of the borrow, in case that runs other code using
+        // the pool.
+        drop(expired);
+        Ok(Async::NotReady)
+    }
+}
+
+impl<K: Hash + Eq + Clone> Future for Checkout<K> {
+    type Item = Option<Pooled<K>>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Pooled<K>>, io::Error> {
+        let key = self.key.take().expect("poll a Checkout after it's done");
+        loop {
+            let (id, stream) = {
+                let mut inner = self.pool.inner.borrow_mut();
+                let popped = inner.idle.get_mut(&key).and_then(|idle| idle.pop());
+                match popped {
+                    Some((id, stream)) => {
+                        inner.tracker.remove(&(key.clone(), id));
+                        (id, stream)
+                    }
+                    None => {
+                        inner.idle.remove(&key);
+                        return Ok(Async::Ready(None))
+                    }
+                }
+            };
+            if is_healthy(&stream) {
+                trace!("reusing idle connection {}", id);
+                let mut pooled = self.pool.pooled(key, stream);
+                pooled.reused = true;
+                return Ok(Async::Ready(Some(pooled)))
+            }
+            trace!("closing broken idle connection {}", id);
+        }
+    }
+}
+
+impl<K: fmt::Debug> fmt::Debug for Checkout<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Checkout")
+         .field("key", &self.key)
+         .finish()
+    }
+}
+
+/// An idle connection can be reused as long as there's nothing to read from
+/// it: neither an EOF nor data the server sent without being asked.
+///
+/// The socket is asked directly, as the cached readiness may not be up to
+/// date yet and the event loop's I/O budget may be used up.
+fn is_healthy(stream: &TcpStream) -> bool {
+    let mut buf = [0; 1];
+    match stream.peek_socket(&mut buf) {
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => true,
+        _ => false,
+    }
+}
+
+impl Future for Connect {
+    type Item = Pooled<SocketAddr>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Pooled<SocketAddr>, io::Error> {
+        loop {
+            let next = match self.state {
+                ConnectState::Checkout(ref mut checkout) => {
+                    if let Some(pooled) = try_ready!(checkout.poll()) {
+                        return Ok(Async::Ready(pooled))
+                    }
+                    TcpStream::connect(&self.addr, &self.pool.handle)
+                }
+                ConnectState::Connecting(ref mut connect) => {
+                    let stream = try_ready!(connect.poll());
+                    return Ok(Async::Ready(self.pool.pooled(self.addr, stream)))
+                }
+            };
+            self.state = ConnectState::Connecting(next);
+        }
+    }
+}
+
+impl fmt::Debug for Connect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Connect")
+         .field("addr", &self.addr)
+         .finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone> Pooled<K> {
+    /// Returns the key this connection is pooled under.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns whether this connection was reused from the pool, rather than
+    /// being a new one.
+    ///
+    /// A request on a reused connection can fail because the server closed
+    /// it just as it was sent, in which case it's usually safe to retry the
+    /// request on a new connection.
+    pub fn is_reused(&self) -> bool {
+        self.reused
+    }
+
+    /// Takes this connection out of the pool, returning the stream which will
+    /// then not return to the pool when dropped.
+    pub fn detach(mut self) -> TcpStream {
+        self.stream.take().unwrap()
+    }
+}
+
+impl<K: Hash + Eq + Clone> Drop for Pooled<K> {
+    fn drop(&mut self) {
+        let stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => return,
+        };
+        let pool = match self.pool.upgrade() {
+            Some(pool) => pool,
+            None => return,
+        };
+        let mut inner = pool.borrow_mut();
+        let inner = &mut *inner;
+        let id = inner.next_id;
+        {
+            let idle = inner.idle.entry(self.key.clone()).or_insert_with(Vec::new);
+            if idle.len() >= inner.max_idle {
+                return
+            }
+            idle.push((id, stream));
+        }
+        inner.next_id += 1;
+        inner.tracker.insert((self.key.clone(), id));
+    }
+}
+
+impl<K: Hash + Eq + Clone> Deref for Pooled<K> {
+    type Target = TcpStream;
+
+    fn deref(&self) -> &TcpStream {
+        self.stream.as_ref().unwrap()
+    }
+}
+
+impl<K: Hash + Eq + Clone> DerefMut for Pooled<K> {
+    fn deref_mut(&mut self) -> &mut TcpStream {
+        self.stream.as_mut().unwrap()
+    }
+}
+
+impl<K: Hash + Eq + Clone> Read for Pooled<K> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (**self).read(buf)
+    }
+}
+
+impl<K: Hash + Eq + Clone> Write for Pooled<K> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (**self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+}
+
+impl<K: Hash + Eq + Clone> AsyncRead for Pooled<K> {
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        (**self).prepare_uninitialized_buffer(buf)
+    }
+
+    fn read_buf<B: BufMut>(&mut self, buf: &mut B) -> Poll<usize, io::Error> {
+        (**self).read_buf(buf)
+    }
+}
+
+impl<K: Hash + Eq + Clone> AsyncWrite for Pooled<K> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        AsyncWrite::shutdown(&mut **self)
+    }
+
+    fn write_buf<B: Buf>(&mut self, buf: &mut B) -> Poll<usize, io::Error> {
+        (**self).write_buf(buf)
+    }
+}
+
+impl<K: Hash + Eq + Clone + fmt::Debug> fmt::Debug for Pooled<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Pooled")
+         .field("key", &self.key)
+         .field("stream", &self.stream)
+         .field("reused", &self.reused)
+         .finish()
+    }
+}
@@ -0,0 +1,109 @@
+use std::cmp;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll, Stream};
+
+use net::{Incoming, TcpStream};
+use reactor::{Handle, Timeout};
+
+/// Stream returned by `Incoming::rate_limited`, which accepts connections at
+/// a limited rate.
+///
+/// The rate is limited with a token bucket: each accepted connection takes a
+/// token, and tokens are refilled at a steady rate up to the size of the
+/// bucket. While the bucket is empty no connections are accepted, leaving
+/// them in the listener's backlog until the next token is due.
+#[must_use = "streams do nothing unless polled"]
+pub struct RateLimited {
+    incoming: Incoming,
+    handle: Handle,
+    interval: Duration,
+    burst: u32,
+    tokens: u32,
+    // When `tokens` was last brought up to date
+    refilled: Instant,
+    delay: Option<Timeout>,
+}
+
+pub fn new(incoming: Incoming, per_second: u32, burst: u32, handle: &Handle) -> RateLimited {
+    assert!(per_second > 0, "at least one connection per second must be allowed");
+    assert!(burst > 0, "the burst size must be at least one connection");
+    RateLimited {
+        incoming: incoming,
+        handle: handle.clone(),
+        // Rates beyond the resolution of `Duration` are capped to it.
+        interval: cmp::max(Duration::from_secs(1) / per_second, Duration::new(0, 1)),
+        burst: burst,
+        tokens: burst,
+        refilled: handle.now(),
+        delay: None,
+    }
+}
+
+impl RateLimited {
+    /// Returns the number of connections which can currently be accepted
+    /// right away.
+    pub fn available(&self) -> u32 {
+        self.tokens
+    }
+
+    fn refill(&mut self) {
+        let now = self.handle.now();
+        if now <= self.refilled {
+            return
+        }
+        let new = nanos(now - self.refilled) / nanos(self.interval);
+        if self.tokens as u64 + new >= self.burst as u64 {
+            self.tokens = self.burst;
+            self.refilled = now;
+        } else {
+            self.tokens += new as u32;
+            self.refilled += self.interval * new as u32;
+        }
+    }
+}
+
+fn nanos(dur: Duration) -> u64 {
+    dur.as_secs() * 1_000_000_000 + dur.subsec_nanos() as u64
+}
+
+impl Stream for RateLimited {
+    type Item = (TcpStream, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+        loop {
+            if let Some(mut delay) = self.delay.take() {
+                if let Async::NotReady = try!(delay.poll()) {
+                    self.delay = Some(delay);
+                    return Ok(Async::NotReady)
+                }
+            }
+            self.refill();
+            if self.tokens > 0 {
+                break
+            }
+            let next = self.refilled + self.interval;
+            self.delay = Some(try!(Timeout::new_at(next, &self.handle)));
+        }
+
+        let conn = try_ready!(self.incoming.poll());
+        if conn.is_some() {
+            self.tokens -= 1;
+        }
+        Ok(Async::Ready(conn))
+    }
+}
+
+impl fmt::Debug for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RateLimited")
+         .field("interval", &self.interval)
+         .field("burst", &self.burst)
+         .field("available", &self.tokens)
+         .finish()
+    }
+}
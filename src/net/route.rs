@@ -0,0 +1,538 @@
+use std::io;
+use std::net::IpAddr;
+
+use futures::{Async, Poll, Stream};
+
+use reactor::Handle;
+
+/// An entry of the system's routing table.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Route {
+    destination: IpAddr,
+    prefix_len: u8,
+    gateway: Option<IpAddr>,
+    interface_index: Option<u32>,
+    table: u32,
+    metric: u32,
+}
+
+impl Route {
+    /// Returns the network this route leads to.
+    pub fn destination(&self) -> IpAddr {
+        self.destination
+    }
+
+    /// Returns the length of the destination network's prefix, which is 0
+    /// for a default route.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns the address of the gateway traffic is sent through, if any.
+    pub fn gateway(&self) -> Option<IpAddr> {
+        self.gateway
+    }
+
+    /// Returns the index of the network interface traffic is sent out of, if
+    /// known.
+    pub fn interface_index(&self) -> Option<u32> {
+        self.interface_index
+    }
+
+    /// Returns the routing table this route belongs to.
+    ///
+    /// On Linux the main table, which is what's normally meant by "the
+    /// routing table", is table 254. On macOS and FreeBSD this is always 0.
+    pub fn table(&self) -> u32 {
+        self.table
+    }
+
+    /// Returns the metric of this route. When several routes lead to the
+    /// same destination, the one with the lowest metric is used.
+    ///
+    /// Routes on macOS and FreeBSD have no metric, so this is always 0.
+    pub fn metric(&self) -> u32 {
+        self.metric
+    }
+
+    /// Returns whether this is a default route, matching all destinations of
+    /// its address family.
+    pub fn is_default(&self) -> bool {
+        self.prefix_len == 0
+    }
+}
+
+/// A change to the system's routing table, as reported by `RouteMonitor`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RouteChange {
+    /// A route was added, or an existing route was replaced.
+    Added(Route),
+
+    /// A route was removed.
+    Removed(Route),
+}
+
+/// A stream of changes to the system's routing table.
+///
+/// The stream yields every change made to the unicast IPv4 and IPv6 routes
+/// of any routing table from the moment it's created, as soon as the kernel
+/// reports it. Only changes are reported; the stream doesn't yield the routes
+/// which already exist.
+///
+/// If routes change faster than they're read from the stream the kernel
+/// may drop notifications, in which case the stream returns an error and
+/// keeps going. Callers relying on an accurate view of the routing table
+/// should then read the full table again.
+///
+/// Route monitoring is built on rtnetlink on Linux and on routing sockets
+/// on macOS and FreeBSD. Other platforms, including the other BSDs whose
+/// routing messages are laid out differently, aren't supported yet.
+#[must_use = "streams do nothing unless polled"]
+pub struct RouteMonitor {
+    inner: sys::RouteMonitor,
+}
+
+impl RouteMonitor {
+    /// Starts monitoring the routing table, registering with the event loop
+    /// that `handle` is associated with.
+    ///
+    /// This fails with an error of kind `Other` on platforms which don't
+    /// support route monitoring.
+    pub fn new(handle: &Handle) -> io::Result<RouteMonitor> {
+        Ok(RouteMonitor { inner: try!(sys::RouteMonitor::new(handle)) })
+    }
+}
+
+impl Stream for RouteMonitor {
+    type Item = RouteChange;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<RouteChange>, io::Error> {
+        loop {
+            match try_ready!(self.inner.poll()) {
+                Some(sys::Event::Added(route, _)) => {
+                    return Ok(Async::Ready(Some(RouteChange::Added(route))))
+                }
+                Some(sys::Event::Removed(route)) => {
+                    return Ok(Async::Ready(Some(RouteChange::Removed(route))))
+                }
+                Some(sys::Event::DumpDone) => {}
+                None => return Ok(Async::Ready(None)),
+            }
+        }
+    }
+}
+
+impl ::std::fmt::Debug for RouteMonitor {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("RouteMonitor").finish()
+    }
+}
+
+/// The default gateways of the system, as reported by
+/// `DefaultGatewayMonitor`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DefaultGateways {
+    ipv4: Option<Route>,
+    ipv6: Option<Route>,
+}
+
+impl DefaultGateways {
+    /// Returns the default route used for IPv4 traffic, if there is one.
+    pub fn ipv4(&self) -> Option<&Route> {
+        self.ipv4.as_ref()
+    }
+
+    /// Returns the default route used for IPv6 traffic, if there is one.
+    pub fn ipv6(&self) -> Option<&Route> {
+        self.ipv6.as_ref()
+    }
+}
+
+/// A stream of the system's default gateways.
+///
+/// The stream first yields the default gateways at the time it's created,
+/// once the routing table has been read, and after that yields them again
+/// every time the default gateway of either address family changes. Only
+/// the main routing table is considered, and when there are several default
+/// routes for an address family the one with the lowest metric is reported.
+///
+/// This is supported on the same platforms as `RouteMonitor`. If the
+/// kernel drops notifications the stream returns an error, after which the
+/// gateways it reports may be out of date.
+#[must_use = "streams do nothing unless polled"]
+pub struct DefaultGatewayMonitor {
+    inner: sys::RouteMonitor,
+    routes: Vec<Route>,
+    dumped: bool,
+    last: Option<DefaultGateways>,
+}
+
+impl DefaultGatewayMonitor {
+    /// Starts monitoring the default gateways, registering with the event
+    /// loop that `handle` is associated with.
+    ///
+    /// This fails with an error of kind `Other` on platforms which don't
+    /// support route monitoring.
+    pub fn new(handle: &Handle) -> io::Result<DefaultGatewayMonitor> {
+        let inner = try!(sys::RouteMonitor::new(handle));
+        try!(inner.request_dump());
+        Ok(DefaultGatewayMonitor {
+            inner: inner,
+            routes: Vec::new(),
+            dumped: false,
+            last: None,
+        })
+    }
+
+    fn current(&self) -> DefaultGateways {
+        let best = |v4: bool| {
+            self.routes.iter()
+                .filter(|r| r.destination.is_ipv4() == v4)
+                .min_by_key(|r| r.metric)
+                .cloned()
+        };
+        DefaultGateways { ipv4: best(true), ipv6: best(false) }
+    }
+}
+
+impl Stream for DefaultGatewayMonitor {
+    type Item = DefaultGateways;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<DefaultGateways>, io::Error> {
+        loop {
+            match try_ready!(self.inner.poll()) {
+                Some(sys::Event::Added(route, replace)) => {
+                    if !route.is_default() || route.table != sys::MAIN_TABLE {
+                        continue
+                    }
+                    if replace {
+                        self.routes.retain(|r| {
+                            r.destination != route.destination || r.metric != route.metric
+                        });
+                    }
+                    if !self.routes.contains(&route) {
+                        self.routes.push(route);
+                    }
+                }
+                Some(sys::Event::Removed(route)) => self.routes.retain(|r| *r != route),
+                Some(sys::Event::DumpDone) => self.dumped = true,
+                None => return Ok(Async::Ready(None)),
+            }
+
+            if !self.dumped {
+                continue
+            }
+            let current = self.current();
+            if self.last.as_ref() != Some(&current) {
+                self.last = Some(current.clone());
+                return Ok(Async::Ready(Some(current)))
+            }
+        }
+    }
+}
+
+impl ::std::fmt::Debug for DefaultGatewayMonitor {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("DefaultGatewayMonitor")
+         .field("current", &self.last)
+         .finish()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::collections::VecDeque;
+    use std::io::{self, Read};
+
+    use futures::{Async, Poll};
+
+    use net::netlink::{self, NLMSG_DONE, NLMSG_ERROR, NLM_F_DUMP, NLM_F_REPLACE};
+    use net::netlink::{RTM_NEWROUTE, RTM_DELROUTE, RTM_GETROUTE};
+    use net::netlink::{RTMGRP_IPV4_ROUTE, RTMGRP_IPV6_ROUTE};
+    use reactor::{Handle, PollEvented2};
+    use super::Route;
+
+    pub const MAIN_TABLE: u32 = 254;
+
+    const RTA_DST: u16 = 1;
+    const RTA_OIF: u16 = 4;
+    const RTA_GATEWAY: u16 = 5;
+    const RTA_PRIORITY: u16 = 6;
+    const RTA_TABLE: u16 = 15;
+    const RTN_UNICAST: u8 = 1;
+
+    pub enum Event {
+        /// A route was added, replacing an existing one if the flag is set.
+        Added(Route, bool),
+        Removed(Route),
+        /// All routes requested with `request_dump` have been received.
+        DumpDone,
+    }
+
+    pub struct RouteMonitor {
+        io: PollEvented2<netlink::Socket>,
+        buf: Vec<u8>,
+        pending: VecDeque<Event>,
+    }
+
+    impl RouteMonitor {
+        pub fn new(handle: &Handle) -> io::Result<RouteMonitor> {
+            let socket = try!(netlink::Socket::new(RTMGRP_IPV4_ROUTE | RTMGRP_IPV6_ROUTE));
+            Ok(RouteMonitor {
+                io: try!(PollEvented2::new_with_handle(socket, handle)),
+                buf: vec![0; netlink::RECV_BUF_LEN],
+                pending: VecDeque::new(),
+            })
+        }
+
+        /// Asks the kernel for all existing routes, which are then yielded
+        /// as `Added` events followed by `DumpDone`.
+        pub fn request_dump(&self) -> io::Result<()> {
+            // struct rtmsg, with an unspecified address family to get the
+            // routes of all families
+            self.io.get_ref().request(RTM_GETROUTE, NLM_F_DUMP, &[0; 12])
+        }
+
+        pub fn poll(&mut self) -> Poll<Option<Event>, io::Error> {
+            loop {
+                if let Some(event) = self.pending.pop_front() {
+                    return Ok(Async::Ready(Some(event)))
+                }
+
+                let n = try_nb!(self.io.read(&mut self.buf));
+                for msg in netlink::messages(&self.buf[..n]) {
+                    match msg.kind {
+                        NLMSG_DONE => self.pending.push_back(Event::DumpDone),
+                        NLMSG_ERROR => {
+                            if let Some(e) = netlink::error(msg.payload) {
+                                return Err(e)
+                            }
+                        }
+                        RTM_NEWROUTE | RTM_DELROUTE => {
+                            let route = match parse_route(msg.payload) {
+                                Some(route) => route,
+                                None => continue,
+                            };
+                            self.pending.push_back(if msg.kind == RTM_NEWROUTE {
+                                Event::Added(route, msg.flags & NLM_F_REPLACE != 0)
+                            } else {
+                                Event::Removed(route)
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parses the payload of an `RTM_NEWROUTE` or `RTM_DELROUTE` message.
+    fn parse_route(payload: &[u8]) -> Option<Route> {
+        // struct rtmsg
+        if payload.len() < 12 || payload[7] != RTN_UNICAST {
+            return None
+        }
+        let family = payload[0];
+        let mut route = Route {
+            destination: match netlink::unspecified(family) {
+                Some(addr) => addr,
+                None => return None,
+            },
+            prefix_len: payload[1],
+            gateway: None,
+            interface_index: None,
+            table: payload[4] as u32,
+            metric: 0,
+        };
+
+        for (kind, data) in netlink::attrs(&payload[12..]) {
+            match kind {
+                RTA_DST => {
+                    if let Some(addr) = netlink::ip_addr(family, data) {
+                        route.destination = addr;
+                    }
+                }
+                RTA_GATEWAY => route.gateway = netlink::ip_addr(family, data),
+                RTA_OIF if data.len() == 4 => {
+                    route.interface_index = Some(netlink::read_u32(data, 0));
+                }
+                RTA_PRIORITY if data.len() == 4 => route.metric = netlink::read_u32(data, 0),
+                RTA_TABLE if data.len() == 4 => route.table = netlink::read_u32(data, 0),
+                _ => {}
+            }
+        }
+        Some(route)
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+mod sys {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::io::{self, Read};
+
+    use futures::{Async, Poll};
+
+    use net::pf_route::{self, RTM_ADD, RTM_CHANGE, RTM_DELETE, RTM_GET};
+    use net::pf_route::{RTAX_DST, RTAX_GATEWAY, RTAX_NETMASK, RTF_GATEWAY, RTF_HOST, RTF_LLINFO};
+    use reactor::{Handle, PollEvented2};
+    use super::Route;
+
+    /// There is a single routing table on macOS, and FreeBSD reports the
+    /// routes of the default FIB to routing sockets which aren't bound to
+    /// another one.
+    pub const MAIN_TABLE: u32 = 0;
+
+    pub enum Event {
+        /// A route was added, replacing an existing one if the flag is set.
+        Added(Route, bool),
+        Removed(Route),
+        /// All routes requested with `request_dump` have been received.
+        DumpDone,
+    }
+
+    pub struct RouteMonitor {
+        io: PollEvented2<pf_route::Socket>,
+        buf: Vec<u8>,
+        // Routing sockets can't be asked for the routing table, so the dump
+        // is read with sysctl and queued up here.
+        pending: RefCell<VecDeque<Event>>,
+    }
+
+    impl RouteMonitor {
+        pub fn new(handle: &Handle) -> io::Result<RouteMonitor> {
+            let socket = try!(pf_route::Socket::new());
+            Ok(RouteMonitor {
+                io: try!(PollEvented2::new_with_handle(socket, handle)),
+                buf: vec![0; pf_route::RECV_BUF_LEN],
+                pending: RefCell::new(VecDeque::new()),
+            })
+        }
+
+        /// Reads all existing routes, which are then yielded as `Added`
+        /// events followed by `DumpDone`.
+        ///
+        /// The routing table is read right away, so changes which are still
+        /// waiting to be received on the socket are yielded after the dump,
+        /// even if they were made before it.
+        pub fn request_dump(&self) -> io::Result<()> {
+            let dump = try!(pf_route::dump());
+            let mut pending = self.pending.borrow_mut();
+            for msg in pf_route::messages(&dump) {
+                if msg.kind == RTM_GET {
+                    if let Some(route) = parse_route(&msg) {
+                        pending.push_back(Event::Added(route, false));
+                    }
+                }
+            }
+            pending.push_back(Event::DumpDone);
+            Ok(())
+        }
+
+        pub fn poll(&mut self) -> Poll<Option<Event>, io::Error> {
+            loop {
+                if let Some(event) = self.pending.borrow_mut().pop_front() {
+                    return Ok(Async::Ready(Some(event)))
+                }
+
+                let n = try_nb!(self.io.read(&mut self.buf));
+                let mut pending = self.pending.borrow_mut();
+                for msg in pf_route::messages(&self.buf[..n]) {
+                    // Failed requests of other processes are reported too.
+                    if msg.errno != 0 {
+                        continue
+                    }
+                    let route = match msg.kind {
+                        RTM_ADD | RTM_CHANGE | RTM_DELETE => match parse_route(&msg) {
+                            Some(route) => route,
+                            None => continue,
+                        },
+                        _ => continue,
+                    };
+                    pending.push_back(match msg.kind {
+                        RTM_DELETE => Event::Removed(route),
+                        kind => Event::Added(route, kind == RTM_CHANGE),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Parses the socket addresses of a route message into a route, if it's
+    /// an IPv4 or IPv6 route.
+    fn parse_route(msg: &pf_route::Message) -> Option<Route> {
+        // Entries of the ARP and neighbor caches, not routes.
+        if msg.flags & RTF_LLINFO != 0 {
+            return None
+        }
+        // Default routes scoped to an interface, which macOS keeps for each
+        // interface alongside the one actually used.
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        {
+            if msg.flags & pf_route::RTF_IFSCOPE != 0 {
+                return None
+            }
+        }
+
+        let destination = match msg.addrs[RTAX_DST].and_then(pf_route::ip_addr) {
+            Some(addr) => addr,
+            None => return None,
+        };
+        let full = if destination.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match msg.addrs[RTAX_NETMASK] {
+            Some(mask) if msg.flags & RTF_HOST == 0 => pf_route::prefix_len(&destination, mask),
+            _ => full,
+        };
+        let gateway = if msg.flags & RTF_GATEWAY != 0 {
+            msg.addrs[RTAX_GATEWAY].and_then(pf_route::ip_addr)
+        } else {
+            None
+        };
+        Some(Route {
+            destination: destination,
+            prefix_len: prefix_len,
+            gateway: gateway,
+            interface_index: if msg.index == 0 { None } else { Some(msg.index as u32) },
+            table: MAIN_TABLE,
+            metric: 0,
+        })
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios",
+              target_os = "freebsd")))]
+mod sys {
+    use std::io;
+
+    use futures::Poll;
+
+    use reactor::Handle;
+    use super::Route;
+
+    pub const MAIN_TABLE: u32 = 0;
+
+    pub enum Event {
+        Added(Route, bool),
+        Removed(Route),
+        DumpDone,
+    }
+
+    pub enum RouteMonitor {}
+
+    impl RouteMonitor {
+        pub fn new(_handle: &Handle) -> io::Result<RouteMonitor> {
+            Err(io::Error::new(io::ErrorKind::Other,
+                               "route monitoring is not supported on this platform"))
+        }
+
+        pub fn request_dump(&self) -> io::Result<()> {
+            match *self {}
+        }
+
+        pub fn poll(&mut self) -> Poll<Option<Event>, io::Error> {
+            match *self {}
+        }
+    }
+}
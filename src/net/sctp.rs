@@ -0,0 +1,483 @@
+//! One-to-one style SCTP sockets.
+//!
+//! `SctpListener` and `SctpStream` mirror `TcpListener` and `TcpStream`:
+//! connections are set up the same way, and `SctpStream` implements
+//! `AsyncRead` and `AsyncWrite` for the default stream of the association.
+//! The other streams of the association can be used with
+//! `SctpStream::send_on` and `SctpStream::recv_from_stream`.
+//!
+//! SCTP is only supported on Linux, where the kernel must have been built
+//! with SCTP support. Creating a socket fails with an error otherwise.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::{Shutdown, SocketAddr};
+use std::os::unix::prelude::*;
+
+use bytes::BufMut;
+use futures::{Async, Future, Poll, Stream};
+use libc;
+use mio::{self, Evented, PollOpt, Ready, Token};
+use mio::unix::EventedFd;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use net::sockopt;
+use reactor::{Handle, PollEvented2};
+
+const SOL_SCTP: libc::c_int = 132;
+const SCTP_INITMSG: libc::c_int = 2;
+const SCTP_RECVRCVINFO: libc::c_int = 32;
+const SCTP_SNDINFO: libc::c_int = 2;
+const SCTP_RCVINFO: libc::c_int = 3;
+
+/// struct sctp_initmsg
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InitMsg {
+    num_ostreams: u16,
+    max_instreams: u16,
+    max_attempts: u16,
+    max_init_timeo: u16,
+}
+
+/// struct sctp_sndinfo
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SndInfo {
+    sid: u16,
+    flags: u16,
+    ppid: u32,
+    context: u32,
+    assoc_id: i32,
+}
+
+/// An SCTP socket listening for incoming associations.
+pub struct SctpListener {
+    io: PollEvented2<Socket>,
+}
+
+/// Stream returned by `SctpListener::incoming`, yielding each accepted
+/// association along with the address of its peer.
+#[must_use = "streams do nothing unless polled"]
+pub struct SctpIncoming {
+    inner: SctpListener,
+}
+
+/// An SCTP association, used like a TCP stream.
+pub struct SctpStream {
+    io: PollEvented2<Socket>,
+}
+
+/// Future returned by `SctpStream::connect` which resolves to an
+/// `SctpStream` once the association has been established.
+#[must_use = "futures do nothing unless polled"]
+pub struct SctpStreamNew {
+    inner: Option<io::Result<SctpStream>>,
+}
+
+impl SctpListener {
+    /// Creates a new SCTP listener bound to the specified address, associated
+    /// with the event loop of `handle`.
+    pub fn bind(addr: &SocketAddr, handle: &Handle) -> io::Result<SctpListener> {
+        let socket = try!(Socket::new(addr));
+        try!(sockopt::set(socket.fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1 as libc::c_int));
+        let (name, len) = sockopt::from_socket_addr(addr);
+        cvt(unsafe { libc::bind(socket.fd, &name as *const _ as *const libc::sockaddr, len) })?;
+        cvt(unsafe { libc::listen(socket.fd, 1024) })?;
+        let io = try!(PollEvented2::new_with_handle(socket, handle));
+        Ok(SctpListener { io: io })
+    }
+
+    /// Attempts to accept an association, returning a "would block" error if
+    /// none is pending and notifying the current task once one arrives.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if it is called outside the context of a
+    /// future's task.
+    pub fn accept(&mut self) -> io::Result<(SctpStream, SocketAddr)> {
+        if let Async::NotReady = self.io.poll_read_ready(mio::Ready::readable())? {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "not ready"))
+        }
+
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        let fd = unsafe {
+            libc::accept4(self.io.get_ref().fd,
+                          &mut storage as *mut _ as *mut libc::sockaddr,
+                          &mut len,
+                          libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC)
+        };
+        if fd < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::WouldBlock {
+                self.io.clear_read_ready(mio::Ready::readable())?;
+            }
+            return Err(e)
+        }
+        let socket = Socket { fd: fd };
+        try!(socket.recv_rcvinfo());
+        let addr = try!(sockopt::to_socket_addr(&storage));
+        Ok((SctpStream { io: PollEvented2::new(socket) }, addr))
+    }
+
+    /// Returns the local address this listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().name(libc::getsockname)
+    }
+
+    /// Consumes this listener, returning a stream of the associations it
+    /// accepts.
+    pub fn incoming(self) -> SctpIncoming {
+        SctpIncoming { inner: self }
+    }
+}
+
+impl fmt::Debug for SctpListener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SctpListener")
+         .field("fd", &self.io.get_ref().fd)
+         .finish()
+    }
+}
+
+impl AsRawFd for SctpListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.get_ref().fd
+    }
+}
+
+impl Stream for SctpIncoming {
+    type Item = (SctpStream, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+        Ok(Async::Ready(Some(try_nb!(self.inner.accept()))))
+    }
+}
+
+impl fmt::Debug for SctpIncoming {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SctpIncoming")
+         .field("listener", &self.inner)
+         .finish()
+    }
+}
+
+impl SctpStream {
+    /// Establishes an SCTP association with the specified address.
+    ///
+    /// The number of outbound streams is left at the system default, which
+    /// is 10 on Linux.
+    pub fn connect(addr: &SocketAddr, handle: &Handle) -> SctpStreamNew {
+        SctpStream::connect_with_streams(addr, 0, handle)
+    }
+
+    /// Establishes an SCTP association with the specified address, asking
+    /// for `streams` outbound streams.
+    ///
+    /// The peer may grant fewer streams than requested. Passing 0 keeps the
+    /// system default.
+    pub fn connect_with_streams(addr: &SocketAddr, streams: u16, handle: &Handle)
+                                -> SctpStreamNew {
+        let stream = (|| {
+            let socket = try!(Socket::new(addr));
+            if streams > 0 {
+                let init = InitMsg {
+                    num_ostreams: streams,
+                    max_instreams: streams,
+                    max_attempts: 0,
+                    max_init_timeo: 0,
+                };
+                try!(sockopt::set(socket.fd, SOL_SCTP, SCTP_INITMSG, init));
+            }
+            try!(socket.recv_rcvinfo());
+            let (name, len) = sockopt::from_socket_addr(addr);
+            let r = unsafe {
+                libc::connect(socket.fd, &name as *const _ as *const libc::sockaddr, len)
+            };
+            if r < 0 {
+                let e = io::Error::last_os_error();
+                if e.raw_os_error() != Some(libc::EINPROGRESS) {
+                    return Err(e)
+                }
+            }
+            let io = try!(PollEvented2::new_with_handle(socket, handle));
+            Ok(SctpStream { io: io })
+        })();
+        SctpStreamNew { inner: Some(stream) }
+    }
+
+    /// Sends `buf` as one message on the stream numbered `stream` of this
+    /// association.
+    ///
+    /// Messages sent with `write` go on stream 0. If the message can't be
+    /// sent right away a "would block" error is returned and the current task
+    /// is notified once the association is writable again.
+    pub fn send_on(&self, stream: u16, buf: &[u8]) -> io::Result<usize> {
+        if let Async::NotReady = self.io.poll_write_ready()? {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        let info = SndInfo { sid: stream, flags: 0, ppid: 0, context: 0, assoc_id: 0 };
+        let mut control = Vec::new();
+        sockopt::push_control(&mut control, SOL_SCTP, SCTP_SNDINFO, info);
+        let r = sockopt::send_msg(self.io.get_ref().fd, buf, None, &control);
+        if let Err(ref e) = r {
+            if e.kind() == io::ErrorKind::WouldBlock {
+                self.io.clear_write_ready()?;
+            }
+        }
+        r
+    }
+
+    /// Receives data from this association, returning the number of bytes
+    /// read and the number of the stream the data arrived on.
+    ///
+    /// A message larger than `buf` is returned in several parts. If no data
+    /// is available a "would block" error is returned and the current task is
+    /// notified once there is.
+    pub fn recv_from_stream(&self, buf: &mut [u8]) -> io::Result<(usize, u16)> {
+        if let Async::NotReady = self.io.poll_read_ready(mio::Ready::readable())? {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        let mut control = [0; 64];
+        let msg = match sockopt::recv_msg(self.io.get_ref().fd, buf, &mut control, 0) {
+            Ok(msg) => msg,
+            Err(e) => {
+                if e.kind() == io::ErrorKind::WouldBlock {
+                    self.io.clear_read_ready(mio::Ready::readable())?;
+                }
+                return Err(e)
+            }
+        };
+        let mut stream = 0;
+        for (level, kind, data) in sockopt::control_messages(&control[..msg.control_len]) {
+            // struct sctp_rcvinfo starts with the stream number
+            if level == SOL_SCTP && kind == SCTP_RCVINFO && data.len() >= 2 {
+                stream = unsafe { ::std::ptr::read_unaligned(data.as_ptr() as *const u16) };
+            }
+        }
+        Ok((msg.len, stream))
+    }
+
+    /// Test whether this association is ready to be read or not.
+    ///
+    /// If it's not then the current task is scheduled to get a notification
+    /// once it is.
+    pub fn poll_read(&self) -> Async<()> {
+        self.io.poll_read_ready(mio::Ready::readable())
+            .map(|r| if r.is_ready() { Async::Ready(()) } else { Async::NotReady })
+            .unwrap_or(().into())
+    }
+
+    /// Test whether this association is ready to be written to or not.
+    ///
+    /// If it's not then the current task is scheduled to get a notification
+    /// once it is.
+    pub fn poll_write(&self) -> Async<()> {
+        self.io.poll_write_ready()
+            .map(|r| if r.is_ready() { Async::Ready(()) } else { Async::NotReady })
+            .unwrap_or(().into())
+    }
+
+    /// Returns the local address of this association.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().name(libc::getsockname)
+    }
+
+    /// Returns the address of the peer of this association.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.io.get_ref().name(libc::getpeername)
+    }
+
+    /// Shuts down the read, write, or both halves of this association.
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        let how = match how {
+            Shutdown::Read => libc::SHUT_RD,
+            Shutdown::Write => libc::SHUT_WR,
+            Shutdown::Both => libc::SHUT_RDWR,
+        };
+        cvt(unsafe { libc::shutdown(self.io.get_ref().fd, how) }).map(|_| ())
+    }
+}
+
+impl Read for SctpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.read(buf)
+    }
+}
+
+impl Write for SctpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncRead for SctpStream {
+    unsafe fn prepare_uninitialized_buffer(&self, _: &mut [u8]) -> bool {
+        false
+    }
+
+    fn read_buf<B: BufMut>(&mut self, buf: &mut B) -> Poll<usize, io::Error> {
+        // `Socket` reads with `read(2)`, which only writes to the buffer.
+        unsafe { self.io.read_buf_uninit(buf) }
+    }
+}
+
+impl AsyncWrite for SctpStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        Ok(().into())
+    }
+}
+
+impl fmt::Debug for SctpStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SctpStream")
+         .field("fd", &self.io.get_ref().fd)
+         .finish()
+    }
+}
+
+impl AsRawFd for SctpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.io.get_ref().fd
+    }
+}
+
+impl Future for SctpStreamNew {
+    type Item = SctpStream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<SctpStream, io::Error> {
+        {
+            let stream = match self.inner {
+                Some(Ok(ref stream)) => stream,
+                Some(Err(_)) => return Err(self.inner.take().unwrap().err().unwrap()),
+                None => panic!("can't poll SCTP stream twice"),
+            };
+
+            // As with TCP the association is established once the socket is
+            // writable, and whether that worked is in `SO_ERROR`.
+            if let Async::NotReady = stream.io.poll_write_ready()? {
+                return Ok(Async::NotReady)
+            }
+            let err: libc::c_int = try!(sockopt::get(stream.io.get_ref().fd,
+                                                     libc::SOL_SOCKET,
+                                                     libc::SO_ERROR));
+            if err != 0 {
+                self.inner = None;
+                return Err(io::Error::from_raw_os_error(err))
+            }
+        }
+        match self.inner.take() {
+            Some(Ok(stream)) => Ok(Async::Ready(stream)),
+            _ => panic!(),
+        }
+    }
+}
+
+impl fmt::Debug for SctpStreamNew {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SctpStreamNew").finish()
+    }
+}
+
+/// A nonblocking one-to-one style SCTP socket.
+struct Socket {
+    fd: RawFd,
+}
+
+impl Socket {
+    fn new(addr: &SocketAddr) -> io::Result<Socket> {
+        let family = match *addr {
+            SocketAddr::V4(..) => libc::AF_INET,
+            SocketAddr::V6(..) => libc::AF_INET6,
+        };
+        let fd = unsafe {
+            libc::socket(family,
+                         libc::SOCK_STREAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                         libc::IPPROTO_SCTP)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(Socket { fd: fd })
+    }
+
+    /// Asks for the stream number of received data to be reported.
+    fn recv_rcvinfo(&self) -> io::Result<()> {
+        sockopt::set(self.fd, SOL_SCTP, SCTP_RECVRCVINFO, 1 as libc::c_int)
+    }
+
+    fn name(&self,
+            f: unsafe extern fn(libc::c_int, *mut libc::sockaddr, *mut libc::socklen_t)
+                                -> libc::c_int)
+            -> io::Result<SocketAddr> {
+        let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        cvt(unsafe { f(self.fd, &mut storage as *mut _ as *mut libc::sockaddr, &mut len) })?;
+        sockopt::to_socket_addr(&storage)
+    }
+}
+
+impl Read for Socket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = unsafe {
+            libc::recv(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0)
+        };
+        cvt(n as libc::c_int).map(|_| n as usize)
+    }
+}
+
+impl Write for Socket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = unsafe {
+            libc::send(self.fd,
+                       buf.as_ptr() as *const libc::c_void,
+                       buf.len(),
+                       libc::MSG_NOSIGNAL)
+        };
+        cvt(n as libc::c_int).map(|_| n as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Evented for Socket {
+    fn register(&self, poll: &mio::Poll, token: Token, interest: Ready, opts: PollOpt)
+                -> io::Result<()> {
+        EventedFd(&self.fd).register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &mio::Poll, token: Token, interest: Ready, opts: PollOpt)
+                  -> io::Result<()> {
+        EventedFd(&self.fd).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
+        EventedFd(&self.fd).deregister(poll)
+    }
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+fn cvt(r: libc::c_int) -> io::Result<libc::c_int> {
+    if r < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(r)
+    }
+}
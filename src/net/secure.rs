@@ -0,0 +1,229 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::{Async, Future, IntoFuture, Poll, Stream};
+
+use net::{Incoming, TcpStream, TcpStreamNew};
+use net::hook::{self, Hooked};
+use reactor::{Handle, Timeout};
+
+/// A handshake which secures a freshly established connection, such as a TLS
+/// handshake.
+///
+/// This is the integration point for TLS implementations, or any other
+/// protocol which has to run before a connection can be used. It's
+/// implemented by anything which can turn a stream into a future of a secured
+/// stream, including closures, so wrapping e.g. a TLS connector takes little
+/// more than `|stream| connector.connect(domain, stream)`.
+///
+/// `TcpStream::connect_secured` and `Incoming::secured` then take care of
+/// composing the handshake with connecting or accepting, bounding the whole
+/// thing by a timeout and mapping errors into `io::Error`s.
+pub trait Handshake<S> {
+    /// The secured stream produced by a successful handshake.
+    type Stream;
+
+    /// The error produced by a failed handshake.
+    type Error: Into<Box<Error + Send + Sync>>;
+
+    /// The future performing the handshake.
+    type Future: Future<Item = Self::Stream, Error = Self::Error>;
+
+    /// Starts a handshake over `stream`.
+    fn handshake(&self, stream: S) -> Self::Future;
+}
+
+impl<S, F, R> Handshake<S> for F
+    where F: Fn(S) -> R,
+          R: IntoFuture,
+          R::Error: Into<Box<Error + Send + Sync>>,
+{
+    type Stream = R::Item;
+    type Error = R::Error;
+    type Future = R::Future;
+
+    fn handshake(&self, stream: S) -> R::Future {
+        self(stream).into_future()
+    }
+}
+
+/// Future returned by `TcpStream::connect_secured` which resolves to a
+/// secured stream once both the connection and the handshake have completed.
+#[must_use = "futures do nothing unless polled"]
+pub struct ConnectSecured<H: Handshake<TcpStream>> {
+    state: State<H>,
+    timeout: Option<Timeout>,
+}
+
+enum State<H: Handshake<TcpStream>> {
+    Connecting(TcpStreamNew, H),
+    Handshaking(H::Future),
+    Error(io::Error),
+    Empty,
+}
+
+pub fn connect<H>(addr: &SocketAddr,
+                  handshake: H,
+                  timeout: Duration,
+                  handle: &Handle) -> ConnectSecured<H>
+    where H: Handshake<TcpStream>,
+{
+    match Timeout::new(timeout, handle) {
+        Ok(t) => {
+            ConnectSecured {
+                state: State::Connecting(TcpStream::connect(addr, handle), handshake),
+                timeout: Some(t),
+            }
+        }
+        Err(e) => ConnectSecured { state: State::Error(e), timeout: None },
+    }
+}
+
+impl<H: Handshake<TcpStream>> Future for ConnectSecured<H> {
+    type Item = H::Stream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<H::Stream, io::Error> {
+        loop {
+            match ::std::mem::replace(&mut self.state, State::Empty) {
+                State::Connecting(mut connect, handshake) => {
+                    match try!(connect.poll()) {
+                        Async::Ready(stream) => {
+                            self.state = State::Handshaking(handshake.handshake(stream));
+                        }
+                        Async::NotReady => {
+                            self.state = State::Connecting(connect, handshake);
+                            break
+                        }
+                    }
+                }
+                State::Handshaking(mut f) => {
+                    match f.poll() {
+                        Ok(Async::Ready(stream)) => return Ok(Async::Ready(stream)),
+                        Ok(Async::NotReady) => {
+                            self.state = State::Handshaking(f);
+                            break
+                        }
+                        Err(e) => return Err(handshake_error(e)),
+                    }
+                }
+                State::Error(e) => return Err(e),
+                State::Empty => panic!("can't poll ConnectSecured twice"),
+            }
+        }
+
+        match self.timeout.as_mut().map(|t| t.poll()) {
+            Some(Ok(Async::Ready(()))) => {
+                self.state = State::Empty;
+                Err(timed_out())
+            }
+            Some(Err(e)) => Err(e),
+            _ => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl<H: Handshake<TcpStream>> fmt::Debug for ConnectSecured<H> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConnectSecured").finish()
+    }
+}
+
+/// Stream returned by `Incoming::secured`, which runs a handshake on each
+/// accepted connection and yields the secured streams.
+#[must_use = "streams do nothing unless polled"]
+pub struct Secured<H: Handshake<TcpStream>> {
+    inner: Hooked<Box<FnMut(TcpStream, SocketAddr) -> Accept<H::Future>>, Accept<H::Future>>,
+}
+
+/// A handshake on an accepted connection, bounded by a timeout.
+struct Accept<F> {
+    inner: Result<(F, Timeout), Option<io::Error>>,
+    addr: SocketAddr,
+}
+
+pub fn secured<H>(incoming: Incoming,
+                  handshake: H,
+                  timeout: Duration,
+                  max_in_flight: usize,
+                  handle: &Handle) -> Secured<H>
+    where H: Handshake<TcpStream> + 'static,
+{
+    let handle = handle.clone();
+    let f = move |stream, addr| {
+        Accept {
+            inner: match Timeout::new(timeout, &handle) {
+                Ok(t) => Ok((handshake.handshake(stream), t)),
+                Err(e) => Err(Some(e)),
+            },
+            addr: addr,
+        }
+    };
+    let f = Box::new(f) as Box<FnMut(TcpStream, SocketAddr) -> Accept<H::Future>>;
+    Secured { inner: hook::new(incoming, max_in_flight, f) }
+}
+
+impl<H: Handshake<TcpStream>> Secured<H> {
+    /// Returns the number of accepted connections whose handshake hasn't
+    /// completed yet.
+    pub fn in_flight(&self) -> usize {
+        self.inner.in_flight()
+    }
+}
+
+impl<H: Handshake<TcpStream>> Stream for Secured<H> {
+    type Item = (H::Stream, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<(H::Stream, SocketAddr)>, io::Error> {
+        self.inner.poll()
+    }
+}
+
+impl<H: Handshake<TcpStream>> fmt::Debug for Secured<H> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Secured")
+         .field("in_flight", &self.in_flight())
+         .finish()
+    }
+}
+
+impl<F> Future for Accept<F>
+    where F: Future,
+          F::Error: Into<Box<Error + Send + Sync>>,
+{
+    type Item = (F::Item, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(F::Item, SocketAddr), io::Error> {
+        let (f, timeout) = match self.inner {
+            Ok((ref mut f, ref mut timeout)) => (f, timeout),
+            Err(ref mut e) => return Err(e.take().expect("can't poll Accept twice")),
+        };
+        match f.poll() {
+            Ok(Async::Ready(stream)) => return Ok(Async::Ready((stream, self.addr))),
+            Ok(Async::NotReady) => {}
+            Err(e) => return Err(handshake_error(e)),
+        }
+        match try!(timeout.poll()) {
+            Async::Ready(()) => Err(timed_out()),
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
+/// Converts the error of a failed handshake into an `io::Error`, without
+/// wrapping errors which already are one.
+fn handshake_error<E: Into<Box<Error + Send + Sync>>>(e: E) -> io::Error {
+    match e.into().downcast::<io::Error>() {
+        Ok(e) => *e,
+        Err(e) => io::Error::new(io::ErrorKind::Other, e),
+    }
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "connection was not secured in time")
+}
@@ -0,0 +1,429 @@
+//! Helpers for socket options and addresses which aren't exposed by the
+//! standard library, `mio` or `net2`.
+
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::unix::prelude::*;
+
+use iovec::{self, IoVec};
+use libc;
+
+/// Sets a socket option to `val`.
+pub fn set<T>(fd: RawFd, level: libc::c_int, name: libc::c_int, val: T) -> io::Result<()> {
+    let r = unsafe {
+        libc::setsockopt(fd,
+                         level,
+                         name,
+                         &val as *const T as *const libc::c_void,
+                         mem::size_of::<T>() as libc::socklen_t)
+    };
+    if r < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(())
+}
+
+/// Reads a socket option, which must be a plain value of type `T`.
+pub fn get<T: Copy>(fd: RawFd, level: libc::c_int, name: libc::c_int) -> io::Result<T> {
+    unsafe {
+        let mut val: T = mem::zeroed();
+        let mut len = mem::size_of::<T>() as libc::socklen_t;
+        let r = libc::getsockopt(fd,
+                                 level,
+                                 name,
+                                 &mut val as *mut T as *mut libc::c_void,
+                                 &mut len);
+        if r < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(val)
+    }
+}
+
+/// Converts a socket address returned by the kernel into a `SocketAddr`.
+pub fn to_socket_addr(storage: &libc::sockaddr_storage) -> io::Result<SocketAddr> {
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET => {
+            let addr = unsafe { &*(storage as *const _ as *const libc::sockaddr_in) };
+            let ip = Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr));
+            Ok(SocketAddr::V4(SocketAddrV4::new(ip, u16::from_be(addr.sin_port))))
+        }
+        libc::AF_INET6 => {
+            let addr = unsafe { &*(storage as *const _ as *const libc::sockaddr_in6) };
+            let ip = Ipv6Addr::from(addr.sin6_addr.s6_addr);
+            Ok(SocketAddr::V6(SocketAddrV6::new(ip,
+                                                u16::from_be(addr.sin6_port),
+                                                addr.sin6_flowinfo,
+                                                addr.sin6_scope_id)))
+        }
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported address family")),
+    }
+}
+
+/// Converts a `SocketAddr` into a socket address to pass to the kernel.
+pub fn from_socket_addr(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = match *addr {
+        SocketAddr::V4(ref addr) => {
+            let sin = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(ref addr) => {
+            let sin6 = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_addr.s6_addr = addr.ip().octets();
+            sin6.sin6_flowinfo = addr.flowinfo();
+            sin6.sin6_scope_id = addr.scope_id();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+    (storage, len as libc::socklen_t)
+}
+
+/// Appends a control message holding `val` to `control`, for use with
+/// `send_msg`.
+pub fn push_control<T>(control: &mut Vec<u8>, level: libc::c_int, kind: libc::c_int, val: T) {
+    unsafe {
+        let start = control.len();
+        let space = libc::CMSG_SPACE(mem::size_of::<T>() as u32) as usize;
+        control.resize(start + space, 0);
+
+        let mut header: libc::cmsghdr = mem::zeroed();
+        header.cmsg_len = libc::CMSG_LEN(mem::size_of::<T>() as u32) as _;
+        header.cmsg_level = level;
+        header.cmsg_type = kind;
+        let ptr = control[start..].as_mut_ptr();
+        ::std::ptr::write_unaligned(ptr as *mut libc::cmsghdr, header);
+        let data = ptr.offset(libc::CMSG_LEN(0) as isize);
+        ::std::ptr::write_unaligned(data as *mut T, val);
+    }
+}
+
+/// Sends a datagram, to `addr` if given, along with the given control
+/// messages.
+pub fn send_msg(fd: RawFd, buf: &[u8], addr: Option<&SocketAddr>, control: &[u8])
+                -> io::Result<usize> {
+    let iov = libc::iovec {
+        iov_base: buf.as_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    send_msg_iov(fd, &[iov], addr, control)
+}
+
+/// Sends a datagram made up of the concatenation of `bufs`, like `send_msg`.
+pub fn send_msg_vec(fd: RawFd, bufs: &[&IoVec], addr: Option<&SocketAddr>, control: &[u8])
+                    -> io::Result<usize> {
+    send_msg_iov(fd, iovec::unix::as_os_slice(bufs), addr, control)
+}
+
+fn send_msg_iov(fd: RawFd, iov: &[libc::iovec], addr: Option<&SocketAddr>, control: &[u8])
+                -> io::Result<usize> {
+    unsafe {
+        let mut msg: libc::msghdr = mem::zeroed();
+        let mut name = addr.map(from_socket_addr);
+        if let Some((ref mut name, len)) = name {
+            msg.msg_name = name as *mut _ as *mut libc::c_void;
+            msg.msg_namelen = len;
+        }
+        // Only ever read from by the kernel.
+        msg.msg_iov = iov.as_ptr() as *mut libc::iovec;
+        msg.msg_iovlen = iov.len() as _;
+        if !control.is_empty() {
+            msg.msg_control = control.as_ptr() as *mut libc::c_void;
+            msg.msg_controllen = control.len() as _;
+        }
+        let n = libc::sendmsg(fd, &msg, 0);
+        if n < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(n as usize)
+    }
+}
+
+/// A datagram received with `recv_msg`.
+pub struct Msg {
+    /// The number of bytes written to the buffer.
+    pub len: usize,
+    /// The address the datagram was sent from, if the socket isn't connected.
+    pub addr: Option<SocketAddr>,
+    /// The number of bytes of control messages written to the control buffer.
+    pub control_len: usize,
+    /// The flags the kernel returned along with the datagram, such as
+    /// `MSG_TRUNC` if it didn't fit the buffer.
+    pub flags: libc::c_int,
+}
+
+/// Receives a datagram along with its control messages.
+pub fn recv_msg(fd: RawFd,
+                buf: &mut [u8],
+                control: &mut [u8],
+                flags: libc::c_int) -> io::Result<Msg> {
+    unsafe {
+        let mut addr: libc::sockaddr_storage = mem::zeroed();
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_name = &mut addr as *mut _ as *mut libc::c_void;
+        msg.msg_namelen = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        if !control.is_empty() {
+            msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+            msg.msg_controllen = control.len() as _;
+        }
+        let n = libc::recvmsg(fd, &mut msg, flags);
+        if n < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(Msg {
+            len: n as usize,
+            addr: if msg.msg_namelen > 0 { to_socket_addr(&addr).ok() } else { None },
+            control_len: msg.msg_controllen as usize,
+            flags: msg.msg_flags,
+        })
+    }
+}
+
+/// Iterates over the control messages in `control`, as filled in by
+/// `recv_msg`, yielding their level, type and data.
+pub fn control_messages(control: &[u8]) -> ControlMessages<'_> {
+    ControlMessages { buf: control }
+}
+
+pub struct ControlMessages<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for ControlMessages<'a> {
+    type Item = (libc::c_int, libc::c_int, &'a [u8]);
+
+    fn next(&mut self) -> Option<(libc::c_int, libc::c_int, &'a [u8])> {
+        let header_len = mem::size_of::<libc::cmsghdr>();
+        if self.buf.len() < header_len {
+            return None
+        }
+        let header = unsafe {
+            ::std::ptr::read_unaligned(self.buf.as_ptr() as *const libc::cmsghdr)
+        };
+        let data_offset = unsafe { libc::CMSG_LEN(0) } as usize;
+        let len = header.cmsg_len as usize;
+        if len < data_offset || len > self.buf.len() {
+            return None
+        }
+        let item = (header.cmsg_level, header.cmsg_type, &self.buf[data_offset..len]);
+        let next = unsafe { libc::CMSG_SPACE((len - data_offset) as u32) } as usize;
+        self.buf = &self.buf[::std::cmp::min(next, self.buf.len())..];
+        Some(item)
+    }
+}
+
+/// Reads a socket address out of control message data.
+pub fn control_socket_addr(data: &[u8]) -> io::Result<SocketAddr> {
+    let mut storage: libc::sockaddr_storage = unsafe { mem::zeroed() };
+    let len = ::std::cmp::min(data.len(), mem::size_of::<libc::sockaddr_storage>());
+    unsafe {
+        ::std::ptr::copy_nonoverlapping(data.as_ptr(),
+                                        &mut storage as *mut _ as *mut u8,
+                                        len);
+    }
+    to_socket_addr(&storage)
+}
+
+/// Sets `IP_TRANSPARENT` or `IPV6_TRANSPARENT` on a socket of the family of
+/// `addr`, allowing it to bind to and send from non-local addresses.
+#[cfg(target_os = "linux")]
+pub fn set_transparent(fd: RawFd, addr: &SocketAddr) -> io::Result<()> {
+    match *addr {
+        SocketAddr::V4(..) => set(fd, libc::SOL_IP, libc::IP_TRANSPARENT, 1 as libc::c_int),
+        SocketAddr::V6(..) => set(fd, libc::SOL_IPV6, libc::IPV6_TRANSPARENT, 1 as libc::c_int),
+    }
+}
+
+/// Sets `IPV6_TCLASS`, the traffic class of packets sent from an IPv6 socket.
+#[cfg(target_os = "linux")]
+pub fn set_traffic_class(fd: RawFd, tclass: u32) -> io::Result<()> {
+    if tclass > 0xff {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "traffic class must fit in 8 bits"))
+    }
+    set(fd, libc::SOL_IPV6, libc::IPV6_TCLASS, tclass as libc::c_int)
+}
+
+/// Reads `IPV6_TCLASS`, see `set_traffic_class`.
+#[cfg(target_os = "linux")]
+pub fn traffic_class(fd: RawFd) -> io::Result<u32> {
+    get::<libc::c_int>(fd, libc::SOL_IPV6, libc::IPV6_TCLASS).map(|t| t as u32)
+}
+
+// From linux/in6.h, which libc doesn't have bindings for
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+#[allow(non_camel_case_types)]
+struct in6_flowlabel_req {
+    flr_dst: libc::in6_addr,
+    flr_label: u32,
+    flr_action: u8,
+    flr_share: u8,
+    flr_flags: u16,
+    flr_expires: u16,
+    flr_linger: u16,
+    __flr_pad: u32,
+}
+
+#[cfg(target_os = "linux")]
+const IPV6_FL_A_GET: u8 = 0;
+#[cfg(target_os = "linux")]
+const IPV6_FL_S_USER: u8 = 3;
+#[cfg(target_os = "linux")]
+const IPV6_FL_F_CREATE: u16 = 1;
+
+/// Leases the flow label `label` for packets sent to `dst` from an IPv6
+/// socket, and enables `IPV6_FLOWINFO_SEND` so that the label is taken from
+/// the flow information of the addresses passed to `connect` and `sendto`,
+/// see `with_flow_label`.
+///
+/// Linux only sends flow labels which have been leased by the socket. The
+/// lease is shared with other sockets of the same user, so that the label
+/// can be reused before an earlier lease has expired. A label of zero needs
+/// no lease, and just enables sending flow information.
+#[cfg(target_os = "linux")]
+pub fn set_flow_label(fd: RawFd, dst: &Ipv6Addr, label: u32) -> io::Result<()> {
+    if label & !(libc::IPV6_FLOWINFO_FLOWLABEL as u32) != 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  "flow label must fit in 20 bits"))
+    }
+    if label == 0 {
+        return set(fd, libc::SOL_IPV6, libc::IPV6_FLOWINFO_SEND, 1 as libc::c_int)
+    }
+    let req = in6_flowlabel_req {
+        flr_dst: libc::in6_addr { s6_addr: dst.octets() },
+        flr_label: label.to_be(),
+        flr_action: IPV6_FL_A_GET,
+        flr_share: IPV6_FL_S_USER,
+        flr_flags: IPV6_FL_F_CREATE,
+        flr_expires: 0,
+        flr_linger: 0,
+        __flr_pad: 0,
+    };
+    try!(set(fd, libc::SOL_IPV6, libc::IPV6_FLOWLABEL_MGR, req));
+    set(fd, libc::SOL_IPV6, libc::IPV6_FLOWINFO_SEND, 1 as libc::c_int)
+}
+
+/// Returns `addr` with the flow label in its flow information set to
+/// `label`.
+///
+/// The flow information of a `SocketAddrV6` is passed to the kernel as is,
+/// which expects it in network byte order.
+#[cfg(target_os = "linux")]
+pub fn with_flow_label(addr: &SocketAddrV6, label: u32) -> SocketAddrV6 {
+    let mask = (libc::IPV6_FLOWINFO_FLOWLABEL as u32).to_be();
+    let flowinfo = (addr.flowinfo() & !mask) | (label.to_be() & mask);
+    SocketAddrV6::new(*addr.ip(), addr.port(), flowinfo, addr.scope_id())
+}
+
+/// Binds a socket of the family of `addr` to the network interface named
+/// `name`, so that it only sends and receives packets through it, or removes
+/// the binding if `name` is `None`.
+///
+/// This is `SO_BINDTODEVICE` on Linux, which needs the `CAP_NET_RAW`
+/// capability unless it's removing the binding.
+#[cfg(target_os = "linux")]
+pub fn bind_device(fd: RawFd, _addr: &SocketAddr, name: Option<&str>) -> io::Result<()> {
+    let name = name.unwrap_or("");
+    if name.len() >= libc::IF_NAMESIZE || name.contains('\0') {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name"))
+    }
+    let r = unsafe {
+        libc::setsockopt(fd,
+                         libc::SOL_SOCKET,
+                         libc::SO_BINDTODEVICE,
+                         name.as_ptr() as *const libc::c_void,
+                         name.len() as libc::socklen_t)
+    };
+    if r < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    Ok(())
+}
+
+/// Returns the name of the network interface a socket is bound to with
+/// `bind_device`, if any.
+#[cfg(target_os = "linux")]
+pub fn bound_device(fd: RawFd, _addr: &SocketAddr) -> io::Result<Option<String>> {
+    let mut name = [0u8; libc::IF_NAMESIZE];
+    let mut len = name.len() as libc::socklen_t;
+    let r = unsafe {
+        libc::getsockopt(fd,
+                         libc::SOL_SOCKET,
+                         libc::SO_BINDTODEVICE,
+                         name.as_mut_ptr() as *mut libc::c_void,
+                         &mut len)
+    };
+    if r < 0 {
+        return Err(io::Error::last_os_error())
+    }
+    let len = name[..len as usize].iter().position(|&b| b == 0).unwrap_or(len as usize);
+    if len == 0 {
+        return Ok(None)
+    }
+    Ok(Some(String::from_utf8_lossy(&name[..len]).into_owned()))
+}
+
+/// Binds a socket of the family of `addr` to the network interface named
+/// `name`, so that it only sends and receives packets through it, or removes
+/// the binding if `name` is `None`.
+///
+/// This is `IP_BOUND_IF` or `IPV6_BOUND_IF` on Apple platforms, which take
+/// the index of the interface.
+#[cfg(any(target_os = "ios", target_os = "macos"))]
+pub fn bind_device(fd: RawFd, addr: &SocketAddr, name: Option<&str>) -> io::Result<()> {
+    let index = match name {
+        Some(name) => try!(interface_index(name)),
+        None => 0,
+    };
+    match *addr {
+        SocketAddr::V4(..) => set(fd, libc::IPPROTO_IP, libc::IP_BOUND_IF, index as libc::c_int),
+        SocketAddr::V6(..) => {
+            set(fd, libc::IPPROTO_IPV6, libc::IPV6_BOUND_IF, index as libc::c_int)
+        }
+    }
+}
+
+/// Returns the name of the network interface a socket is bound to with
+/// `bind_device`, if any.
+#[cfg(any(target_os = "ios", target_os = "macos"))]
+pub fn bound_device(fd: RawFd, addr: &SocketAddr) -> io::Result<Option<String>> {
+    let index = try!(match *addr {
+        SocketAddr::V4(..) => get::<libc::c_int>(fd, libc::IPPROTO_IP, libc::IP_BOUND_IF),
+        SocketAddr::V6(..) => get::<libc::c_int>(fd, libc::IPPROTO_IPV6, libc::IPV6_BOUND_IF),
+    });
+    if index == 0 {
+        return Ok(None)
+    }
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+    let r = unsafe { libc::if_indextoname(index as libc::c_uint, name.as_mut_ptr()) };
+    if r.is_null() {
+        return Err(io::Error::last_os_error())
+    }
+    let name = unsafe { ::std::ffi::CStr::from_ptr(name.as_ptr()) };
+    Ok(Some(name.to_string_lossy().into_owned()))
+}
+
+#[cfg(any(target_os = "ios", target_os = "macos"))]
+fn interface_index(name: &str) -> io::Result<u32> {
+    let name = try!(::std::ffi::CString::new(name).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidInput, "invalid interface name")
+    }));
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(io::Error::last_os_error()),
+        index => Ok(index),
+    }
+}
@@ -0,0 +1,263 @@
+//! A client for SOCKS5 proxies.
+//!
+//! This module implements the connect command of the SOCKS5 protocol as
+//! described in RFC 1928, with either no authentication or the
+//! username/password authentication of RFC 1929. The futures returned by
+//! `connect` and `connect_with_password` resolve to a `TcpStream` which is
+//! connected to the target through the proxy, ready to be used just like a
+//! direct connection.
+
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+
+use futures::{Future, Poll};
+use futures::future;
+use tokio_io::io::{read_exact, write_all};
+
+use net::TcpStream;
+use reactor::Handle;
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const PASSWORD_AUTH: u8 = 2;
+const NO_ACCEPTABLE_AUTH: u8 = 0xff;
+const PASSWORD_AUTH_VERSION: u8 = 1;
+const CMD_CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// The destination a SOCKS proxy is asked to connect to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Target {
+    /// An IP address and port.
+    Addr(SocketAddr),
+
+    /// A domain name and port, which the proxy resolves.
+    Domain(String, u16),
+}
+
+impl From<SocketAddr> for Target {
+    fn from(addr: SocketAddr) -> Target {
+        Target::Addr(addr)
+    }
+}
+
+impl<'a> From<(&'a str, u16)> for Target {
+    fn from((host, port): (&'a str, u16)) -> Target {
+        Target::Domain(host.to_string(), port)
+    }
+}
+
+impl From<(String, u16)> for Target {
+    fn from((host, port): (String, u16)) -> Target {
+        Target::Domain(host, port)
+    }
+}
+
+/// Future returned by `connect` and `connect_with_password`, which resolves
+/// to a stream connected to the target through the proxy.
+#[must_use = "futures do nothing unless polled"]
+pub struct Connect {
+    inner: Box<Future<Item = TcpStream, Error = io::Error>>,
+}
+
+impl Future for Connect {
+    type Item = TcpStream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<TcpStream, io::Error> {
+        self.inner.poll()
+    }
+}
+
+impl fmt::Debug for Connect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Connect").finish()
+    }
+}
+
+/// Connects to `target` through the SOCKS5 proxy at `proxy`, without
+/// authenticating.
+///
+/// The returned future fails if the proxy requires authentication, if it
+/// fails to connect to the target, or if it doesn't speak SOCKS5.
+pub fn connect<T>(proxy: &SocketAddr, target: T, handle: &Handle) -> Connect
+    where T: Into<Target>,
+{
+    new(proxy, target.into(), None, handle)
+}
+
+/// Connects to `target` through the SOCKS5 proxy at `proxy`, authenticating
+/// with the given username and password if the proxy asks for it.
+///
+/// The username and password must each be at most 255 bytes long. The
+/// returned future fails with an error of kind `PermissionDenied` if the
+/// proxy rejects the credentials.
+pub fn connect_with_password<T>(proxy: &SocketAddr,
+                                target: T,
+                                username: &str,
+                                password: &str,
+                                handle: &Handle) -> Connect
+    where T: Into<Target>,
+{
+    let auth = (username.as_bytes().to_vec(), password.as_bytes().to_vec());
+    new(proxy, target.into(), Some(auth), handle)
+}
+
+fn new(proxy: &SocketAddr,
+       target: Target,
+       auth: Option<(Vec<u8>, Vec<u8>)>,
+       handle: &Handle) -> Connect {
+    // Validate everything up front so no connection is made for a request
+    // which can't be encoded.
+    let request = match connect_request(&target) {
+        Ok(request) => request,
+        Err(e) => return Connect { inner: Box::new(future::err(e)) },
+    };
+    let auth = match auth {
+        Some((ref user, ref pass)) if user.len() > 255 || pass.len() > 255 => {
+            let e = invalid_input("SOCKS username and password must be at most 255 bytes");
+            return Connect { inner: Box::new(future::err(e)) }
+        }
+        Some((user, pass)) => {
+            let mut msg = vec![PASSWORD_AUTH_VERSION, user.len() as u8];
+            msg.extend_from_slice(&user);
+            msg.push(pass.len() as u8);
+            msg.extend_from_slice(&pass);
+            Some(msg)
+        }
+        None => None,
+    };
+    let greeting = match auth {
+        Some(_) => vec![VERSION, 2, NO_AUTH, PASSWORD_AUTH],
+        None => vec![VERSION, 1, NO_AUTH],
+    };
+
+    let negotiated = TcpStream::connect(proxy, handle).and_then(move |stream| {
+        write_all(stream, greeting)
+    }).and_then(|(stream, _)| {
+        read_exact(stream, [0; 2])
+    }).and_then(move |(stream, reply)| {
+        if reply[0] != VERSION {
+            return future::Either::A(future::err(invalid_data("proxy doesn't speak SOCKS5")))
+        }
+        match (reply[1], auth) {
+            (NO_AUTH, _) => future::Either::A(future::ok(stream)),
+            (PASSWORD_AUTH, Some(msg)) => {
+                future::Either::B(authenticate(stream, msg))
+            }
+            (NO_ACCEPTABLE_AUTH, _) => {
+                let e = io::Error::new(io::ErrorKind::PermissionDenied,
+                                       "SOCKS proxy requires unsupported authentication");
+                future::Either::A(future::err(e))
+            }
+            _ => {
+                let e = invalid_data("SOCKS proxy chose an authentication method not offered");
+                future::Either::A(future::err(e))
+            }
+        }
+    });
+
+    let connected = negotiated.and_then(move |stream| {
+        write_all(stream, request)
+    }).and_then(|(stream, _)| {
+        read_exact(stream, [0; 4])
+    }).and_then(|(stream, reply)| {
+        if reply[0] != VERSION {
+            return Err(invalid_data("invalid SOCKS reply"))
+        }
+        if reply[1] != 0 {
+            return Err(reply_error(reply[1]))
+        }
+        Ok((stream, reply[3]))
+    }).and_then(|(stream, atyp)| {
+        // The address the proxy bound to isn't of any use to us, but it has
+        // to be read so it isn't mistaken for data from the target.
+        let len = match atyp {
+            ATYP_IPV4 => future::Either::A(future::ok((stream, 4 + 2))),
+            ATYP_IPV6 => future::Either::A(future::ok((stream, 16 + 2))),
+            ATYP_DOMAIN => {
+                future::Either::B(read_exact(stream, [0; 1]).map(|(stream, len)| {
+                    (stream, len[0] as usize + 2)
+                }))
+            }
+            _ => future::Either::A(future::err(invalid_data("invalid SOCKS address type"))),
+        };
+        len.and_then(|(stream, len)| read_exact(stream, vec![0; len]))
+    }).map(|(stream, _)| stream);
+
+    Connect { inner: Box::new(connected) }
+}
+
+fn authenticate(stream: TcpStream, msg: Vec<u8>)
+                -> Box<Future<Item = TcpStream, Error = io::Error>> {
+    Box::new(write_all(stream, msg).and_then(|(stream, _)| {
+        read_exact(stream, [0; 2])
+    }).and_then(|(stream, reply)| {
+        if reply[0] != PASSWORD_AUTH_VERSION {
+            return Err(invalid_data("invalid SOCKS authentication reply"))
+        }
+        if reply[1] != 0 {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                      "SOCKS proxy rejected the credentials"))
+        }
+        Ok(stream)
+    }))
+}
+
+fn connect_request(target: &Target) -> io::Result<Vec<u8>> {
+    let mut msg = vec![VERSION, CMD_CONNECT, 0];
+    let port = match *target {
+        Target::Addr(addr) => {
+            match addr.ip() {
+                IpAddr::V4(ip) => {
+                    msg.push(ATYP_IPV4);
+                    msg.extend_from_slice(&ip.octets());
+                }
+                IpAddr::V6(ip) => {
+                    msg.push(ATYP_IPV6);
+                    msg.extend_from_slice(&ip.octets());
+                }
+            }
+            addr.port()
+        }
+        Target::Domain(ref host, port) => {
+            if host.is_empty() || host.len() > 255 {
+                return Err(invalid_input("SOCKS domain names must be 1 to 255 bytes long"))
+            }
+            msg.push(ATYP_DOMAIN);
+            msg.push(host.len() as u8);
+            msg.extend_from_slice(host.as_bytes());
+            port
+        }
+    };
+    msg.push((port >> 8) as u8);
+    msg.push(port as u8);
+    Ok(msg)
+}
+
+fn reply_error(code: u8) -> io::Error {
+    let (kind, msg) = match code {
+        1 => (io::ErrorKind::Other, "general SOCKS server failure"),
+        2 => (io::ErrorKind::PermissionDenied, "connection not allowed by SOCKS ruleset"),
+        3 => (io::ErrorKind::Other, "network unreachable"),
+        4 => (io::ErrorKind::Other, "host unreachable"),
+        5 => (io::ErrorKind::ConnectionRefused, "connection refused"),
+        6 => (io::ErrorKind::TimedOut, "TTL expired"),
+        7 => (io::ErrorKind::Other, "SOCKS command not supported"),
+        8 => (io::ErrorKind::Other, "SOCKS address type not supported"),
+        _ => (io::ErrorKind::Other, "unknown SOCKS error"),
+    };
+    io::Error::new(kind, msg)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn invalid_input(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
@@ -6,12 +6,19 @@ use std::time::Duration;
 
 use bytes::{Buf, BufMut};
 use futures::stream::Stream;
-use futures::{Future, Poll, Async};
+use futures::{Future, IntoFuture, Poll, Async};
 use iovec::IoVec;
 use mio;
 use tokio_io::{AsyncRead, AsyncWrite};
 
-use reactor::{Handle, PollEvented2};
+use net::distribute::{self, Balance, Distribute};
+use net::drain::{self, GuardedIncoming};
+use net::hook::{self, Hooked};
+use net::rate_limit::{self, RateLimited};
+use net::secure::{self, ConnectSecured, Handshake, Secured};
+#[cfg(target_os = "linux")]
+use net::sockopt;
+use reactor::{Handle, PollEvented2, Remote};
 
 /// An I/O object representing a TCP socket listening for incoming connections.
 ///
@@ -47,6 +54,65 @@ impl TcpListener {
         TcpListener::new2(l)
     }
 
+    /// Create a new TCP listener associated with this event loop, allowing
+    /// other sockets to bind to the same address.
+    ///
+    /// This is the same as `bind` except that `SO_REUSEPORT` (as well as
+    /// `SO_REUSEADDR`) is set on the socket before it's bound. Calling this
+    /// once on each of several event loops with the same `addr` gives every
+    /// event loop its own listener, and the kernel distributes incoming
+    /// connections between them. Unlike registering a single listener with
+    /// several event loops, only one of them is woken up for each incoming
+    /// connection.
+    ///
+    /// Note that on Linux each listener gets its own accept queue, and
+    /// connections queued on a listener are reset once it's closed. Other
+    /// platforms may not balance connections between the listeners at all.
+    ///
+    /// This function is only available on Unix platforms.
+    #[cfg(unix)]
+    pub fn bind_reuse_port(addr: &SocketAddr, handle: &Handle) -> io::Result<TcpListener> {
+        use net2::TcpBuilder;
+        use net2::unix::UnixTcpBuilderExt;
+
+        let builder = match *addr {
+            SocketAddr::V4(..) => try!(TcpBuilder::new_v4()),
+            SocketAddr::V6(..) => try!(TcpBuilder::new_v6()),
+        };
+        try!(builder.reuse_address(true));
+        try!(builder.reuse_port(true));
+        try!(builder.bind(addr));
+        let listener = try!(builder.listen(1024));
+        TcpListener::from_listener(listener, addr, handle)
+    }
+
+    /// Create a new TCP listener associated with this event loop which can
+    /// accept connections for addresses that aren't local to the system.
+    ///
+    /// This sets `IP_TRANSPARENT` (or `IPV6_TRANSPARENT`) on the socket before
+    /// it's bound, which is what's needed to receive connections redirected
+    /// with the `TPROXY` iptables target. The original destination of such
+    /// connections is the local address of the accepted stream, see
+    /// `TcpStream::original_destination`.
+    ///
+    /// Setting the option requires the `CAP_NET_ADMIN` capability. This
+    /// function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn bind_transparent(addr: &SocketAddr, handle: &Handle) -> io::Result<TcpListener> {
+        use std::os::unix::prelude::*;
+        use net2::TcpBuilder;
+
+        let builder = match *addr {
+            SocketAddr::V4(..) => try!(TcpBuilder::new_v4()),
+            SocketAddr::V6(..) => try!(TcpBuilder::new_v6()),
+        };
+        try!(builder.reuse_address(true));
+        try!(sockopt::set_transparent(builder.as_raw_fd(), addr));
+        try!(builder.bind(addr));
+        let listener = try!(builder.listen(1024));
+        TcpListener::from_listener(listener, addr, handle)
+    }
+
     /// Attempt to accept a connection and create a new connected `TcpStream` if
     /// successful.
     ///
@@ -129,9 +195,21 @@ impl TcpListener {
         TcpListener::new(l, handle)
     }
 
+    /// Create a new TCP listener from the standard library's TCP listener,
+    /// without an explicit `&Handle`.
+    ///
+    /// This is the same as `from_listener`, except that the listener isn't
+    /// registered with a reactor right away. Instead it's registered with the
+    /// default reactor the first time it's polled from within a task, so this
+    /// can be called from code which has no access to a `Handle`.
+    pub fn from_std(listener: net::TcpListener) -> io::Result<TcpListener> {
+        let l = try!(mio::net::TcpListener::from_std(listener));
+        TcpListener::new2(l)
+    }
+
     fn new(listener: mio::net::TcpListener, handle: &Handle)
            -> io::Result<TcpListener> {
-        let io = try!(PollEvented2::new_with_handle(listener, handle.new_tokio_handle()));
+        let io = try!(PollEvented2::new_with_handle(listener, handle));
         Ok(TcpListener { io: io })
     }
 
@@ -171,6 +249,44 @@ impl TcpListener {
         Incoming { inner: self }
     }
 
+    /// Consumes this listener, returning a stream of the sockets it accepts,
+    /// each handed out along with a guard tracking the connection.
+    ///
+    /// This allows for graceful shutdown: `GuardedIncoming::drain`, or
+    /// `DrainHandle::drain` while the stream is being driven elsewhere, stops
+    /// accepting and waits for the connections whose guards are still alive.
+    pub fn incoming_guarded(self) -> GuardedIncoming {
+        drain::new(self)
+    }
+
+    /// Consumes this listener, returning a future which accepts connections
+    /// and hands each of them off to one of the event loops in `remotes`.
+    ///
+    /// The returned future runs on the event loop this listener is
+    /// associated with. Each accepted socket is sent to the event loop chosen
+    /// by `balance`, registered with it there, and passed to `f` along with
+    /// the peer's address and a handle to that event loop. The future returned
+    /// by `f` is then spawned onto the same event loop, and the connection
+    /// counts towards that event loop's load until the future completes.
+    ///
+    /// Event loops which have been dropped are skipped. The returned future
+    /// only completes if accepting a connection fails, or once all the event
+    /// loops in `remotes` are gone.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `remotes` is empty.
+    pub fn distribute<F, R>(self,
+                            remotes: Vec<Remote>,
+                            balance: Balance,
+                            f: F) -> Distribute<F>
+        where F: Fn(TcpStream, SocketAddr, &Handle) -> R + Send + Sync + 'static,
+              R: IntoFuture<Item = (), Error = ()>,
+              R::Future: 'static,
+    {
+        distribute::new(self, remotes, balance, f)
+    }
+
     /// Sets the value for the `IP_TTL` option on this socket.
     ///
     /// This value sets the time-to-live field that is used in every packet sent
@@ -216,6 +332,70 @@ impl fmt::Debug for TcpListener {
     }
 }
 
+impl Incoming {
+    /// Runs `f` on every accepted connection before it's yielded, allowing at
+    /// most `max_in_flight` of the returned futures to run at once.
+    ///
+    /// This is intended for work which has to happen before a connection can
+    /// be served, such as a TLS handshake, checking the peer's address against
+    /// an allowlist or parsing a PROXY protocol header. While `max_in_flight`
+    /// hooks are running no further connections are accepted, leaving them in
+    /// the listener's backlog.
+    ///
+    /// The returned stream yields whatever the hooks resolve to, in the order
+    /// they complete. If a hook fails its connection is dropped and the error
+    /// is logged, whereas errors accepting connections are returned from the
+    /// stream.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max_in_flight` is zero.
+    pub fn hook<F, R>(self, max_in_flight: usize, f: F) -> Hooked<F, R::Future>
+        where F: FnMut(TcpStream, SocketAddr) -> R,
+              R: IntoFuture<Error = io::Error>,
+    {
+        hook::new(self, max_in_flight, f)
+    }
+
+    /// Runs `handshake` on every accepted connection, yielding the secured
+    /// streams along with the address of their peer.
+    ///
+    /// This works like `hook`, with at most `max_in_flight` handshakes running
+    /// at once, and is meant for server-side TLS. Each handshake has to
+    /// complete within `timeout`, otherwise the connection is dropped just
+    /// like when the handshake fails.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max_in_flight` is zero.
+    pub fn secured<H>(self,
+                      handshake: H,
+                      timeout: Duration,
+                      max_in_flight: usize,
+                      handle: &Handle) -> Secured<H>
+        where H: Handshake<TcpStream> + 'static,
+    {
+        secure::secured(self, handshake, timeout, max_in_flight, handle)
+    }
+
+    /// Limits the rate at which connections are accepted to `per_second`,
+    /// allowing bursts of up to `burst` connections at once.
+    ///
+    /// Unlike `hook`, which limits how many connections are being set up at
+    /// once, this limits how quickly they come in regardless of how quickly
+    /// they're dealt with, protecting the server against connection floods.
+    /// While the limit is reached no connections are accepted, leaving them
+    /// in the listener's backlog, and a timeout on the event loop of `handle`
+    /// resumes accepting once the next connection is allowed.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `per_second` or `burst` is zero.
+    pub fn rate_limited(self, per_second: u32, burst: u32, handle: &Handle) -> RateLimited {
+        rate_limit::new(self, per_second, burst, handle)
+    }
+}
+
 impl Stream for Incoming {
     type Item = (TcpStream, SocketAddr);
     type Error = io::Error;
@@ -265,6 +445,23 @@ impl TcpStream {
         TcpStreamNew { inner: inner }
     }
 
+    /// Connects to the specified address and secures the connection with
+    /// `handshake`, such as a TLS handshake.
+    ///
+    /// The returned future resolves to the stream produced by the handshake.
+    /// Connecting and the handshake together have to complete within
+    /// `timeout`, otherwise the future fails with an error of kind
+    /// `TimedOut`. Errors of the handshake are converted into `io::Error`s
+    /// of kind `Other`, unless they already are `io::Error`s.
+    pub fn connect_secured<H>(addr: &SocketAddr,
+                              handshake: H,
+                              timeout: Duration,
+                              handle: &Handle) -> ConnectSecured<H>
+        where H: Handshake<TcpStream>,
+    {
+        secure::connect(addr, handshake, timeout, handle)
+    }
+
     /// Create a new TCP stream connected to the specified address.
     ///
     /// This is the same as `connect`, but uses the default reactor instead of
@@ -279,7 +476,7 @@ impl TcpStream {
 
     fn new(connected_stream: mio::net::TcpStream, handle: &Handle)
            -> TcpStreamNewState {
-        match PollEvented2::new_with_handle(connected_stream, handle.new_tokio_handle()) {
+        match PollEvented2::new_with_handle(connected_stream, handle) {
             Ok(io) => TcpStreamNewState::Waiting(TcpStream { io: io }),
             Err(e) => TcpStreamNewState::Error(e),
         }
@@ -300,7 +497,21 @@ impl TcpStream {
                        -> io::Result<TcpStream> {
         let inner = try!(mio::net::TcpStream::from_stream(stream));
         Ok(TcpStream {
-            io: try!(PollEvented2::new_with_handle(inner, handle.new_tokio_handle())),
+            io: try!(PollEvented2::new_with_handle(inner, handle)),
+        })
+    }
+
+    /// Create a new `TcpStream` from a `net::TcpStream`, without an explicit
+    /// `&Handle`.
+    ///
+    /// This is the same as `from_stream`, except that the stream isn't
+    /// registered with a reactor right away. Instead it's registered with the
+    /// default reactor the first time it's polled from within a task, so this
+    /// can be called from code which has no access to a `Handle`.
+    pub fn from_std(stream: net::TcpStream) -> io::Result<TcpStream> {
+        let inner = try!(mio::net::TcpStream::from_stream(stream));
+        Ok(TcpStream {
+            io: PollEvented2::new(inner),
         })
     }
 
@@ -333,6 +544,80 @@ impl TcpStream {
         Box::new(state)
     }
 
+    /// Create a new TCP stream connected to `addr` from the local address
+    /// `source`, which doesn't have to be local to the system.
+    ///
+    /// This sets `IP_TRANSPARENT` (or `IPV6_TRANSPARENT`) on the socket before
+    /// binding it to `source`, allowing a transparent proxy to connect to a
+    /// server on behalf of a client while using the client's address.
+    ///
+    /// Setting the option requires the `CAP_NET_ADMIN` capability. This
+    /// function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn connect_transparent(source: &SocketAddr,
+                               addr: &SocketAddr,
+                               handle: &Handle) -> TcpStreamNew {
+        use std::os::unix::prelude::*;
+        use net2::TcpBuilder;
+
+        let stream = (|| {
+            let builder = match *source {
+                SocketAddr::V4(..) => try!(TcpBuilder::new_v4()),
+                SocketAddr::V6(..) => try!(TcpBuilder::new_v6()),
+            };
+            try!(sockopt::set_transparent(builder.as_raw_fd(), source));
+            try!(builder.bind(source));
+            let stream = try!(builder.to_tcp_stream());
+            mio::net::TcpStream::connect_stream(stream, addr)
+        })();
+        let inner = match stream {
+            Ok(tcp) => TcpStream::new(tcp, handle),
+            Err(e) => TcpStreamNewState::Error(e),
+        };
+        TcpStreamNew { inner: inner }
+    }
+
+    /// Create a new TCP stream connected to the IPv6 address `addr`, with its
+    /// packets tagged with a flow label and traffic class from the start.
+    ///
+    /// The flow label is leased for the destination before connecting, as
+    /// Linux won't send labels that haven't been, and may be shared with
+    /// other sockets of the same user. Only the lower 20 bits of `flow_label`
+    /// and the lower 8 bits of `traffic_class` can be set. The traffic class
+    /// can also be changed later with `set_traffic_class`.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn connect_with_flow(addr: &SocketAddr,
+                             flow_label: u32,
+                             traffic_class: u32,
+                             handle: &Handle) -> TcpStreamNew {
+        use std::os::unix::prelude::*;
+        use net2::TcpBuilder;
+
+        let stream = (|| {
+            let addr = match *addr {
+                SocketAddr::V6(ref addr) => addr,
+                SocketAddr::V4(..) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                              "flow labels are only sent over IPv6"))
+                }
+            };
+            let builder = try!(TcpBuilder::new_v6());
+            let fd = builder.as_raw_fd();
+            try!(sockopt::set_traffic_class(fd, traffic_class));
+            try!(sockopt::set_flow_label(fd, addr.ip(), flow_label));
+            let stream = try!(builder.to_tcp_stream());
+            let addr = sockopt::with_flow_label(addr, flow_label);
+            mio::net::TcpStream::connect_stream(stream, &SocketAddr::V6(addr))
+        })();
+        let inner = match stream {
+            Ok(tcp) => TcpStream::new(tcp, handle),
+            Err(e) => TcpStreamNewState::Error(e),
+        };
+        TcpStreamNew { inner: inner }
+    }
+
     /// Test whether this socket is ready to be read or not.
     ///
     /// If the socket is *not* readable then the current task is scheduled to
@@ -379,6 +664,37 @@ impl TcpStream {
         self.io.get_ref().peer_addr()
     }
 
+    /// Returns the address the peer originally connected to, before the
+    /// connection was intercepted.
+    ///
+    /// For connections redirected with NAT, such as the `REDIRECT` iptables
+    /// target, this queries `SO_ORIGINAL_DST`. Connections which have no NAT
+    /// entry, which includes those intercepted with `TPROXY` through a
+    /// listener created with `TcpListener::bind_transparent`, already have
+    /// the original destination as their local address, which is returned
+    /// instead.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn original_destination(&self) -> io::Result<SocketAddr> {
+        use std::os::unix::prelude::*;
+        use libc;
+
+        let local = try!(self.local_addr());
+        let (level, name) = match local {
+            SocketAddr::V4(..) => (libc::SOL_IP, libc::SO_ORIGINAL_DST),
+            SocketAddr::V6(..) => (libc::SOL_IPV6, libc::IP6T_SO_ORIGINAL_DST),
+        };
+        let fd = self.io.get_ref().as_raw_fd();
+        match sockopt::get::<libc::sockaddr_storage>(fd, level, name) {
+            Ok(addr) => sockopt::to_socket_addr(&addr),
+            // No conntrack entry, or conntrack isn't loaded at all
+            Err(ref e) if e.raw_os_error() == Some(libc::ENOENT) ||
+                          e.raw_os_error() == Some(libc::ENOPROTOOPT) => Ok(local),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Receives data on the socket from the remote address to which it is
     /// connected, without removing that data from the queue. On success,
     /// returns the number of bytes peeked.
@@ -397,6 +713,12 @@ impl TcpStream {
 
     }
 
+    /// Peeks at the socket itself, regardless of the cached readiness and of
+    /// the event loop's I/O budget, and without registering the current task.
+    pub(crate) fn peek_socket(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.get_ref().peek(buf)
+    }
+
     /// Shuts down the read, write, or both halves of this connection.
     ///
     /// This function will cause all pending and future I/O on the specified
@@ -525,6 +847,32 @@ impl TcpStream {
         self.io.get_ref().only_v6()
     }
 
+    /// Sets the value of the `IPV6_TCLASS` option on this socket.
+    ///
+    /// This value sets the traffic class field of every packet sent from this
+    /// socket, which must be an IPv6 one.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_traffic_class(&self, tclass: u32) -> io::Result<()> {
+        use std::os::unix::prelude::*;
+
+        sockopt::set_traffic_class(self.io.get_ref().as_raw_fd(), tclass)
+    }
+
+    /// Gets the value of the `IPV6_TCLASS` option for this socket.
+    ///
+    /// For more information about this option, see
+    /// [`set_traffic_class`][link].
+    ///
+    /// [link]: #method.set_traffic_class
+    #[cfg(target_os = "linux")]
+    pub fn traffic_class(&self) -> io::Result<u32> {
+        use std::os::unix::prelude::*;
+
+        sockopt::traffic_class(self.io.get_ref().as_raw_fd())
+    }
+
     /// Sets the linger duration of this socket by setting the SO_LINGER option
     pub fn set_linger(&self, dur: Option<Duration>) -> io::Result<()> {
         self.io.get_ref().set_linger(dur)
@@ -796,17 +1144,57 @@ mod sys {
     use std::os::unix::prelude::*;
     use super::{TcpStream, TcpListener};
 
+    use mio;
+
+    use reactor::PollEvented2;
+
     impl AsRawFd for TcpStream {
         fn as_raw_fd(&self) -> RawFd {
             self.io.get_ref().as_raw_fd()
         }
     }
 
+    impl IntoRawFd for TcpStream {
+        fn into_raw_fd(self) -> RawFd {
+            self.io.into_inner_lossy().into_raw_fd()
+        }
+    }
+
+    impl FromRawFd for TcpStream {
+        /// Creates a new `TcpStream` from a raw file descriptor.
+        ///
+        /// The descriptor must refer to a socket which is already in
+        /// nonblocking mode. It's registered with the default reactor the
+        /// first time it's polled from within a task, just like with
+        /// `from_std`.
+        unsafe fn from_raw_fd(fd: RawFd) -> TcpStream {
+            TcpStream { io: PollEvented2::new(mio::net::TcpStream::from_raw_fd(fd)) }
+        }
+    }
+
     impl AsRawFd for TcpListener {
         fn as_raw_fd(&self) -> RawFd {
             self.io.get_ref().as_raw_fd()
         }
     }
+
+    impl IntoRawFd for TcpListener {
+        fn into_raw_fd(self) -> RawFd {
+            self.io.into_inner_lossy().into_raw_fd()
+        }
+    }
+
+    impl FromRawFd for TcpListener {
+        /// Creates a new `TcpListener` from a raw file descriptor.
+        ///
+        /// The descriptor must refer to a socket which is already in
+        /// nonblocking mode. It's registered with the default reactor the
+        /// first time it's polled from within a task, just like with
+        /// `from_std`.
+        unsafe fn from_raw_fd(fd: RawFd) -> TcpListener {
+            TcpListener { io: PollEvented2::new(mio::net::TcpListener::from_raw_fd(fd)) }
+        }
+    }
 }
 
 #[cfg(windows)]
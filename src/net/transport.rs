@@ -0,0 +1,174 @@
+use std::fmt;
+use std::io;
+use std::net::{Shutdown, SocketAddr};
+
+use futures::{Async, Poll, Stream};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use net::{TcpListener, TcpStream};
+#[cfg(target_os = "linux")]
+use net::{SctpListener, SctpStream};
+
+/// A connected stream socket of any transport, such as a `TcpStream`.
+///
+/// Together with `Listener` this allows servers to be written once, generic
+/// over the transport they accept connections on. The stream can also be
+/// boxed up as a `Box<NetworkStream<Addr = SocketAddr>>` to mix streams of
+/// different transports which share the same kind of address.
+pub trait NetworkStream: AsyncRead + AsyncWrite {
+    /// The address identifying either end of the stream.
+    type Addr: Clone + fmt::Debug;
+
+    /// Returns the local address of this stream.
+    fn local_addr(&self) -> io::Result<Self::Addr>;
+
+    /// Returns the address of the peer of this stream.
+    fn peer_addr(&self) -> io::Result<Self::Addr>;
+
+    /// Shuts down the read, write, or both halves of this stream.
+    fn shutdown(&self, how: Shutdown) -> io::Result<()>;
+
+    /// Tests whether this stream is ready to be read from, scheduling the
+    /// current task to be notified once it is if not.
+    fn poll_read(&self) -> Async<()>;
+
+    /// Tests whether this stream is ready to be written to, scheduling the
+    /// current task to be notified once it is if not.
+    fn poll_write(&self) -> Async<()>;
+}
+
+/// A socket listening for incoming connections of any transport, such as a
+/// `TcpListener`.
+pub trait Listener {
+    /// The type of the accepted streams.
+    type Stream: NetworkStream;
+
+    /// Returns the local address this listener is bound to.
+    fn local_addr(&self) -> io::Result<<Self::Stream as NetworkStream>::Addr>;
+
+    /// Attempts to accept a connection, returning it along with the address
+    /// of its peer.
+    ///
+    /// If no connection is pending a "would block" error is returned, and the
+    /// current task is scheduled to be notified once one is.
+    fn accept(&mut self)
+              -> io::Result<(Self::Stream, <Self::Stream as NetworkStream>::Addr)>;
+
+    /// Consumes this listener, returning a stream of the connections it
+    /// accepts.
+    fn incoming(self) -> ListenerIncoming<Self>
+        where Self: Sized,
+    {
+        ListenerIncoming { listener: self }
+    }
+}
+
+/// Stream returned by `Listener::incoming`, yielding each accepted connection
+/// along with the address of its peer.
+#[must_use = "streams do nothing unless polled"]
+pub struct ListenerIncoming<L> {
+    listener: L,
+}
+
+impl<L> ListenerIncoming<L> {
+    /// Returns a reference to the listener.
+    pub fn get_ref(&self) -> &L {
+        &self.listener
+    }
+
+    /// Consumes this stream, returning the listener.
+    pub fn into_inner(self) -> L {
+        self.listener
+    }
+}
+
+impl<L: Listener> Stream for ListenerIncoming<L> {
+    type Item = (L::Stream, <L::Stream as NetworkStream>::Addr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+        Ok(Async::Ready(Some(try_nb!(self.listener.accept()))))
+    }
+}
+
+impl<L: fmt::Debug> fmt::Debug for ListenerIncoming<L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ListenerIncoming")
+         .field("listener", &self.listener)
+         .finish()
+    }
+}
+
+impl NetworkStream for TcpStream {
+    type Addr = SocketAddr;
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        TcpStream::local_addr(self)
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        TcpStream::peer_addr(self)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        TcpStream::shutdown(self, how)
+    }
+
+    fn poll_read(&self) -> Async<()> {
+        TcpStream::poll_read(self)
+    }
+
+    fn poll_write(&self) -> Async<()> {
+        TcpStream::poll_write(self)
+    }
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        TcpListener::local_addr(self)
+    }
+
+    fn accept(&mut self) -> io::Result<(TcpStream, SocketAddr)> {
+        TcpListener::accept(self)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl NetworkStream for SctpStream {
+    type Addr = SocketAddr;
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        SctpStream::local_addr(self)
+    }
+
+    fn peer_addr(&self) -> io::Result<SocketAddr> {
+        SctpStream::peer_addr(self)
+    }
+
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        SctpStream::shutdown(self, how)
+    }
+
+    fn poll_read(&self) -> Async<()> {
+        SctpStream::poll_read(self)
+    }
+
+    fn poll_write(&self) -> Async<()> {
+        SctpStream::poll_write(self)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Listener for SctpListener {
+    type Stream = SctpStream;
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        SctpListener::local_addr(self)
+    }
+
+    fn accept(&mut self) -> io::Result<(SctpStream, SocketAddr)> {
+        SctpListener::accept(self)
+    }
+}
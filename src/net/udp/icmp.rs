@@ -0,0 +1,88 @@
+use std::io;
+use std::mem;
+use std::net::{IpAddr, SocketAddr};
+
+use libc;
+
+use net::sockopt;
+
+const ICMP_TIME_EXCEEDED: u8 = 11;
+const ICMPV6_TIME_EXCEEDED: u8 = 3;
+
+/// An ICMP error received in response to a datagram sent from a
+/// `UdpSocket`, as returned by `UdpSocket::recv_icmp_error`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IcmpError {
+    errno: i32,
+    v6: bool,
+    icmp_type: u8,
+    icmp_code: u8,
+    offender: Option<IpAddr>,
+    destination: Option<SocketAddr>,
+}
+
+impl IcmpError {
+    /// Returns the error the kernel translated the ICMP message into, such as
+    /// "host unreachable".
+    pub fn error(&self) -> io::Error {
+        io::Error::from_raw_os_error(self.errno)
+    }
+
+    /// Returns the ICMP (or ICMPv6) type of the message.
+    pub fn icmp_type(&self) -> u8 {
+        self.icmp_type
+    }
+
+    /// Returns the ICMP (or ICMPv6) code of the message.
+    pub fn icmp_code(&self) -> u8 {
+        self.icmp_code
+    }
+
+    /// Returns whether this error was caused by the datagram's TTL or hop
+    /// limit running out on the way to its destination.
+    pub fn is_time_exceeded(&self) -> bool {
+        let ty = if self.v6 { ICMPV6_TIME_EXCEEDED } else { ICMP_TIME_EXCEEDED };
+        self.icmp_type == ty
+    }
+
+    /// Returns the address of the host which sent the ICMP message, which
+    /// for a "time exceeded" error is the router where the datagram expired.
+    pub fn offender(&self) -> Option<IpAddr> {
+        self.offender
+    }
+
+    /// Returns the address the failed datagram was sent to.
+    pub fn destination(&self) -> Option<SocketAddr> {
+        self.destination
+    }
+}
+
+/// Parses an `IP_RECVERR` or `IPV6_RECVERR` control message.
+pub fn parse(level: libc::c_int,
+             kind: libc::c_int,
+             data: &[u8],
+             destination: Option<SocketAddr>) -> Option<IcmpError> {
+    let is_err = (level == libc::SOL_IP && kind == libc::IP_RECVERR) ||
+                 (level == libc::SOL_IPV6 && kind == libc::IPV6_RECVERR);
+    let header_len = mem::size_of::<libc::sock_extended_err>();
+    if !is_err || data.len() < header_len {
+        return None
+    }
+    let ee = unsafe {
+        ::std::ptr::read_unaligned(data.as_ptr() as *const libc::sock_extended_err)
+    };
+    let v6 = match ee.ee_origin {
+        libc::SO_EE_ORIGIN_ICMP => false,
+        libc::SO_EE_ORIGIN_ICMP6 => true,
+        _ => return None,
+    };
+    Some(IcmpError {
+        errno: ee.ee_errno as i32,
+        v6: v6,
+        icmp_type: ee.ee_type,
+        icmp_code: ee.ee_code,
+        // The offender's address follows the error.
+        offender: sockopt::control_socket_addr(&data[header_len..]).ok().map(|a| a.ip()),
+        destination: destination,
+    })
+}
@@ -1,10 +1,19 @@
 use std::io;
 use std::net::{self, SocketAddr, Ipv4Addr, Ipv6Addr};
 use std::fmt;
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
 
+#[cfg(unix)]
+use bytes::Buf;
+use bytes::BufMut;
 use futures::{Async, Future, Poll};
+#[cfg(unix)]
+use iovec::IoVec;
 use mio;
 
+#[cfg(unix)]
+use net::sockopt;
 use reactor::{Handle, PollEvented2};
 
 /// An I/O object representing a UDP socket.
@@ -12,8 +21,20 @@ pub struct UdpSocket {
     io: PollEvented2<mio::net::UdpSocket>,
 }
 
+/// `SO_TXTIME`, and the matching `SCM_TXTIME` control message, which aren't
+/// in `libc` yet. This is the value on all architectures we build for.
+#[cfg(target_os = "linux")]
+const SO_TXTIME: i32 = 61;
+
+#[cfg(target_os = "linux")]
+const UDP_SEGMENT: i32 = 103;
+
 mod frame;
 pub use self::frame::{UdpFramed, UdpCodec};
+#[cfg(target_os = "linux")]
+mod icmp;
+#[cfg(target_os = "linux")]
+pub use self::icmp::IcmpError;
 
 impl UdpSocket {
     /// Create a new UDP socket bound to the specified address.
@@ -26,7 +47,7 @@ impl UdpSocket {
     }
 
     fn new(socket: mio::net::UdpSocket, handle: &Handle) -> io::Result<UdpSocket> {
-        let io = try!(PollEvented2::new_with_handle(socket, handle.new_tokio_handle()));
+        let io = try!(PollEvented2::new_with_handle(socket, handle));
         Ok(UdpSocket { io: io })
     }
 
@@ -45,6 +66,18 @@ impl UdpSocket {
         UdpSocket::new(udp, handle)
     }
 
+    /// Creates a new `UdpSocket` from the previously bound socket provided,
+    /// without an explicit `&Handle`.
+    ///
+    /// This is the same as `from_socket`, except that the socket isn't
+    /// registered with a reactor right away. Instead it's registered with the
+    /// default reactor the first time it's polled from within a task, so this
+    /// can be called from code which has no access to a `Handle`.
+    pub fn from_std(socket: net::UdpSocket) -> io::Result<UdpSocket> {
+        let udp = try!(mio::net::UdpSocket::from_socket(socket));
+        Ok(UdpSocket { io: PollEvented2::new(udp) })
+    }
+
     /// Provides a `Stream` and `Sink` interface for reading and writing to this
     /// `UdpSocket` object, using the provided `UdpCodec` to read and write the
     /// raw data.
@@ -207,6 +240,54 @@ impl UdpSocket {
         }
     }
 
+    /// Receives a datagram into the remaining capacity of `buf` like
+    /// `recv_from`, advancing it by the size of the datagram.
+    ///
+    /// Unlike with `recv_from` the memory the datagram is received into
+    /// doesn't need to be initialized first, so a large `BytesMut` can be
+    /// reserved and received into without zeroing it. A datagram which doesn't
+    /// fit is truncated.
+    pub fn recv_buf_from<B: BufMut>(&self, buf: &mut B) -> io::Result<(usize, SocketAddr)> {
+        if let Async::NotReady = self.io.poll_read_ready(mio::Ready::readable())? {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        // The kernel only ever writes to the buffer.
+        let r = unsafe { self.io.get_ref().recv_from(buf.bytes_mut()) };
+        match r {
+            Ok((n, addr)) => {
+                unsafe { buf.advance_mut(n) };
+                Ok((n, addr))
+            }
+            Err(e) => {
+                if e.kind() == io::ErrorKind::WouldBlock {
+                    self.io.clear_read_ready(mio::Ready::readable())?;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Receives a datagram from the address previously connected to into the
+    /// remaining capacity of `buf`, like `recv_buf_from` does.
+    pub fn recv_buf<B: BufMut>(&self, buf: &mut B) -> io::Result<usize> {
+        if let Async::NotReady = self.io.poll_read_ready(mio::Ready::readable())? {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        let r = unsafe { self.io.get_ref().recv(buf.bytes_mut()) };
+        match r {
+            Ok(n) => {
+                unsafe { buf.advance_mut(n) };
+                Ok(n)
+            }
+            Err(e) => {
+                if e.kind() == io::ErrorKind::WouldBlock {
+                    self.io.clear_read_ready(mio::Ready::readable())?;
+                }
+                Err(e)
+            }
+        }
+    }
+
     /// Creates a future that receive a datagram to be written to the buffer
     /// provided.
     ///
@@ -387,6 +468,498 @@ impl UdpSocket {
     pub fn only_v6(&self) -> io::Result<bool> {
         self.io.get_ref().only_v6()
     }
+
+    /// Sets the value of the `IPV6_TCLASS` option on this socket.
+    ///
+    /// This value sets the traffic class field of every datagram sent from
+    /// this socket, which must be an IPv6 one.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_traffic_class(&self, tclass: u32) -> io::Result<()> {
+        use std::os::unix::prelude::*;
+
+        sockopt::set_traffic_class(self.as_raw_fd(), tclass)
+    }
+
+    /// Gets the value of the `IPV6_TCLASS` option for this socket.
+    ///
+    /// For more information about this option, see
+    /// [`set_traffic_class`][link].
+    ///
+    /// [link]: #method.set_traffic_class
+    #[cfg(target_os = "linux")]
+    pub fn traffic_class(&self) -> io::Result<u32> {
+        use std::os::unix::prelude::*;
+
+        sockopt::traffic_class(self.as_raw_fd())
+    }
+
+    /// Allows datagrams sent from this IPv6 socket to `dst` to carry the flow
+    /// label `label`, which can then be sent with `send_to_with_flow_label`.
+    ///
+    /// This leases the label for the destination, as Linux won't send labels
+    /// that haven't been, and may be shared with other sockets of the same
+    /// user. Only the lower 20 bits of `label` can be set. Several labels can
+    /// be leased by calling this again.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_flow_label(&self, dst: &Ipv6Addr, label: u32) -> io::Result<()> {
+        use std::os::unix::prelude::*;
+
+        sockopt::set_flow_label(self.as_raw_fd(), dst, label)
+    }
+
+    /// Sends `buf` to the IPv6 address `target` like `send_to`, with the flow
+    /// label `label` which must have been leased with `set_flow_label`.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn send_to_with_flow_label(&self, buf: &[u8], target: &SocketAddr, label: u32)
+                                   -> io::Result<usize> {
+        match *target {
+            SocketAddr::V6(ref addr) => {
+                self.send_to(buf, &SocketAddr::V6(sockopt::with_flow_label(addr, label)))
+            }
+            SocketAddr::V4(..) => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                   "flow labels are only sent over IPv6"))
+            }
+        }
+    }
+
+    /// Create a new UDP socket bound to `addr`, which doesn't have to be local
+    /// to the system, for use in a transparent proxy.
+    ///
+    /// This sets `IP_TRANSPARENT` (or `IPV6_TRANSPARENT`) and `SO_REUSEADDR`
+    /// on the socket before it's bound, and enables receiving the original
+    /// destination of datagrams as with `set_recv_original_destination`. A
+    /// socket bound to a wildcard address in this way receives the datagrams
+    /// redirected to it with the `TPROXY` iptables target, and
+    /// `recv_from_original` tells which address each one was sent to.
+    ///
+    /// To reply from the original destination, bind another socket to that
+    /// address with this function and send the reply from it.
+    ///
+    /// Setting the option requires the `CAP_NET_ADMIN` capability. This
+    /// function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn bind_transparent(addr: &SocketAddr, handle: &Handle) -> io::Result<UdpSocket> {
+        use std::os::unix::prelude::*;
+        use net2::UdpBuilder;
+
+        let builder = match *addr {
+            SocketAddr::V4(..) => try!(UdpBuilder::new_v4()),
+            SocketAddr::V6(..) => try!(UdpBuilder::new_v6()),
+        };
+        try!(builder.reuse_address(true));
+        try!(sockopt::set_transparent(builder.as_raw_fd(), addr));
+        let socket = try!(builder.bind(addr));
+        let socket = try!(UdpSocket::from_socket(socket, handle));
+        try!(socket.set_recv_original_destination(true));
+        Ok(socket)
+    }
+
+    /// Create a new UDP socket bound to `addr` on the network interface named
+    /// `interface`, so that it only sends and receives datagrams through that
+    /// interface regardless of the routing table.
+    ///
+    /// The socket is bound to the interface before it's bound to `addr`. This
+    /// uses `SO_BINDTODEVICE` on Linux, which requires the `CAP_NET_RAW`
+    /// capability, and `IP_BOUND_IF` (or `IPV6_BOUND_IF`) on macOS and iOS.
+    /// See also `set_interface`.
+    ///
+    /// This function is only available on Linux, macOS and iOS.
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "ios"))]
+    pub fn bind_interface(addr: &SocketAddr,
+                          interface: &str,
+                          handle: &Handle) -> io::Result<UdpSocket> {
+        use std::os::unix::prelude::*;
+        use net2::UdpBuilder;
+
+        let builder = match *addr {
+            SocketAddr::V4(..) => try!(UdpBuilder::new_v4()),
+            SocketAddr::V6(..) => try!(UdpBuilder::new_v6()),
+        };
+        try!(sockopt::bind_device(builder.as_raw_fd(), addr, Some(interface)));
+        let socket = try!(builder.bind(addr));
+        UdpSocket::from_socket(socket, handle)
+    }
+
+    /// Binds this socket to the network interface named `interface`, or
+    /// removes the binding if `interface` is `None`.
+    ///
+    /// While bound, datagrams are only sent and received through the
+    /// interface, regardless of the routing table. For more information, see
+    /// [`bind_interface`][link].
+    ///
+    /// This function is only available on Linux, macOS and iOS.
+    ///
+    /// [link]: #method.bind_interface
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "ios"))]
+    pub fn set_interface(&self, interface: Option<&str>) -> io::Result<()> {
+        use std::os::unix::prelude::*;
+
+        let addr = try!(self.local_addr());
+        sockopt::bind_device(self.as_raw_fd(), &addr, interface)
+    }
+
+    /// Returns the name of the network interface this socket is bound to, if
+    /// any.
+    ///
+    /// For more information, see [`set_interface`][link].
+    ///
+    /// This function is only available on Linux, macOS and iOS.
+    ///
+    /// [link]: #method.set_interface
+    #[cfg(any(target_os = "linux", target_os = "macos", target_os = "ios"))]
+    pub fn interface(&self) -> io::Result<Option<String>> {
+        use std::os::unix::prelude::*;
+
+        let addr = try!(self.local_addr());
+        sockopt::bound_device(self.as_raw_fd(), &addr)
+    }
+
+    /// Sets the value of the `IP_RECVORIGDSTADDR` option (or
+    /// `IPV6_RECVORIGDSTADDR` for IPv6 sockets) for this socket.
+    ///
+    /// When enabled, `recv_from_original` reports the destination address of
+    /// each datagram as it was sent, which for datagrams intercepted with
+    /// `TPROXY` is the address they were meant for rather than one of this
+    /// socket's own.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_recv_original_destination(&self, on: bool) -> io::Result<()> {
+        use std::os::unix::prelude::*;
+        use libc;
+
+        let (level, name) = match try!(self.local_addr()) {
+            SocketAddr::V4(..) => (libc::SOL_IP, libc::IP_RECVORIGDSTADDR),
+            SocketAddr::V6(..) => (libc::SOL_IPV6, libc::IPV6_RECVORIGDSTADDR),
+        };
+        sockopt::set(self.as_raw_fd(), level, name, on as libc::c_int)
+    }
+
+    /// Receives data from the socket along with the address it came from and
+    /// the address it was originally sent to.
+    ///
+    /// The original destination is only known if
+    /// `set_recv_original_destination` has been enabled, which
+    /// `bind_transparent` does. Otherwise it's the local address of this
+    /// socket.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn recv_from_original(&self, buf: &mut [u8])
+                              -> io::Result<(usize, SocketAddr, SocketAddr)> {
+        use libc;
+
+        let mut control = [0; 64];
+        let msg = try!(self.recv_msg(buf, &mut control, 0, mio::Ready::readable()));
+        let mut original = None;
+        for (level, kind, data) in sockopt::control_messages(&control[..msg.control_len]) {
+            if (level == libc::SOL_IP && kind == libc::IP_ORIGDSTADDR) ||
+               (level == libc::SOL_IPV6 && kind == libc::IPV6_ORIGDSTADDR) {
+                original = Some(try!(sockopt::control_socket_addr(data)));
+            }
+        }
+        let original = match original {
+            Some(addr) => addr,
+            None => try!(self.local_addr()),
+        };
+        let from = try!(msg.addr.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "datagram without a source address")
+        }));
+        Ok((msg.len, from, original))
+    }
+
+    /// Receives data from the socket like `recv_from`, but never truncates a
+    /// datagram which doesn't fit `buf` silently.
+    ///
+    /// On success, returns the number of bytes read, the length the datagram
+    /// originally had, and the address it came from. On Linux the datagram is
+    /// received with `MSG_TRUNC`, so the original length is reported even when
+    /// it's larger than `buf`, in which case only the first `buf.len()` bytes
+    /// were read. Other platforms only report that the datagram was truncated,
+    /// which is then returned as an error of kind `InvalidData`.
+    ///
+    /// This function is only available on Unix.
+    #[cfg(unix)]
+    pub fn recv_from_checked(&self, buf: &mut [u8])
+                             -> io::Result<(usize, usize, SocketAddr)> {
+        use libc;
+
+        #[cfg(target_os = "linux")]
+        let flags = libc::MSG_TRUNC;
+        #[cfg(not(target_os = "linux"))]
+        let flags = 0;
+
+        let msg = try!(self.recv_msg(buf, &mut [], flags, mio::Ready::readable()));
+        let from = try!(msg.addr.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "datagram without a source address")
+        }));
+        if cfg!(target_os = "linux") {
+            return Ok((::std::cmp::min(msg.len, buf.len()), msg.len, from))
+        }
+        if msg.flags & libc::MSG_TRUNC != 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      "datagram was larger than the buffer"))
+        }
+        Ok((msg.len, msg.len, from))
+    }
+
+    /// Sends `buf` to `target` like `send_to`, but with the TTL (or hop limit
+    /// for IPv6) of this one datagram set to `ttl`.
+    ///
+    /// Together with `set_recv_icmp_errors` and `recv_icmp_error` this makes
+    /// it possible to implement traceroute: datagrams sent with increasing
+    /// TTLs expire at the routers along the path, which report back with
+    /// "time exceeded" errors.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn send_to_with_ttl(&self, buf: &[u8], target: &SocketAddr, ttl: u32)
+                            -> io::Result<usize> {
+        use libc;
+
+        let mut control = Vec::new();
+        match *target {
+            SocketAddr::V4(..) => {
+                sockopt::push_control(&mut control, libc::SOL_IP, libc::IP_TTL, ttl as libc::c_int)
+            }
+            SocketAddr::V6(..) => {
+                sockopt::push_control(&mut control,
+                                      libc::SOL_IPV6,
+                                      libc::IPV6_HOPLIMIT,
+                                      ttl as libc::c_int)
+            }
+        }
+        self.send_msg(buf, target, &control)
+    }
+
+    /// Sets the value of the `IP_RECVERR` option (or `IPV6_RECVERR` for IPv6
+    /// sockets) for this socket.
+    ///
+    /// When enabled, ICMP errors received in response to datagrams sent from
+    /// this socket are queued up so they can be read with `recv_icmp_error`.
+    /// Note that the kernel also reports them as errors from the next call to
+    /// `recv_from` or `send_to`.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_recv_icmp_errors(&self, on: bool) -> io::Result<()> {
+        use std::os::unix::prelude::*;
+        use libc;
+
+        let (level, name) = match try!(self.local_addr()) {
+            SocketAddr::V4(..) => (libc::SOL_IP, libc::IP_RECVERR),
+            SocketAddr::V6(..) => (libc::SOL_IPV6, libc::IPV6_RECVERR),
+        };
+        sockopt::set(self.as_raw_fd(), level, name, on as libc::c_int)
+    }
+
+    /// Receives an ICMP error queued up for this socket, after enabling
+    /// `set_recv_icmp_errors`.
+    ///
+    /// As much of the failed datagram as the ICMP message carried is written
+    /// to `buf`, and its length is returned along with the error. If no error
+    /// is queued up a "would block" error is returned and the current task is
+    /// notified once one arrives, just like with `recv_from`.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn recv_icmp_error(&self, buf: &mut [u8]) -> io::Result<(usize, IcmpError)> {
+        use libc;
+        use mio::unix::UnixReady;
+
+        let mut control = [0; 128];
+        let ready = UnixReady::error().into();
+        let msg = try!(self.recv_msg(buf, &mut control, libc::MSG_ERRQUEUE, ready));
+        for (level, kind, data) in sockopt::control_messages(&control[..msg.control_len]) {
+            if let Some(err) = icmp::parse(level, kind, data, msg.addr) {
+                return Ok((msg.len, err))
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::Other, "queued error didn't come from ICMP"))
+    }
+
+    /// Enables `SO_TXTIME` on this socket, so datagrams sent with
+    /// `send_to_at` are held back by the kernel until their departure time.
+    ///
+    /// Departure times are only honored by queueing disciplines which support
+    /// them, such as `fq` or `etf`, on the interface the datagrams leave
+    /// through. Other queueing disciplines send the datagrams right away.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn enable_txtime(&self) -> io::Result<()> {
+        use std::os::unix::prelude::*;
+        use libc;
+
+        let txtime = libc::sock_txtime { clockid: libc::CLOCK_MONOTONIC, flags: 0 };
+        sockopt::set(self.as_raw_fd(), libc::SOL_SOCKET, SO_TXTIME, txtime)
+    }
+
+    /// Sends `buf` to `target` like `send_to`, but asks the kernel not to
+    /// transmit the datagram before `at`.
+    ///
+    /// This allows pacing datagrams far more precisely than with timeouts on
+    /// the event loop. It requires `enable_txtime` to have been called on
+    /// this socket, otherwise the kernel fails the send with an error. A
+    /// departure time in the past sends the datagram right away.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn send_to_at(&self, buf: &[u8], target: &SocketAddr, at: Instant)
+                      -> io::Result<usize> {
+        use libc;
+
+        // `Instant` is based on the monotonic clock, but that isn't exposed,
+        // so translate through the current time.
+        let mut now: libc::timespec = unsafe { ::std::mem::zeroed() };
+        if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) } < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        let now_ns = now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64;
+        let now = Instant::now();
+        let delay = if at > now { at - now } else { Duration::from_secs(0) };
+        let txtime = now_ns + delay.as_secs() * 1_000_000_000 + delay.subsec_nanos() as u64;
+
+        let mut control = Vec::new();
+        sockopt::push_control(&mut control, libc::SOL_SOCKET, SO_TXTIME, txtime);
+        self.send_msg(buf, target, &control)
+    }
+
+    /// Sets the segment size used for UDP generic segmentation offload
+    /// (`UDP_SEGMENT`) on this socket, or disables it with 0.
+    ///
+    /// With a segment size set, a buffer passed to `send_to` is split into
+    /// datagrams of `size` bytes each (the last one may be shorter) by the
+    /// kernel or the network card, which is much cheaper than sending them one
+    /// at a time. The whole buffer must not be larger than 64 KiB.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn set_segment_size(&self, size: u16) -> io::Result<()> {
+        use std::os::unix::prelude::*;
+        use libc;
+
+        sockopt::set(self.as_raw_fd(), libc::SOL_UDP, UDP_SEGMENT, size as libc::c_int)
+    }
+
+    /// Sends `buf` to `target` split into datagrams of `segment_size` bytes,
+    /// like `send_to` after `set_segment_size`, but for this call only.
+    ///
+    /// This function is only available on Linux.
+    #[cfg(target_os = "linux")]
+    pub fn send_to_segmented(&self, buf: &[u8], target: &SocketAddr, segment_size: u16)
+                             -> io::Result<usize> {
+        use libc;
+
+        let mut control = Vec::new();
+        sockopt::push_control(&mut control, libc::SOL_UDP, UDP_SEGMENT, segment_size);
+        self.send_msg(buf, target, &control)
+    }
+
+    /// Sends the remaining bytes of `buf` to `target` as one datagram,
+    /// advancing it by the number of bytes sent.
+    ///
+    /// The datagram is sent with `sendmsg` straight from the segments of
+    /// `buf`, so a header and payload can be chained together as in
+    /// `header.chain(payload)` without first copying them into one buffer.
+    /// At most 64 segments are sent, a buffer made up of more fails with an
+    /// error of kind `InvalidInput`.
+    ///
+    /// This function is only available on Unix.
+    #[cfg(unix)]
+    pub fn send_buf_to<B: Buf>(&self, buf: &mut B, target: &SocketAddr) -> io::Result<usize> {
+        self.send_buf_msg(buf, Some(target))
+    }
+
+    /// Sends the remaining bytes of `buf` as one datagram to the address
+    /// previously connected to, like `send_buf_to` does.
+    ///
+    /// This function is only available on Unix.
+    #[cfg(unix)]
+    pub fn send_buf<B: Buf>(&self, buf: &mut B) -> io::Result<usize> {
+        self.send_buf_msg(buf, None)
+    }
+
+    #[cfg(unix)]
+    fn send_buf_msg<B: Buf>(&self, buf: &mut B, target: Option<&SocketAddr>)
+                            -> io::Result<usize> {
+        use std::os::unix::prelude::*;
+
+        // `IoVec` can't be empty, so fill the array with a dummy which is
+        // overwritten by `bytes_vec`, as `TcpStream::write_buf` does.
+        static DUMMY: &[u8] = &[0];
+        let iovec = <&IoVec>::from(DUMMY);
+        let mut bufs = [iovec; 64];
+        let n = buf.bytes_vec(&mut bufs);
+        let len = bufs[..n].iter().map(|b| b.len()).sum::<usize>();
+        if len < buf.remaining() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "buffer has too many segments to send as a datagram"))
+        }
+
+        if let Async::NotReady = self.io.poll_write_ready()? {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        match sockopt::send_msg_vec(self.as_raw_fd(), &bufs[..n], target, &[]) {
+            Ok(n) => {
+                buf.advance(n);
+                Ok(n)
+            }
+            Err(e) => {
+                if e.kind() == io::ErrorKind::WouldBlock {
+                    self.io.clear_write_ready()?;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Receives a datagram with `recvmsg`, handling readiness like
+    /// `recv_from` does for the readiness in `ready`.
+    #[cfg(unix)]
+    fn recv_msg(&self,
+                buf: &mut [u8],
+                control: &mut [u8],
+                flags: i32,
+                ready: mio::Ready) -> io::Result<sockopt::Msg> {
+        use std::os::unix::prelude::*;
+
+        if let Async::NotReady = self.io.poll_read_ready(ready)? {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        let r = sockopt::recv_msg(self.as_raw_fd(), buf, control, flags);
+        if let Err(ref e) = r {
+            if e.kind() == io::ErrorKind::WouldBlock {
+                self.io.clear_read_ready(ready)?;
+            }
+        }
+        r
+    }
+
+    /// Sends a datagram with `sendmsg`, handling readiness like `send_to`
+    /// does.
+    #[cfg(unix)]
+    fn send_msg(&self, buf: &[u8], target: &SocketAddr, control: &[u8]) -> io::Result<usize> {
+        use std::os::unix::prelude::*;
+
+        if let Async::NotReady = self.io.poll_write_ready()? {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        let r = sockopt::send_msg(self.as_raw_fd(), buf, Some(target), control);
+        if let Err(ref e) = r {
+            if e.kind() == io::ErrorKind::WouldBlock {
+                self.io.clear_write_ready()?;
+            }
+        }
+        r
+    }
 }
 
 impl fmt::Debug for UdpSocket {
@@ -457,11 +1030,33 @@ mod sys {
     use std::os::unix::prelude::*;
     use super::UdpSocket;
 
+    use mio;
+
+    use reactor::PollEvented2;
+
     impl AsRawFd for UdpSocket {
         fn as_raw_fd(&self) -> RawFd {
             self.io.get_ref().as_raw_fd()
         }
     }
+
+    impl IntoRawFd for UdpSocket {
+        fn into_raw_fd(self) -> RawFd {
+            self.io.into_inner_lossy().into_raw_fd()
+        }
+    }
+
+    impl FromRawFd for UdpSocket {
+        /// Creates a new `UdpSocket` from a raw file descriptor.
+        ///
+        /// The descriptor must refer to a socket which is already in
+        /// nonblocking mode. It's registered with the default reactor the
+        /// first time it's polled from within a task, just like with
+        /// `from_std`.
+        unsafe fn from_raw_fd(fd: RawFd) -> UdpSocket {
+            UdpSocket { io: PollEvented2::new(mio::net::UdpSocket::from_raw_fd(fd)) }
+        }
+    }
 }
 
 #[cfg(windows)]
@@ -0,0 +1,98 @@
+use std::fmt;
+use std::io;
+use std::panic;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+use futures::sync::oneshot;
+
+use reactor::{Core, Error, Remote};
+
+/// A guard for an event loop running on a background thread, as returned by
+/// `Core::background`.
+///
+/// Dropping the guard, or calling `shutdown`, stops the event loop and waits
+/// for its thread to exit. Futures still running on the event loop at that
+/// point are dropped.
+#[must_use = "the background event loop is shut down when the guard is dropped"]
+pub struct Background {
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+pub fn spawn() -> io::Result<(Remote, Background)> {
+    let (tx, rx) = mpsc::channel();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let thread = try!(thread::Builder::new().name("tokio-core".to_string()).spawn(move || {
+        let mut core = match Core::new() {
+            Ok(core) => core,
+            Err(e) => {
+                let _ = tx.send(Err(e));
+                return
+            }
+        };
+        let _ = tx.send(Ok(core.remote()));
+        let _ = core.run(shutdown_rx);
+    }));
+
+    match rx.recv() {
+        Ok(Ok(remote)) => {
+            Ok((remote, Background {
+                shutdown: Some(shutdown_tx),
+                thread: Some(thread),
+            }))
+        }
+        Ok(Err(e)) => {
+            let _ = thread.join();
+            Err(e)
+        }
+        Err(_) => {
+            match thread.join() {
+                Err(payload) => panic::resume_unwind(payload),
+                Ok(()) => Err(Error::ReactorGone.into()),
+            }
+        }
+    }
+}
+
+impl Background {
+    /// Stops the event loop and waits for its thread to exit.
+    ///
+    /// If the event loop panicked, the panic is returned as an error rather
+    /// than being propagated as it is when the guard is dropped.
+    pub fn shutdown(mut self) -> thread::Result<()> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> thread::Result<()> {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+        match self.thread.take() {
+            // Waiting for the loop to exit from the loop itself would never
+            // finish, so in that case it's just told to stop.
+            Some(ref thread) if thread.thread().id() == thread::current().id() => Ok(()),
+            Some(thread) => thread.join(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        if let Err(payload) = self.stop() {
+            // Don't panic while already panicking, which would abort.
+            if !thread::panicking() {
+                panic::resume_unwind(payload);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Background {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Background")
+         .field("thread", &self.thread.as_ref().map(|t| t.thread().id()))
+         .finish()
+    }
+}
@@ -0,0 +1,115 @@
+use std::error;
+use std::fmt;
+use std::io;
+
+use tokio_timer;
+
+use reactor::RegistrationLimitExceeded;
+
+/// Error returned by operations on an event loop, such as spawning futures
+/// onto it or registering I/O objects with it.
+///
+/// Most of the crate reports errors as `io::Error`, into which this converts.
+/// Converting such an `io::Error` back with `Error::from` recovers which of
+/// these errors it was, so that callers don't have to match on the message.
+#[derive(Debug)]
+pub enum Error {
+    /// A handle to one event loop was used where it had to be the event loop
+    /// currently running, either because another one is running or because
+    /// none is.
+    WrongReactor,
+
+    /// The event loop has been dropped.
+    ReactorGone,
+
+    /// The handle was created with `Handle::from_tokio`, so there's no event
+    /// loop to run a closure or future on.
+    NoEventLoop,
+
+    /// Registering another I/O object would exceed the limit set with
+    /// `Core::set_registration_limit`.
+    RegistrationFull(RegistrationLimitExceeded),
+
+    /// Any other I/O error.
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::WrongReactor => f.write_str("handle belongs to a different event loop"),
+            Error::ReactorGone => f.write_str("event loop is gone"),
+            Error::NoEventLoop => f.write_str("handle has no event loop"),
+            Error::RegistrationFull(ref e) => e.fmt(f),
+            Error::Io(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::WrongReactor => "handle belongs to a different event loop",
+            Error::ReactorGone => "event loop is gone",
+            Error::NoEventLoop => "handle has no event loop",
+            Error::RegistrationFull(ref e) => e.description(),
+            Error::Io(ref e) => e.description(),
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::RegistrationFull(ref e) => Some(e),
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        let structured = match e.get_ref() {
+            Some(inner) => inner.is::<Error>() || inner.is::<RegistrationLimitExceeded>(),
+            None => false,
+        };
+        if !structured {
+            return Error::Io(e)
+        }
+        let inner = e.into_inner().unwrap();
+        let inner = match inner.downcast::<Error>() {
+            Ok(e) => return *e,
+            Err(inner) => inner,
+        };
+        match inner.downcast::<RegistrationLimitExceeded>() {
+            Ok(e) => Error::RegistrationFull(*e),
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+impl From<RegistrationLimitExceeded> for Error {
+    fn from(e: RegistrationLimitExceeded) -> Error {
+        Error::RegistrationFull(e)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        match e {
+            // Wrapped on its own, as it was before there was this type.
+            Error::RegistrationFull(e) => io::Error::new(io::ErrorKind::Other, e),
+            Error::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::Other, e),
+        }
+    }
+}
+
+/// Converts an error of the event loop's timer, which has shut down if the
+/// event loop is gone.
+pub fn timer_error(err: tokio_timer::Error) -> io::Error {
+    if err.is_shutdown() {
+        Error::ReactorGone.into()
+    } else {
+        io::Error::new(io::ErrorKind::Other, err)
+    }
+}
@@ -0,0 +1,127 @@
+//! Support for sending keepalives on otherwise idle sinks.
+//!
+//! This module contains the `Heartbeat` type, a sink which sends a heartbeat
+//! item of its own whenever nothing else has been sent for some time.
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+
+use futures::{AsyncSink, Future, Poll, Sink, StartSend, Stream};
+
+use reactor::{Handle, Timeout};
+
+/// A sink which sends a heartbeat whenever no other item has been sent for a
+/// period.
+///
+/// Items sent through a `Heartbeat` are passed on to the wrapped sink as is.
+/// Additionally a clone of the heartbeat item is sent once `period` has
+/// elapsed since the last item, so heartbeats are only sent on an otherwise
+/// idle sink.
+///
+/// Heartbeats are sent from `poll_complete`, which has to keep being called
+/// for them to go out, as it is when the sink is driven with
+/// `Stream::forward`. The task calling `poll_complete` is notified when the
+/// next heartbeat is due.
+///
+/// If the wrapped sink is also a stream, so is the `Heartbeat`, yielding the
+/// items of the wrapped stream.
+#[must_use = "sinks do nothing unless polled"]
+pub struct Heartbeat<S: Sink> {
+    sink: S,
+    item: S::SinkItem,
+    period: Duration,
+    handle: Handle,
+    timer: Timeout,
+}
+
+impl<S: Sink> Heartbeat<S>
+    where S::SinkItem: Clone,
+          S::SinkError: From<io::Error>,
+{
+    /// Wraps `sink`, sending `item` to it whenever nothing else has been sent
+    /// for `period`.
+    pub fn new(sink: S, item: S::SinkItem, period: Duration, handle: &Handle)
+               -> io::Result<Heartbeat<S>> {
+        Ok(Heartbeat {
+            sink: sink,
+            item: item,
+            period: period,
+            handle: handle.clone(),
+            timer: try!(Timeout::new(period, handle)),
+        })
+    }
+
+    /// Returns a reference to the wrapped sink.
+    pub fn get_ref(&self) -> &S {
+        &self.sink
+    }
+
+    /// Returns a mutable reference to the wrapped sink.
+    ///
+    /// Items sent directly to the wrapped sink don't postpone the next
+    /// heartbeat.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.sink
+    }
+
+    /// Consumes this `Heartbeat`, returning the wrapped sink.
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+
+    fn sent(&mut self) {
+        let at = self.handle.now() + self.period;
+        self.timer.reset(at);
+    }
+}
+
+impl<S: Sink> Sink for Heartbeat<S>
+    where S::SinkItem: Clone,
+          S::SinkError: From<io::Error>,
+{
+    type SinkItem = S::SinkItem;
+    type SinkError = S::SinkError;
+
+    fn start_send(&mut self, item: S::SinkItem) -> StartSend<S::SinkItem, S::SinkError> {
+        let res = try!(self.sink.start_send(item));
+        if res.is_ready() {
+            self.sent();
+        }
+        Ok(res)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), S::SinkError> {
+        // A heartbeat which doesn't fit into the sink right now stays due, so
+        // it's retried on the next call.
+        while try!(self.timer.poll()).is_ready() {
+            match try!(self.sink.start_send(self.item.clone())) {
+                AsyncSink::Ready => self.sent(),
+                AsyncSink::NotReady(_) => break,
+            }
+        }
+        self.sink.poll_complete()
+    }
+
+    fn close(&mut self) -> Poll<(), S::SinkError> {
+        self.sink.close()
+    }
+}
+
+impl<S: Sink + Stream> Stream for Heartbeat<S> {
+    type Item = S::Item;
+    type Error = <S as Stream>::Error;
+
+    fn poll(&mut self) -> Poll<Option<S::Item>, <S as Stream>::Error> {
+        self.sink.poll()
+    }
+}
+
+impl<S: Sink + fmt::Debug> fmt::Debug for Heartbeat<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Heartbeat")
+         .field("sink", &self.sink)
+         .field("period", &self.period)
+         .finish()
+    }
+}
@@ -0,0 +1,223 @@
+//! Support for expiring idle connections.
+//!
+//! This module contains the `IdleTracker` type, a stream of the connections
+//! which have seen no activity for some time.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::hash::Hash;
+use std::io;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll, Stream};
+use futures::task::{self, Task};
+
+use reactor::{Handle, Timeout};
+
+/// A stream of keys which have been idle for longer than a timeout.
+///
+/// Keys, typically identifying connections, are registered with `insert` and
+/// then `touch`ed whenever there is activity on them. Once a key hasn't been
+/// touched for the tracker's timeout it is removed from the tracker and
+/// yielded by the stream.
+///
+/// Rather than one `Timeout` per key, all keys share a single timer wheel
+/// with slots as wide as the tracker's resolution, so touching a key only
+/// records the time and tracking many keys puts little load on the event
+/// loop's timer. Keys expire up to one resolution after their timeout has
+/// elapsed.
+///
+/// The stream never ends; it is simply not ready while no keys are tracked.
+#[must_use = "streams do nothing unless polled"]
+pub struct IdleTracker<K> {
+    timeout: Duration,
+    resolution: Duration,
+    handle: Handle,
+    entries: HashMap<K, Entry>,
+    next_generation: u64,
+    wheel: VecDeque<Vec<(K, u64)>>,
+    base: Instant,
+    timer: Timeout,
+    expired: VecDeque<K>,
+    task: Option<Task>,
+}
+
+struct Entry {
+    last_active: Instant,
+    generation: u64,
+}
+
+impl<K: Hash + Eq + Clone> IdleTracker<K> {
+    /// Creates a new tracker expiring keys which have been idle for longer
+    /// than `timeout`, with a resolution of a 32nd of the timeout.
+    pub fn new(timeout: Duration, handle: &Handle) -> io::Result<IdleTracker<K>> {
+        let resolution = ::std::cmp::max(timeout / 32, Duration::from_millis(1));
+        IdleTracker::with_resolution(timeout, resolution, handle)
+    }
+
+    /// Creates a new tracker expiring keys which have been idle for longer
+    /// than `timeout`, checking for idle keys every `resolution`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `resolution` is zero.
+    pub fn with_resolution(timeout: Duration, resolution: Duration, handle: &Handle)
+                           -> io::Result<IdleTracker<K>> {
+        assert!(resolution > Duration::new(0, 0), "resolution must not be zero");
+        let now = handle.now();
+        Ok(IdleTracker {
+            timeout: timeout,
+            resolution: resolution,
+            handle: handle.clone(),
+            entries: HashMap::new(),
+            next_generation: 0,
+            wheel: VecDeque::new(),
+            base: now,
+            timer: try!(Timeout::new_at(now, handle)),
+            expired: VecDeque::new(),
+            task: None,
+        })
+    }
+
+    /// Starts tracking `key`, or records activity on it if it's already
+    /// tracked.
+    pub fn insert(&mut self, key: K) {
+        let now = self.handle.now();
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_active = now;
+            return
+        }
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.entries.insert(key.clone(), Entry {
+            last_active: now,
+            generation: generation,
+        });
+        let deadline = now + self.timeout;
+        self.schedule(key, generation, deadline, now);
+    }
+
+    /// Records activity on `key`, postponing its expiration.
+    ///
+    /// Returns `false` if `key` isn't tracked, which is the case once it has
+    /// been yielded by the stream.
+    pub fn touch(&mut self, key: &K) -> bool {
+        match self.entries.get_mut(key) {
+            Some(entry) => {
+                entry.last_active = self.handle.now();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Stops tracking `key`, returning whether it was tracked.
+    pub fn remove(&mut self, key: &K) -> bool {
+        self.entries.remove(key).is_some()
+    }
+
+    /// Returns whether `key` is tracked.
+    pub fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Returns the number of keys tracked.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no keys are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the time `key` was last active, if it's tracked.
+    pub fn last_active(&self, key: &K) -> Option<Instant> {
+        self.entries.get(key).map(|e| e.last_active)
+    }
+
+    fn schedule(&mut self, key: K, generation: u64, deadline: Instant, now: Instant) {
+        if self.wheel.is_empty() {
+            self.base = now;
+            if let Some(task) = self.task.take() {
+                task.notify();
+            }
+        }
+        // Round up so keys never expire early.
+        let slot = if deadline > self.base {
+            let res = nanos(self.resolution);
+            ((nanos(deadline - self.base) + res - 1) / res) as usize
+        } else {
+            0
+        };
+        while self.wheel.len() <= slot {
+            self.wheel.push_back(Vec::new());
+        }
+        self.wheel[slot].push((key, generation));
+    }
+
+    /// Empties the slots of the wheel which are due, moving the keys which
+    /// have been idle for too long to `expired` and rescheduling the rest.
+    fn turn(&mut self, now: Instant) {
+        while !self.wheel.is_empty() && self.base <= now {
+            let slot = self.wheel.pop_front().unwrap();
+            self.base += self.resolution;
+            for (key, generation) in slot {
+                let deadline = match self.entries.get(&key) {
+                    Some(entry) if entry.generation == generation => {
+                        entry.last_active + self.timeout
+                    }
+                    // Removed, or removed and inserted again in which case
+                    // it's also in a later slot.
+                    _ => continue,
+                };
+                if deadline <= now {
+                    self.entries.remove(&key);
+                    self.expired.push_back(key);
+                } else {
+                    self.schedule(key, generation, deadline, now);
+                }
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> Stream for IdleTracker<K> {
+    type Item = K;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<K>, io::Error> {
+        loop {
+            if let Some(key) = self.expired.pop_front() {
+                return Ok(Async::Ready(Some(key)))
+            }
+            if self.wheel.is_empty() {
+                self.task = Some(task::current());
+                return Ok(Async::NotReady)
+            }
+            let now = self.handle.now();
+            if self.base > now {
+                let base = self.base;
+                self.timer.reset(base);
+                if try!(self.timer.poll()).is_not_ready() {
+                    return Ok(Async::NotReady)
+                }
+            }
+            self.turn(::std::cmp::max(now, self.base));
+        }
+    }
+}
+
+impl<K: Hash + Eq> fmt::Debug for IdleTracker<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IdleTracker")
+         .field("timeout", &self.timeout)
+         .field("resolution", &self.resolution)
+         .field("tracked", &self.entries.len())
+         .finish()
+    }
+}
+
+fn nanos(d: Duration) -> u64 {
+    d.as_secs() * 1_000_000_000 + d.subsec_nanos() as u64
+}
@@ -10,7 +10,7 @@ use futures::Poll;
 use futures::Stream;
 use tokio_timer::Interval as NewInterval;
 
-use reactor::Handle;
+use reactor::{error, Handle};
 
 /// A stream representing notifications at fixed interval
 ///
@@ -34,7 +34,7 @@ impl Interval {
     /// interval object. The interval object itself is then a stream which will
     /// be set to fire at the specified intervals
     pub fn new(dur: Duration, handle: &Handle) -> io::Result<Interval> {
-        Interval::new_at(Instant::now() + dur, dur, handle)
+        Interval::new_at(handle.remote.clock.now() + dur, dur, handle)
     }
 
     /// Creates a new interval which will fire at the time specified by `at`,
@@ -59,6 +59,6 @@ impl Stream for Interval {
     fn poll(&mut self) -> Poll<Option<()>, io::Error> {
         self.new.poll()
             .map(|async| async.map(|option| option.map(|_| ())))
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+            .map_err(error::timer_error)
     }
 }
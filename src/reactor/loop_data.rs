@@ -0,0 +1,176 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use futures::{future, Future, Poll};
+use futures::sync::oneshot;
+
+use reactor::{Error, Handle, Remote};
+
+/// Data which isn't `Send` stored on an event loop, accessible from any
+/// thread.
+///
+/// A `LoopData` is created on the event loop's thread, which then owns the
+/// data. The `LoopData` itself can be sent to and shared with other threads,
+/// which access the data with `with`, running a closure on the event loop's
+/// thread. This makes it possible to use data which must not leave its
+/// thread, such as a context of a C library, from anywhere.
+///
+/// Dropping the `LoopData`, on any thread, drops the data on the event loop's
+/// thread. If the event loop is dropped first then the data is dropped along
+/// with it.
+pub struct LoopData<T: 'static> {
+    id: usize,
+    remote: Remote,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Future returned by `LoopData::with`, which resolves to the value returned
+/// by the closure once it has run on the event loop.
+///
+/// The future fails if the event loop or the data is dropped before the
+/// closure could run.
+#[must_use = "futures do nothing unless polled"]
+pub struct WithData<R> {
+    rx: oneshot::Receiver<R>,
+}
+
+impl<T: 'static> LoopData<T> {
+    /// Stores `data` on the event loop of `handle`.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `handle` was created with `Handle::from_tokio`
+    /// and thus has no event loop to store data on.
+    pub fn new(data: T, handle: &Handle) -> LoopData<T> {
+        let inner = handle.inner.as_ref()
+            .expect("`LoopData::new` requires a handle to a `Core`")
+            .upgrade();
+        let mut id = 0;
+        if let Some(inner) = inner {
+            let mut inner = inner.borrow_mut();
+            id = inner.next_loop_data;
+            inner.next_loop_data += 1;
+            inner.loop_data.insert(id, Box::new(Rc::new(RefCell::new(data))));
+        }
+        LoopData {
+            id: id,
+            remote: handle.remote().clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Runs `f` with the data on the event loop's thread, resolving to what
+    /// it returns.
+    ///
+    /// This can be called from any thread.
+    pub fn with<F, R>(&self, f: F) -> WithData<R>
+        where F: FnOnce(&mut T) -> R + Send + 'static,
+              R: Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        let id = self.id;
+        // Deferred to a future of its own, as on the event loop's thread the
+        // closure passed to `spawn` runs right away and could be nested in
+        // another access to the data.
+        self.remote.spawn(move |handle| {
+            let handle = handle.clone();
+            future::lazy(move || {
+                if let Some(data) = lookup::<T>(&handle, id) {
+                    let _ = tx.send(f(&mut data.borrow_mut()));
+                }
+                Ok(())
+            })
+        });
+        WithData { rx: rx }
+    }
+
+    /// Runs `f` with the data right away, given a handle to the event loop
+    /// the data is stored on.
+    ///
+    /// Returns `None` if `handle` belongs to another event loop.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if called from within another call accessing the
+    /// same data.
+    pub fn with_local<F, R>(&self, handle: &Handle, f: F) -> Option<R>
+        where F: FnOnce(&mut T) -> R,
+    {
+        self.try_with_local(handle, f).ok()
+    }
+
+    /// Like `with_local`, but fails with `Error::WrongReactor` if `handle`
+    /// belongs to another event loop, and with `Error::ReactorGone` if the
+    /// event loop has been dropped.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if called from within another call accessing the
+    /// same data.
+    pub fn try_with_local<F, R>(&self, handle: &Handle, f: F) -> Result<R, Error>
+        where F: FnOnce(&mut T) -> R,
+    {
+        if handle.id() != self.remote.id() {
+            return Err(Error::WrongReactor)
+        }
+        match lookup::<T>(handle, self.id) {
+            Some(data) => Ok(f(&mut data.borrow_mut())),
+            None => Err(Error::ReactorGone),
+        }
+    }
+}
+
+fn lookup<T: 'static>(handle: &Handle, id: usize) -> Option<Rc<RefCell<T>>> {
+    let inner = match handle.inner.as_ref().and_then(|i| i.upgrade()) {
+        Some(inner) => inner,
+        None => return None,
+    };
+    let inner = inner.borrow();
+    inner.loop_data.get(&id)
+        .and_then(|data| data.downcast_ref::<Rc<RefCell<T>>>())
+        .cloned()
+}
+
+impl<T: 'static> Drop for LoopData<T> {
+    fn drop(&mut self) {
+        let id = self.id;
+        // Deferred like `with`, so pending calls to it still see the data.
+        self.remote.spawn(move |handle| {
+            let handle = handle.clone();
+            future::lazy(move || {
+                // Dropped outside of the borrow, in case its destructor uses
+                // the event loop.
+                let data = handle.inner.as_ref()
+                    .and_then(|i| i.upgrade())
+                    .and_then(|inner| inner.borrow_mut().loop_data.remove(&id));
+                drop(data);
+                Ok(())
+            })
+        });
+    }
+}
+
+impl<T: 'static> fmt::Debug for LoopData<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LoopData")
+         .field("core", &self.remote.id())
+         .finish()
+    }
+}
+
+impl<R> Future for WithData<R> {
+    type Item = R;
+    type Error = oneshot::Canceled;
+
+    fn poll(&mut self) -> Poll<R, oneshot::Canceled> {
+        self.rx.poll()
+    }
+}
+
+impl<R> fmt::Debug for WithData<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WithData").finish()
+    }
+}
@@ -4,7 +4,9 @@
 //! happening in `tokio-core`. This reactor (or event loop) is used to run
 //! futures, schedule tasks, issue I/O requests, etc.
 
-use std::cell::RefCell;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::fmt;
 use std::io;
 use std::rc::{Rc, Weak};
@@ -15,10 +17,11 @@ use std::time::{Instant, Duration};
 use tokio;
 use tokio::executor::current_thread::{CurrentThread, TaskExecutor};
 use tokio_executor;
-use tokio_executor::park::{Park, Unpark, ParkThread, UnparkThread};
+use tokio_executor::park::{Park, Unpark, UnparkThread};
+use tokio_timer::clock::{self, Clock};
 use tokio_timer::timer::{self, Timer};
 
-use futures::{Future, IntoFuture, Async};
+use futures::{task, Future, IntoFuture, Async, Poll, Stream};
 use futures::future::{self, Executor, ExecuteError};
 use futures::executor::{self, Spawn, Notify};
 use futures::sync::mpsc;
@@ -26,12 +29,32 @@ use mio;
 
 mod poll_evented;
 mod poll_evented2;
+mod sim;
+use self::sim::{CorePark, SimNow};
 mod timeout;
 mod interval;
+mod idle;
+mod heartbeat;
+mod background;
+mod spawn_stream;
+mod loop_data;
+mod notified;
+mod quota;
+mod error;
 pub use self::poll_evented::PollEvented;
 pub(crate) use self::poll_evented2::PollEvented as PollEvented2;
 pub use self::timeout::Timeout;
 pub use self::interval::Interval;
+pub use self::idle::IdleTracker;
+pub use self::heartbeat::Heartbeat;
+pub use self::background::Background;
+pub use self::loop_data::{LoopData, WithData};
+pub use self::quota::{RegistrationLimitExceeded, RegistrationUsage};
+pub use self::error::Error;
+use self::quota::{RegistrationGuard, Registrations};
+
+/// The default number of I/O readiness events and of messages handled in a turn.
+const DEFAULT_BUDGET: usize = 128;
 
 static NEXT_LOOP_ID: AtomicUsize = ATOMIC_USIZE_INIT;
 scoped_thread_local!(static CURRENT_LOOP: Core);
@@ -51,11 +74,17 @@ pub struct Core {
     rt: tokio::runtime::Runtime,
 
     /// Executes tasks
-    executor: RefCell<CurrentThread<Timer<ParkThread>>>,
+    executor: RefCell<CurrentThread<Timer<CorePark, Clock>>>,
 
     /// Timer handle
     timer_handle: timer::Handle,
 
+    /// Source of time for timers
+    clock: Clock,
+
+    /// Set if time is simulated
+    sim: Option<SimNow>,
+
     /// Wakes up the thread when the `run` future is notified
     notify_future: Arc<MyNotify>,
 
@@ -68,6 +97,17 @@ pub struct Core {
     /// Receive messages
     rx: RefCell<Spawn<mpsc::UnboundedReceiver<Message>>>,
 
+    /// Readiness events I/O objects may consume per turn, and how many of
+    /// them are left in the current turn
+    io_budget: Cell<usize>,
+    io_remaining: Cell<usize>,
+
+    /// Messages from remotes handled per turn
+    message_budget: Cell<usize>,
+
+    /// I/O objects registered with this core
+    registrations: Arc<Registrations>,
+
     // Shared inner state
     inner: Rc<RefCell<Inner>>,
 }
@@ -75,6 +115,10 @@ pub struct Core {
 struct Inner {
     // Tasks that need to be spawned onto the executor.
     pending_spawn: Vec<Box<Future<Item = (), Error = ()>>>,
+
+    // Data stored with `LoopData`, each an `Rc<RefCell<T>>`.
+    loop_data: HashMap<usize, Box<Any>>,
+    next_loop_data: usize,
 }
 
 /// An unique ID for a Core
@@ -97,6 +141,11 @@ pub struct Remote {
     tx: mpsc::UnboundedSender<Message>,
     new_handle: tokio::reactor::Handle,
     timer_handle: timer::Handle,
+    clock: Clock,
+    registrations: Arc<Registrations>,
+    // Set for remotes of handles created with `Handle::from_tokio`, which
+    // have no event loop to send closures to.
+    executor: Option<::tokio::runtime::TaskExecutor>,
 }
 
 /// A non-sendable handle to an event loop, typically passed into functions that
@@ -104,7 +153,9 @@ pub struct Remote {
 #[derive(Clone)]
 pub struct Handle {
     remote: Remote,
-    inner: Weak<RefCell<Inner>>,
+    // `None` for handles created with `Handle::from_tokio`, which aren't
+    // backed by a `Core`.
+    inner: Option<Weak<RefCell<Inner>>>,
     thread_pool: ::tokio::runtime::TaskExecutor,
 }
 
@@ -118,8 +169,34 @@ impl Core {
     /// Creates a new event loop, returning any error that happened during the
     /// creation.
     pub fn new() -> io::Result<Core> {
+        Core::new_with_sim(None)
+    }
+
+    /// Creates a new event loop whose time is simulated, for use in tests.
+    ///
+    /// Time on the returned event loop starts out at the current time but then
+    /// only moves forward when `advance` is called, or when the event loop
+    /// would otherwise block waiting for a timer. In the latter case it skips
+    /// ahead straight to the next timer instead of sleeping, so timeouts and
+    /// intervals created through this core's handles fire immediately once
+    /// nothing else is left to do.
+    ///
+    /// Time also skips ahead while I/O driven by another thread, such as a
+    /// socket, is still outstanding, so this is best suited for testing logic
+    /// which only depends on timers and in-memory I/O objects like
+    /// `testing::MockEvented`.
+    pub fn new_simulated() -> io::Result<Core> {
+        Core::new_with_sim(Some(SimNow::new()))
+    }
+
+    fn new_with_sim(sim: Option<SimNow>) -> io::Result<Core> {
+        let clock = match sim {
+            Some(ref sim) => Clock::new_with_now(sim.clone()),
+            None => Clock::system(),
+        };
+
         // Create a new parker
-        let timer = Timer::new(ParkThread::new());
+        let timer = Timer::new_with_now(CorePark::new(sim.clone()), clock.clone());
 
         // Create notifiers
         let notify_future = Arc::new(MyNotify::new(timer.unpark()));
@@ -150,12 +227,34 @@ impl Core {
             rx,
             executor,
             timer_handle,
+            clock,
+            sim,
+            io_budget: Cell::new(DEFAULT_BUDGET),
+            io_remaining: Cell::new(DEFAULT_BUDGET),
+            message_budget: Cell::new(DEFAULT_BUDGET),
+            registrations: Registrations::new(),
             inner: Rc::new(RefCell::new(Inner {
                 pending_spawn: vec![],
+                loop_data: HashMap::new(),
+                next_loop_data: 0,
             })),
         })
     }
 
+    /// Creates a new event loop running on a dedicated background thread.
+    ///
+    /// Returns a `Remote` to spawn futures onto the event loop, along with a
+    /// guard which shuts the event loop down and joins its thread when
+    /// dropped. If the event loop panics, dropping the guard propagates the
+    /// panic to the dropping thread, while `Background::shutdown` returns it.
+    ///
+    /// Note that the guard must not be dropped by a future running on the
+    /// event loop itself, as the event loop can't wait for its own thread to
+    /// exit. In that case the event loop only stops once the future returns.
+    pub fn background() -> io::Result<(Remote, Background)> {
+        background::spawn()
+    }
+
     /// Returns a handle to this event loop which cannot be sent across threads
     /// but can be used as a proxy to the event loop itself.
     ///
@@ -165,7 +264,7 @@ impl Core {
     pub fn handle(&self) -> Handle {
         Handle {
             remote: self.remote(),
-            inner: Rc::downgrade(&self.inner),
+            inner: Some(Rc::downgrade(&self.inner)),
             thread_pool: self.rt.executor().clone(),
         }
     }
@@ -184,7 +283,10 @@ impl Core {
             id: self.id,
             tx: self.tx.clone(),
             new_handle: self.rt.reactor().clone(),
-            timer_handle: self.timer_handle.clone()
+            timer_handle: self.timer_handle.clone(),
+            clock: self.clock.clone(),
+            registrations: self.registrations.clone(),
+            executor: None,
         }
     }
 
@@ -216,12 +318,14 @@ impl Core {
         let mut executor1 = self.rt.executor().clone();
         let mut executor2 = self.rt.executor().clone();
         let timer_handle = self.timer_handle.clone();
+        let clock = self.clock.clone();
 
         // Make sure the future will run at least once on enter
         self.notify_future.notify(0);
 
         loop {
             if self.notify_future.take() {
+                self.next_turn();
                 let mut enter = tokio_executor::enter()
                     .ok().expect("cannot recursively call into `Core`");
 
@@ -232,10 +336,12 @@ impl Core {
                     ::tokio_reactor::with_default(&handle1, &mut enter, |enter| {
                         tokio_executor::with_default(&mut executor1, enter, |enter| {
                             timer::with_default(&timer_handle, enter, |enter| {
-                                current_thread.enter(enter)
-                                    .block_on(future::lazy(|| {
-                                        Ok::<_, ()>(task.poll_future_notify(notify, 0))
-                                    })).unwrap()
+                                clock::with_default(&clock, enter, |enter| {
+                                    current_thread.enter(enter)
+                                        .block_on(future::lazy(|| {
+                                            Ok::<_, ()>(task.poll_future_notify(notify, 0))
+                                        })).unwrap()
+                                })
                             })
                         })
                     })
@@ -250,6 +356,25 @@ impl Core {
         }
     }
 
+    /// Runs a `std::future::Future` until completion, driving the event loop
+    /// while we're otherwise waiting for the future to complete.
+    ///
+    /// This behaves just like `run`, except that it accepts a future from the
+    /// standard library rather than a futures 0.1 future. Futures spawned on
+    /// this core, of either kind, keep running alongside it.
+    ///
+    /// This method is only available when the `std-future` feature is
+    /// enabled.
+    #[cfg(feature = "std-future")]
+    pub fn block_on<F>(&mut self, f: F) -> F::Output
+        where F: ::std::future::Future,
+    {
+        match self.run(::compat::BlockOn::new(f)) {
+            Ok(output) => output,
+            Err(()) => unreachable!(),
+        }
+    }
+
     /// Performs one iteration of the event loop, blocking on waiting for events
     /// for at most `max_wait` (forever if `None`).
     ///
@@ -264,65 +389,144 @@ impl Core {
         self.poll(max_wait, &handle, &mut executor);
     }
 
+    /// Limits how much work of each kind a turn of the event loop does.
+    ///
+    /// In each turn the event loop handles up to `messages` closures and
+    /// futures sent through a `Remote`, and the sockets of this crate polled
+    /// by tasks on the event loop report readiness up to `io_events` times,
+    /// after which they act as if they weren't ready and have their task
+    /// polled again in the next turn. This way neither a flood of messages nor a task
+    /// working through a busy socket holds up timers and other tasks for
+    /// long, with the ratio between the two limits deciding which side is
+    /// favored under load.
+    ///
+    /// Both limits default to 128.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if either limit is zero.
+    pub fn set_turn_budget(&mut self, io_events: usize, messages: usize) {
+        assert!(io_events > 0 && messages > 0, "turn budgets must not be zero");
+        self.io_budget.set(io_events);
+        self.io_remaining.set(io_events);
+        self.message_budget.set(messages);
+    }
+
+    /// Limits the number of I/O objects which can be registered with this
+    /// event loop at once, or removes the limit if `None`.
+    ///
+    /// Once the limit is reached, creating an I/O object through one of the
+    /// event loop's handles, or polling one which is registered lazily like an
+    /// accepted `TcpStream`, fails with a `RegistrationLimitExceeded` error
+    /// until another I/O object is dropped. This makes it possible to shed
+    /// load at a known point, well before running out of file descriptors.
+    ///
+    /// Lowering the limit below the number of I/O objects already registered
+    /// doesn't affect those. There's no limit by default.
+    pub fn set_registration_limit(&mut self, limit: Option<usize>) {
+        self.registrations.set_limit(limit);
+    }
+
+    /// Returns how many I/O objects are registered with this event loop.
+    pub fn registration_usage(&self) -> RegistrationUsage {
+        self.registrations.usage()
+    }
+
+    /// Returns the current time of this event loop.
+    ///
+    /// This is the system time unless the event loop was created with
+    /// `new_simulated`.
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Moves the simulated time of this event loop forward by `dur`, and then
+    /// performs one iteration of the event loop without blocking, so all
+    /// timeouts and intervals which became due are fired and the tasks waiting
+    /// on them are run.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the event loop wasn't created with
+    /// `new_simulated`.
+    pub fn advance(&mut self, dur: Duration) {
+        self.sim.as_ref()
+            .expect("`Core::advance` requires a core created with `new_simulated`")
+            .advance(dur);
+        self.turn(Some(Duration::from_millis(0)));
+    }
+
     fn poll(&mut self, max_wait: Option<Duration>,
             handle: &tokio::reactor::Handle,
             sender: &mut tokio::runtime::TaskExecutor) {
         let mut enter = tokio_executor::enter()
             .ok().expect("cannot recursively call into `Core`");
         let timer_handle = self.timer_handle.clone();
+        let clock = self.clock.clone();
+        self.next_turn();
 
         ::tokio_reactor::with_default(handle, &mut enter, |enter| {
             tokio_executor::with_default(sender, enter, |enter| {
                 timer::with_default(&timer_handle, enter, |enter| {
-                    let start = Instant::now();
+                    clock::with_default(&clock, enter, |enter| {
+                        let start = Instant::now();
 
-                    // Process all the events that came in, dispatching appropriately
-                    if self.notify_rx.take() {
-                        CURRENT_LOOP.set(self, || self.consume_queue());
-                    }
+                        // Process all the events that came in, dispatching appropriately
+                        if self.notify_rx.take() {
+                            CURRENT_LOOP.set(self, || self.consume_queue());
+                        }
 
-                    // Drain any futures pending spawn
-                    {
-                        let mut e = self.executor.borrow_mut();
-                        let mut i = self.inner.borrow_mut();
-
-                        for f in i.pending_spawn.drain(..) {
-                            // Little hack
-                            e.enter(enter).block_on(future::lazy(|| {
-                                TaskExecutor::current().spawn_local(f).unwrap();
-                                Ok::<_, ()>(())
-                            })).unwrap();
+                        // Drain any futures pending spawn
+                        {
+                            let mut e = self.executor.borrow_mut();
+                            let mut i = self.inner.borrow_mut();
+
+                            for f in i.pending_spawn.drain(..) {
+                                // Little hack
+                                e.enter(enter).block_on(future::lazy(|| {
+                                    TaskExecutor::current().spawn_local(f).unwrap();
+                                    Ok::<_, ()>(())
+                                })).unwrap();
+                            }
                         }
-                    }
 
-                    CURRENT_LOOP.set(self, || {
-                        self.executor.borrow_mut()
-                            .enter(enter)
-                            .turn(max_wait)
-                            .ok().expect("error in `CurrentThread::turn`");
-                    });
+                        CURRENT_LOOP.set(self, || {
+                            self.executor.borrow_mut()
+                                .enter(enter)
+                                .turn(max_wait)
+                                .ok().expect("error in `CurrentThread::turn`");
+                        });
 
-                    let after_poll = Instant::now();
-                    debug!("loop poll - {:?}", after_poll - start);
-                    debug!("loop time - {:?}", after_poll);
+                        let after_poll = Instant::now();
+                        debug!("loop poll - {:?}", after_poll - start);
+                        debug!("loop time - {:?}", after_poll);
 
-                    debug!("loop process, {:?}", after_poll.elapsed());
+                        debug!("loop process, {:?}", after_poll.elapsed());
+                    })
                 })
             });
         });
     }
 
+    fn next_turn(&self) {
+        self.io_remaining.set(self.io_budget.get());
+    }
+
     fn consume_queue(&self) {
         debug!("consuming notification queue");
         // TODO: can we do better than `.unwrap()` here?
-        loop {
+        for _ in 0..self.message_budget.get() {
             let msg = self.rx.borrow_mut().poll_stream_notify(&self.notify_rx, 0).unwrap();
             match msg {
                 Async::Ready(Some(msg)) => self.notify(msg),
                 Async::NotReady |
-                Async::Ready(None) => break,
+                Async::Ready(None) => return,
             }
         }
+
+        // The budget ran out, so leave the rest of the messages to the next
+        // turn, which mustn't block waiting for them.
+        self.notify_rx.notify(0);
     }
 
     fn notify(&self, msg: Message) {
@@ -330,6 +534,16 @@ impl Core {
         r.call_box(self);
     }
 
+    fn take_io_budget(&self) -> bool {
+        match self.io_remaining.get() {
+            0 => false,
+            n => {
+                self.io_remaining.set(n - 1);
+                true
+            }
+        }
+    }
+
     /// Get the ID of this loop
     pub fn id(&self) -> CoreId {
         CoreId(self.id)
@@ -353,7 +567,10 @@ impl fmt::Debug for Core {
 }
 
 impl Remote {
-    fn send(&self, msg: Message) {
+    fn send(&self, msg: Message) -> Result<(), Error> {
+        if self.executor.is_some() {
+            return Err(Error::NoEventLoop)
+        }
         self.with_loop(|lp| {
             match lp {
                 Some(lp) => {
@@ -374,16 +591,11 @@ impl Remote {
                         lp.consume_queue();
                     }
                     lp.notify(msg);
+                    Ok(())
                 }
                 None => {
-                    match self.tx.unbounded_send(msg) {
-                        Ok(()) => {}
-
-                        // TODO: this error should punt upwards and we should
-                        //       notify the caller that the message wasn't
-                        //       received. This is tokio-core#17
-                        Err(e) => drop(e),
-                    }
+                    // The receiving end is dropped along with the `Core`.
+                    self.tx.unbounded_send(msg).map_err(|_| Error::ReactorGone)
                 }
             }
         })
@@ -417,6 +629,9 @@ impl Remote {
     ///
     /// # Panics
     ///
+    /// This method panics if this is the remote of a handle created with
+    /// `Handle::from_tokio`, as there's no event loop to run `f` on.
+    ///
     /// This method will **not** catch panics from polling the future `f`. If
     /// the future panics then it's the responsibility of the caller to catch
     /// that panic and handle it as appropriate.
@@ -424,11 +639,29 @@ impl Remote {
         where F: FnOnce(&Handle) -> R + Send + 'static,
               R: IntoFuture<Item=(), Error=()>,
               R::Future: 'static,
+    {
+        if let Err(Error::NoEventLoop) = self.try_spawn(f) {
+            panic!("`Remote::spawn` on the remote of a handle created with \
+                    `from_tokio`, which has no event loop to run it on");
+        }
+    }
+
+    /// Like `spawn`, but fails with `Error::ReactorGone` if the event loop
+    /// has been dropped, in which case `f` is dropped without being run, and
+    /// with `Error::NoEventLoop` for the remote of a handle created with
+    /// `Handle::from_tokio`.
+    ///
+    /// Succeeding doesn't guarantee that `f` runs, as the event loop may still
+    /// be dropped before it gets to it.
+    pub fn try_spawn<F, R>(&self, f: F) -> Result<(), Error>
+        where F: FnOnce(&Handle) -> R + Send + 'static,
+              R: IntoFuture<Item=(), Error=()>,
+              R::Future: 'static,
     {
         self.send(Message::Run(Box::new(|lp: &Core| {
             let f = f(&lp.handle());
             lp.handle().spawn(f.into_future());
-        })));
+        })))
     }
 
     /// Return the ID of the represented Core
@@ -436,6 +669,19 @@ impl Remote {
         CoreId(self.id)
     }
 
+    /// Returns how many I/O objects are registered with the event loop this
+    /// remote is associated with.
+    ///
+    /// See `Core::set_registration_limit` for limiting the number.
+    pub fn registration_usage(&self) -> RegistrationUsage {
+        self.registrations.usage()
+    }
+
+    /// Registers an I/O object with the event loop, if the limit allows it.
+    fn acquire_registration(&self) -> io::Result<RegistrationGuard> {
+        Registrations::acquire(&self.registrations)
+    }
+
     /// Attempts to "promote" this remote to a handle, if possible.
     ///
     /// This function is intended for structures which typically work through a
@@ -449,18 +695,18 @@ impl Remote {
     /// a `Handle`, then you can call this function and fall back to using
     /// `spawn` above if it returns `None`.
     pub fn handle(&self) -> Option<Handle> {
-        if CURRENT_LOOP.is_set() {
-            CURRENT_LOOP.with(|lp| {
-                let same = lp.id == self.id;
-                if same {
-                    Some(lp.handle())
-                } else {
-                    None
-                }
-            })
-        } else {
-            None
-        }
+        self.try_handle().ok()
+    }
+
+    /// Like `handle`, but fails with `Error::WrongReactor` rather than
+    /// returning `None`.
+    pub fn try_handle(&self) -> Result<Handle, Error> {
+        self.with_loop(|lp| {
+            match lp {
+                Some(lp) => Ok(lp.handle()),
+                None => Err(Error::WrongReactor),
+            }
+        })
     }
 }
 
@@ -468,6 +714,11 @@ impl<F> Executor<F> for Remote
     where F: Future<Item = (), Error = ()> + Send + 'static,
 {
     fn execute(&self, future: F) -> Result<(), ExecuteError<F>> {
+        // Without an event loop the future can still run on the executor the
+        // handle was created with, as it's `Send`.
+        if let Some(ref executor) = self.executor {
+            return executor.execute(future)
+        }
         self.spawn(|_| future);
         Ok(())
     }
@@ -482,11 +733,55 @@ impl fmt::Debug for Remote {
 }
 
 impl Handle {
+    /// Creates a handle which drives I/O objects with an existing Tokio
+    /// reactor rather than a `Core`.
+    ///
+    /// I/O objects such as `TcpStream` created with the returned handle are
+    /// registered with `reactor`, so they make progress whenever that reactor
+    /// is being driven, typically by a Tokio `Runtime` owned by someone else.
+    /// Timeouts and intervals use the timer of the executor they're polled on.
+    /// Futures passed to `spawn_send` are spawned onto `executor`.
+    ///
+    /// There's no event loop behind the handle, so futures passed to `spawn`
+    /// are spawned onto the current-thread executor the caller is running on,
+    /// and `spawn` panics if there's none. Likewise the handle's `Remote` has
+    /// no event loop to run closures on, so `Remote::spawn` panics and
+    /// `Remote::try_spawn` fails with `Error::NoEventLoop`. Futures passed to
+    /// the `Remote` as an `Executor`, which are `Send`, are spawned onto
+    /// `executor`.
+    pub fn from_tokio(reactor: &::tokio::reactor::Handle,
+                      executor: ::tokio::runtime::TaskExecutor) -> Handle {
+        let (tx, _) = mpsc::unbounded();
+        Handle {
+            remote: Remote {
+                id: NEXT_LOOP_ID.fetch_add(1, Ordering::Relaxed),
+                tx: tx,
+                new_handle: reactor.clone(),
+                timer_handle: timer::Handle::default(),
+                clock: Clock::new(),
+                registrations: Registrations::new(),
+                executor: Some(executor.clone()),
+            },
+            inner: None,
+            thread_pool: executor,
+        }
+    }
+
     /// Returns a reference to the new Tokio handle
     pub fn new_tokio_handle(&self) -> &::tokio::reactor::Handle {
         &self.remote.new_handle
     }
 
+    /// Returns the current time of the event loop this handle is associated
+    /// with.
+    ///
+    /// This is the system time unless the event loop was created with
+    /// `Core::new_simulated`, and is the time relative to which `Timeout::new`
+    /// and `Interval::new` compute when to fire.
+    pub fn now(&self) -> Instant {
+        self.remote.clock.now()
+    }
+
     /// Returns a reference to the underlying remote handle to the event loop.
     pub fn remote(&self) -> &Remote {
         &self.remote
@@ -502,22 +797,46 @@ impl Handle {
     pub fn spawn<F>(&self, f: F)
         where F: Future<Item=(), Error=()> + 'static,
     {
-        let inner = match self.inner.upgrade() {
-            Some(inner) => inner,
+        let res = self.try_spawn(f);
+        if self.inner.is_none() {
+            res.expect("`Handle::spawn` on a handle created with `from_tokio` \
+                        must be called from within a current-thread executor");
+        }
+    }
+
+    /// Like `spawn`, but fails with `Error::ReactorGone` if the event loop
+    /// has been dropped, in which case `f` is dropped without being run.
+    ///
+    /// For a handle created with `from_tokio` this fails with
+    /// `Error::NoEventLoop` if there's no current-thread executor to spawn `f`
+    /// onto, rather than panicking.
+    pub fn try_spawn<F>(&self, f: F) -> Result<(), Error>
+        where F: Future<Item=(), Error=()> + 'static,
+    {
+        let inner = match self.inner {
+            Some(ref inner) => inner.upgrade(),
             None => {
-                return;
+                // Not backed by a `Core`, so the future can only run on the
+                // current thread's executor, if there is one.
+                return TaskExecutor::current().spawn_local(Box::new(f))
+                    .map_err(|_| Error::NoEventLoop)
             }
         };
+        let inner = match inner {
+            Some(inner) => inner,
+            None => return Err(Error::ReactorGone),
+        };
 
         // Try accessing the executor directly
         if let Ok(mut inner) = inner.try_borrow_mut() {
             inner.pending_spawn.push(Box::new(f));
-            return;
+            return Ok(());
         }
 
         // If that doesn't work, the executor is probably active, so spawn using
         // the global fn.
         let _ = TaskExecutor::current().spawn_local(Box::new(f));
+        Ok(())
     }
 
     /// Spawns a new future onto the threadpool
@@ -551,6 +870,57 @@ impl Handle {
         self.spawn(future::lazy(f))
     }
 
+    /// Spawns a task on this event loop which drives `stream` to completion,
+    /// calling `f` on each item and running the futures it returns.
+    ///
+    /// The futures returned by `f` run concurrently, without a limit on how
+    /// many do at once. Once the stream produces an error it's logged and the
+    /// stream is dropped, though futures already returned by `f` keep running.
+    ///
+    /// This is a shorthand for `spawn_stream_with` with an unbounded number
+    /// of futures in flight and an error handler which only logs.
+    pub fn spawn_stream<S, F, R>(&self, stream: S, f: F)
+        where S: Stream + 'static,
+              S::Error: fmt::Debug,
+              F: FnMut(S::Item) -> R + 'static,
+              R: IntoFuture<Item=(), Error=()> + 'static,
+    {
+        self.spawn_stream_with(stream, usize::max_value(), f, |e| {
+            error!("spawned stream failed: {:?}", e);
+            false
+        })
+    }
+
+    /// Spawns a task on this event loop which drives `stream` to completion,
+    /// calling `f` on each item and running the futures it returns.
+    ///
+    /// At most `max_in_flight` futures returned by `f` run concurrently; the
+    /// stream isn't polled for more items until one of them completes.
+    /// Errors produced by the stream are passed to `on_error`, which returns
+    /// whether to keep polling the stream. Futures already returned by `f`
+    /// keep running after the stream has been given up on.
+    ///
+    /// After an error the stream is kept for, the task yields to the event
+    /// loop before polling the stream again. A stream which keeps failing,
+    /// such as a listener which has run out of file descriptors, therefore
+    /// fails at most once per turn rather than blocking the event loop.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if `max_in_flight` is zero.
+    pub fn spawn_stream_with<S, F, R, E>(&self,
+                                         stream: S,
+                                         max_in_flight: usize,
+                                         f: F,
+                                         on_error: E)
+        where S: Stream + 'static,
+              F: FnMut(S::Item) -> R + 'static,
+              R: IntoFuture<Item=(), Error=()> + 'static,
+              E: FnMut(S::Error) -> bool + 'static,
+    {
+        self.spawn(spawn_stream::new(stream, max_in_flight, f, on_error))
+    }
+
     /// Return the ID of the represented Core
     pub fn id(&self) -> CoreId {
         self.remote.id()
@@ -599,6 +969,33 @@ impl Notify for MyNotify {
     }
 }
 
+/// Charges a successful readiness check of an I/O object to the budget of the
+/// current turn of the event loop.
+///
+/// Once the budget is used up `NotReady` is returned instead, and the current
+/// task is notified so it's polled again. This always notifies, as the task
+/// may be polled again before the turn ends, say by a `FuturesUnordered`
+/// which it's part of, and would never be woken again if it wasn't notified
+/// each time. Outside of a `Core` readiness is passed through as is.
+pub(crate) fn charge_io_budget<T>(ready: Poll<T, io::Error>) -> Poll<T, io::Error> {
+    if let Ok(Async::Ready(_)) = ready {
+        if CURRENT_LOOP.is_set() && !CURRENT_LOOP.with(|lp| lp.take_io_budget()) {
+            task::current().notify();
+            return Ok(Async::NotReady)
+        }
+    }
+    ready
+}
+
+/// Takes up a registration of the event loop running the current task, if
+/// any, for an I/O object registered lazily.
+pub(crate) fn acquire_current_registration() -> io::Result<Option<RegistrationGuard>> {
+    if !CURRENT_LOOP.is_set() {
+        return Ok(None)
+    }
+    CURRENT_LOOP.with(|lp| Registrations::acquire(&lp.registrations)).map(Some)
+}
+
 trait FnBox: Send + 'static {
     fn call_box(self: Box<Self>, lp: &Core);
 }
@@ -0,0 +1,51 @@
+use std::sync::Mutex;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
+
+use futures::task::{self, Task};
+
+/// Remembers the task an I/O object notified because readiness arrived while
+/// the task was finding it not ready, for one direction of the object.
+///
+/// The task then isn't notified again until it polls the object for
+/// readiness, as it's already scheduled to be. Clearing on the task's own
+/// poll rather than once per turn of the event loop keeps this correct for
+/// tasks which are polled several times in a turn, such as the futures of a
+/// `FuturesUnordered`.
+pub struct Notified {
+    /// Set from notifying `task` until the object is next polled
+    pending: AtomicBool,
+    task: Mutex<Option<Task>>,
+}
+
+impl Notified {
+    pub fn new() -> Notified {
+        Notified {
+            pending: AtomicBool::new(false),
+            task: Mutex::new(None),
+        }
+    }
+
+    /// Forgets the notification, called whenever the object is polled for
+    /// readiness.
+    pub fn clear(&self) {
+        if self.pending.load(Relaxed) {
+            self.pending.store(false, Relaxed);
+        }
+    }
+
+    /// Notifies the current task, unless it was notified already and hasn't
+    /// polled the object since.
+    pub fn notify_current(&self) {
+        let mut task = self.task.lock().unwrap();
+        if self.pending.load(Relaxed) {
+            if task.as_ref().map_or(false, |task| task.will_notify_current()) {
+                return
+            }
+        }
+        let current = task::current();
+        current.notify();
+        *task = Some(current);
+        self.pending.store(true, Relaxed);
+    }
+}
@@ -11,13 +11,16 @@ use std::io::{self, Read, Write};
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering::Relaxed;
 
-use futures::{task, Async, Poll};
+use bytes::BufMut;
+use futures::{Async, Poll};
 use mio::event::Evented;
 use mio::Ready;
 use tokio_io::{AsyncRead, AsyncWrite};
 use tokio::reactor::{Registration};
 
 use reactor::{Handle, Remote};
+use reactor::notified::Notified;
+use reactor::quota::RegistrationGuard;
 
 /// A concrete implementation of a stream of readiness notifications for I/O
 /// objects that originates from an event loop.
@@ -64,10 +67,30 @@ use reactor::{Handle, Remote};
 /// Essentially a good rule of thumb is that if you're using the `poll_ready`
 /// method you want to also use `need_read` to signal blocking and you should
 /// otherwise probably avoid using two tasks on the same `PollEvented`.
+///
+/// ## Wakeups
+///
+/// If readiness arrived in the meantime, `need_read` and `need_write` notify
+/// the current task right away so it tries its operation again. Such
+/// notifications are coalesced: once a task has been notified this way, it
+/// isn't notified again for the same direction until it next polls this
+/// `PollEvented` for readiness, as it's going to be polled again anyway. A
+/// task calling `need_read` several times before that, for example from
+/// several layers of wrappers, is notified only once instead of being
+/// scheduled over and over.
+///
+/// This doesn't change when the task runs again. A task that keeps finding
+/// its I/O object ready is still polled again as soon as the executor gets
+/// to it, which on a `Core` is in the next turn, after the other tasks which
+/// became ready in the meantime. The notification is only skipped for the
+/// very task that was notified, so a task polled within another one, such as
+/// the futures of a `FuturesUnordered`, still gets a notification each time
+/// it's polled and finds the object ready.
 pub struct PollEvented<E> {
     io: E,
     inner: Inner,
     remote: Remote,
+    _registration: RegistrationGuard,
 }
 
 struct Inner {
@@ -78,6 +101,10 @@ struct Inner {
 
     /// Currently visible write readiness
     write_readiness: AtomicUsize,
+
+    /// Tasks notified by `need_read` and `need_write`
+    read_notified: Notified,
+    write_notified: Notified,
 }
 
 impl<E: Evented> PollEvented<E> {
@@ -87,6 +114,7 @@ impl<E: Evented> PollEvented<E> {
     /// This method returns a future which will resolve to the readiness stream
     /// when it's ready.
     pub fn new(io: E, handle: &Handle) -> io::Result<PollEvented<E>> {
+        let guard = handle.remote().acquire_registration()?;
         let registration = Registration::new();
         registration.register_with(&io, handle.new_tokio_handle())?;
 
@@ -96,8 +124,11 @@ impl<E: Evented> PollEvented<E> {
                 registration,
                 read_readiness: AtomicUsize::new(0),
                 write_readiness: AtomicUsize::new(0),
+                read_notified: Notified::new(),
+                write_notified: Notified::new(),
             },
             remote: handle.remote().clone(),
+            _registration: guard,
         })
     }
 
@@ -136,6 +167,7 @@ impl<E> PollEvented<E> {
     /// This function will panic if called outside the context of a future's
     /// task.
     pub fn poll_read(&self) -> Async<()> {
+        self.inner.read_notified.clear();
         if self.poll_read2().is_ready() {
             return ().into();
         }
@@ -184,6 +216,11 @@ impl<E> PollEvented<E> {
     /// This function will panic if called outside the context of a future's
     /// task.
     pub fn poll_write(&self) -> Async<()> {
+        self.inner.write_notified.clear();
+        self.poll_write2()
+    }
+
+    fn poll_write2(&self) -> Async<()> {
         match self.inner.write_readiness.load(Relaxed) {
             0 => {}
             mut n => {
@@ -247,6 +284,7 @@ impl<E> PollEvented<E> {
         let mask = mask - Ready::writable();
 
         if !mask.is_empty() {
+            self.inner.read_notified.clear();
             if let Async::Ready(v) = self.poll_read2() {
                 ret |= v & mask;
             }
@@ -293,9 +331,9 @@ impl<E> PollEvented<E> {
     pub fn need_read(&self) {
         self.inner.read_readiness.store(0, Relaxed);
 
-        if self.poll_read().is_ready() {
-            // Notify the current task
-            task::current().notify();
+        if self.poll_read2().is_ready() {
+            // Notify the current task, unless it already was
+            self.inner.read_notified.notify_current();
         }
     }
 
@@ -322,9 +360,9 @@ impl<E> PollEvented<E> {
     pub fn need_write(&self) {
         self.inner.write_readiness.store(0, Relaxed);
 
-        if self.poll_write().is_ready() {
-            // Notify the current task
-            task::current().notify();
+        if self.poll_write2().is_ready() {
+            // Notify the current task, unless it already was
+            self.inner.write_notified.notify_current();
         }
     }
 
@@ -347,6 +385,31 @@ impl<E> PollEvented<E> {
     }
 }
 
+impl<E: Read> PollEvented<E> {
+    /// Reads into the remaining capacity of `buf`, advancing it by the number
+    /// of bytes read.
+    ///
+    /// This is `AsyncRead::read_buf` without zeroing the memory read into
+    /// first, which the `AsyncRead` implementation can't skip as it doesn't
+    /// know how `E` reads. Large buffers can then be reserved and read into
+    /// without initializing them. If the buffer is full no read is attempted
+    /// and zero is returned.
+    ///
+    /// # Safety
+    ///
+    /// The `Read` implementation of `E` must only ever write to the buffer it
+    /// is given, never read from it, as it holds uninitialized memory. This
+    /// is the case for sockets and pipes, which read through a system call.
+    pub unsafe fn read_buf_uninit<B: BufMut>(&mut self, buf: &mut B) -> Poll<usize, io::Error> {
+        if !buf.has_remaining_mut() {
+            return Ok(Async::Ready(0))
+        }
+        let n = try_nb!(self.read(buf.bytes_mut()));
+        buf.advance_mut(n);
+        Ok(Async::Ready(n))
+    }
+}
+
 impl<E: Read> Read for PollEvented<E> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if let Async::NotReady = PollEvented::poll_read(self) {
@@ -503,3 +566,27 @@ impl<E: Evented + fmt::Debug> fmt::Debug for PollEvented<E> {
          .finish()
     }
 }
+
+#[cfg(all(unix, not(target_os = "fuchsia")))]
+mod sys {
+    use std::os::unix::prelude::*;
+
+    use mio::event::Evented;
+
+    use super::PollEvented;
+
+    impl<E: AsRawFd> AsRawFd for PollEvented<E> {
+        fn as_raw_fd(&self) -> RawFd {
+            self.io.as_raw_fd()
+        }
+    }
+
+    impl<E: Evented + IntoRawFd> IntoRawFd for PollEvented<E> {
+        fn into_raw_fd(mut self) -> RawFd {
+            // Ignore errors, the descriptor is still handed back and the
+            // registration goes away once it's closed.
+            let _ = self.inner.registration.deregister(&self.io);
+            self.io.into_raw_fd()
+        }
+    }
+}
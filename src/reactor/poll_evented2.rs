@@ -1,15 +1,21 @@
-use tokio::reactor::{Handle, Registration};
+use tokio::reactor::Registration;
 
-use futures::{task, Async, Poll};
+use bytes::BufMut;
+use futures::{Async, Poll};
 use mio;
 use mio::event::Evented;
 use tokio_io::{AsyncRead, AsyncWrite};
 
 use std::fmt;
 use std::io::{self, Read, Write};
-use std::sync::atomic::AtomicUsize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::sync::atomic::Ordering::Relaxed;
 
+use reactor::Handle;
+use reactor::notified::Notified;
+use reactor::quota::RegistrationGuard;
+
 /// Associates an I/O resource that implements the [`std::Read`] and / or
 /// [`std::Write`] traits with the reactor that drives it.
 ///
@@ -94,6 +100,15 @@ struct Inner {
 
     /// Currently visible write readiness
     write_readiness: AtomicUsize,
+
+    /// Tasks notified by `clear_read_ready` and `clear_write_ready`
+    read_notified: Notified,
+    write_notified: Notified,
+
+    /// Set once registered, along with the registration counted against the
+    /// event loop's limit
+    registered: AtomicBool,
+    quota: Mutex<Option<RegistrationGuard>>,
 }
 
 // ===== impl PollEvented =====
@@ -150,14 +165,23 @@ where E: Evented
                 registration: Registration::new(),
                 read_readiness: AtomicUsize::new(0),
                 write_readiness: AtomicUsize::new(0),
+                read_notified: Notified::new(),
+                write_notified: Notified::new(),
+                registered: AtomicBool::new(false),
+                quota: Mutex::new(None),
             }
         }
     }
 
-    /// Creates a new `PollEvented` associated with the specified reactor.
+    /// Creates a new `PollEvented` registered with the event loop of `handle`,
+    /// counting against its registration limit.
     pub fn new_with_handle(io: E, handle: &Handle) -> io::Result<Self> {
+        let guard = handle.remote().acquire_registration()?;
         let ret = PollEvented::new(io);
-        ret.inner.registration.register_with(ret.io.as_ref().unwrap(), handle)?;
+        ret.inner.registration.register_with(ret.io.as_ref().unwrap(),
+                                             handle.new_tokio_handle())?;
+        *ret.inner.quota.lock().unwrap() = Some(guard);
+        ret.inner.registered.store(true, Relaxed);
         Ok(ret)
     }
 
@@ -187,6 +211,17 @@ where E: Evented
         Ok(io)
     }
 
+    /// Consumes self, returning the inner I/O object even if deregistering it
+    /// from the reactor fails.
+    ///
+    /// A failed deregistration leaves the I/O resource registered until it's
+    /// closed, but unlike `into_inner` the resource isn't lost.
+    pub(crate) fn into_inner_lossy(mut self) -> E {
+        let io = self.io.take().unwrap();
+        let _ = self.inner.registration.deregister(&io);
+        io
+    }
+
     /// Check the I/O resource's read readiness state.
     ///
     /// The mask argument allows specifying what readiness to notify on. This
@@ -210,6 +245,12 @@ where E: Evented
     /// * called from outside of a task context.
     pub fn poll_read_ready(&self, mask: mio::Ready) -> Poll<mio::Ready, io::Error> {
         assert!(!mask.is_writable(), "cannot poll for write readiness");
+        self.inner.read_notified.clear();
+        let ready = self.poll_read_ready_unbudgeted(mask);
+        ::reactor::charge_io_budget(ready)
+    }
+
+    fn poll_read_ready_unbudgeted(&self, mask: mio::Ready) -> Poll<mio::Ready, io::Error> {
         poll_ready!(self, mask, read_readiness, poll_read_ready, take_read_ready)
     }
 
@@ -235,9 +276,9 @@ where E: Evented
 
         self.inner.read_readiness.fetch_and(!ready.as_usize(), Relaxed);
 
-        if self.poll_read_ready(ready)?.is_ready() {
-            // Notify the current task
-            task::current().notify();
+        if self.poll_read_ready_unbudgeted(ready)?.is_ready() {
+            // Notify the current task, unless it already was
+            self.inner.read_notified.notify_current();
         }
 
         Ok(())
@@ -263,6 +304,12 @@ where E: Evented
     /// * `ready` contains bits besides `writable` and `hup`.
     /// * called from outside of a task context.
     pub fn poll_write_ready(&self) -> Poll<mio::Ready, io::Error> {
+        self.inner.write_notified.clear();
+        let ready = self.poll_write_ready_unbudgeted();
+        ::reactor::charge_io_budget(ready)
+    }
+
+    fn poll_write_ready_unbudgeted(&self) -> Poll<mio::Ready, io::Error> {
         poll_ready!(self,
                     mio::Ready::writable(),
                     write_readiness,
@@ -287,9 +334,9 @@ where E: Evented
 
         self.inner.write_readiness.fetch_and(!ready.as_usize(), Relaxed);
 
-        if self.poll_write_ready()?.is_ready() {
-            // Notify the current task
-            task::current().notify();
+        if self.poll_write_ready_unbudgeted()?.is_ready() {
+            // Notify the current task, unless it already was
+            self.inner.write_notified.notify_current();
         }
 
         Ok(())
@@ -297,11 +344,46 @@ where E: Evented
 
     /// Ensure that the I/O resource is registered with the reactor.
     fn register(&self) -> io::Result<()> {
+        if self.inner.registered.load(Relaxed) {
+            return Ok(())
+        }
+        // Registered with the default reactor, which is that of the event loop
+        // running the current task if there is one.
+        let guard = ::reactor::acquire_current_registration()?;
         self.inner.registration.register(self.io.as_ref().unwrap())?;
+        *self.inner.quota.lock().unwrap() = guard;
+        self.inner.registered.store(true, Relaxed);
         Ok(())
     }
 }
 
+impl<E> PollEvented<E>
+where E: Evented + Read,
+{
+    /// Reads into the remaining capacity of `buf`, advancing it by the number
+    /// of bytes read.
+    ///
+    /// This is `AsyncRead::read_buf` without zeroing the memory read into
+    /// first, which the `AsyncRead` implementation can't skip as it doesn't
+    /// know how `E` reads. Large buffers can then be reserved and read into
+    /// without initializing them. If the buffer is full no read is attempted
+    /// and zero is returned.
+    ///
+    /// # Safety
+    ///
+    /// The `Read` implementation of `E` must only ever write to the buffer it
+    /// is given, never read from it, as it holds uninitialized memory. This
+    /// is the case for sockets and pipes, which read through a system call.
+    pub unsafe fn read_buf_uninit<B: BufMut>(&mut self, buf: &mut B) -> Poll<usize, io::Error> {
+        if !buf.has_remaining_mut() {
+            return Ok(Async::Ready(0))
+        }
+        let n = try_nb!(self.read(buf.bytes_mut()));
+        buf.advance_mut(n);
+        Ok(Async::Ready(n))
+    }
+}
+
 // ===== Read / Write impls =====
 
 impl<E> Read for PollEvented<E>
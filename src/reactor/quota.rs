@@ -0,0 +1,118 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use reactor;
+
+/// Error returned when registering an I/O object with an event loop would
+/// exceed the limit set with `Core::set_registration_limit`.
+///
+/// It's returned wrapped in an `io::Error` of kind `Other`, from which it can
+/// be recovered with `get_ref` and `downcast_ref`, or by converting the error
+/// into a `reactor::Error`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegistrationLimitExceeded {
+    limit: usize,
+}
+
+impl RegistrationLimitExceeded {
+    /// Returns the limit which would have been exceeded.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl fmt::Display for RegistrationLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "event loop already has {} registered I/O objects", self.limit)
+    }
+}
+
+impl Error for RegistrationLimitExceeded {
+    fn description(&self) -> &str {
+        "registration limit of event loop exceeded"
+    }
+}
+
+/// A snapshot of how many I/O objects are registered with an event loop, as
+/// returned by `Core::registration_usage`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegistrationUsage {
+    registered: usize,
+    limit: Option<usize>,
+    rejected: usize,
+}
+
+impl RegistrationUsage {
+    /// Returns the number of I/O objects currently registered.
+    pub fn registered(&self) -> usize {
+        self.registered
+    }
+
+    /// Returns the limit on the number of registered I/O objects, if any.
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Returns the number of registrations which have been refused because
+    /// of the limit.
+    pub fn rejected(&self) -> usize {
+        self.rejected
+    }
+}
+
+/// Counts the I/O objects registered with an event loop.
+pub struct Registrations {
+    registered: AtomicUsize,
+    // `usize::max_value()` if there's no limit
+    limit: AtomicUsize,
+    rejected: AtomicUsize,
+}
+
+/// Held by each registered I/O object, releasing its registration on drop.
+pub struct RegistrationGuard {
+    registrations: Arc<Registrations>,
+}
+
+impl Registrations {
+    pub fn new() -> Arc<Registrations> {
+        Arc::new(Registrations {
+            registered: AtomicUsize::new(0),
+            limit: AtomicUsize::new(usize::max_value()),
+            rejected: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn set_limit(&self, limit: Option<usize>) {
+        self.limit.store(limit.unwrap_or(usize::max_value()), Ordering::SeqCst);
+    }
+
+    pub fn usage(&self) -> RegistrationUsage {
+        let limit = self.limit.load(Ordering::SeqCst);
+        RegistrationUsage {
+            registered: self.registered.load(Ordering::SeqCst),
+            limit: if limit == usize::max_value() { None } else { Some(limit) },
+            rejected: self.rejected.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Takes up one registration, failing if that's over the limit.
+    pub fn acquire(this: &Arc<Registrations>) -> io::Result<RegistrationGuard> {
+        let limit = this.limit.load(Ordering::SeqCst);
+        if this.registered.fetch_add(1, Ordering::SeqCst) >= limit {
+            this.registered.fetch_sub(1, Ordering::SeqCst);
+            this.rejected.fetch_add(1, Ordering::SeqCst);
+            let e = RegistrationLimitExceeded { limit: limit };
+            return Err(reactor::Error::RegistrationFull(e).into())
+        }
+        Ok(RegistrationGuard { registrations: this.clone() })
+    }
+}
+
+impl Drop for RegistrationGuard {
+    fn drop(&mut self) {
+        self.registrations.registered.fetch_sub(1, Ordering::SeqCst);
+    }
+}
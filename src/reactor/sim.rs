@@ -0,0 +1,73 @@
+//! Support for cores whose time is simulated rather than taken from the
+//! system clock.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio_executor::park::{Park, ParkError, ParkThread, UnparkThread};
+use tokio_timer::clock::Now;
+
+/// Source of time for a simulated core, which only moves forward when told
+/// to.
+#[derive(Clone, Debug)]
+pub struct SimNow {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl SimNow {
+    pub fn new() -> SimNow {
+        SimNow { now: Arc::new(Mutex::new(Instant::now())) }
+    }
+
+    pub fn advance(&self, dur: Duration) {
+        *self.now.lock().unwrap() += dur;
+    }
+}
+
+impl Now for SimNow {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// Parks the thread running a core.
+///
+/// With simulated time, waiting for a timeout skips ahead in time instead of
+/// sleeping, so the event loop only ever blocks if there are no timers at
+/// all.
+pub struct CorePark {
+    park: ParkThread,
+    sim: Option<SimNow>,
+}
+
+impl CorePark {
+    pub fn new(sim: Option<SimNow>) -> CorePark {
+        CorePark {
+            park: ParkThread::new(),
+            sim: sim,
+        }
+    }
+}
+
+impl Park for CorePark {
+    type Unpark = UnparkThread;
+    type Error = ParkError;
+
+    fn unpark(&self) -> UnparkThread {
+        self.park.unpark()
+    }
+
+    fn park(&mut self) -> Result<(), ParkError> {
+        self.park.park()
+    }
+
+    fn park_timeout(&mut self, dur: Duration) -> Result<(), ParkError> {
+        match self.sim {
+            Some(ref sim) => {
+                sim.advance(dur);
+                self.park.park_timeout(Duration::from_millis(0))
+            }
+            None => self.park.park_timeout(dur),
+        }
+    }
+}
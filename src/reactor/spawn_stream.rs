@@ -0,0 +1,76 @@
+use futures::{task, Async, Future, IntoFuture, Poll, Stream};
+use futures::stream::FuturesUnordered;
+
+/// Future spawned by `Handle::spawn_stream_with`, which drives a stream and
+/// runs a handler on each of its items.
+pub struct SpawnStream<S, F, R, E> {
+    stream: S,
+    f: F,
+    on_error: E,
+    in_flight: FuturesUnordered<R>,
+    max_in_flight: usize,
+    done: bool,
+}
+
+pub fn new<S, F, R, E>(stream: S, max_in_flight: usize, f: F, on_error: E)
+                       -> SpawnStream<S, F, R::Future, E>
+    where S: Stream,
+          F: FnMut(S::Item) -> R,
+          R: IntoFuture<Item = (), Error = ()>,
+          E: FnMut(S::Error) -> bool,
+{
+    assert!(max_in_flight > 0, "at least one handler must be allowed to run");
+    SpawnStream {
+        stream: stream,
+        f: f,
+        on_error: on_error,
+        in_flight: FuturesUnordered::new(),
+        max_in_flight: max_in_flight,
+        done: false,
+    }
+}
+
+impl<S, F, R, E> Future for SpawnStream<S, F, R::Future, E>
+    where S: Stream,
+          F: FnMut(S::Item) -> R,
+          R: IntoFuture<Item = (), Error = ()>,
+          E: FnMut(S::Error) -> bool,
+{
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        // Set once the stream failed and is kept, after which it's only
+        // polled again in a later poll, so that a stream which keeps failing
+        // doesn't keep the event loop busy.
+        let mut yielded = false;
+        loop {
+            while !self.done && !yielded && self.in_flight.len() < self.max_in_flight {
+                match self.stream.poll() {
+                    Ok(Async::Ready(Some(item))) => {
+                        self.in_flight.push((self.f)(item).into_future());
+                    }
+                    Ok(Async::Ready(None)) => self.done = true,
+                    Ok(Async::NotReady) => break,
+                    Err(e) => {
+                        self.done = !(self.on_error)(e);
+                        if !self.done {
+                            task::current().notify();
+                            yielded = true;
+                        }
+                    }
+                }
+            }
+
+            // Handlers take care of their own errors, so a failed one has
+            // simply freed up a slot like a successful one.
+            match self.in_flight.poll() {
+                Ok(Async::Ready(Some(()))) | Err(()) => {}
+                Ok(Async::Ready(None)) => {
+                    return Ok(if self.done { Async::Ready(()) } else { Async::NotReady })
+                }
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
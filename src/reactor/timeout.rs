@@ -9,7 +9,7 @@ use std::time::{Duration, Instant};
 use futures::{Future, Poll};
 use tokio_timer::Delay;
 
-use reactor::Handle;
+use reactor::{error, Handle};
 
 /// A future representing the notification that a timeout has occurred.
 ///
@@ -31,7 +31,7 @@ impl Timeout {
     /// error. The timeout object itself is then a future which will be
     /// set to fire at the specified point in the future.
     pub fn new(dur: Duration, handle: &Handle) -> io::Result<Timeout> {
-        Timeout::new_at(Instant::now() + dur, handle)
+        Timeout::new_at(handle.remote.clock.now() + dur, handle)
     }
 
     /// Creates a new timeout which will fire at the time specified by `at`.
@@ -69,6 +69,6 @@ impl Future for Timeout {
 
     fn poll(&mut self) -> Poll<(), io::Error> {
         self.delay.poll()
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+            .map_err(error::timer_error)
     }
 }
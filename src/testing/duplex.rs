@@ -0,0 +1,153 @@
+use std::cmp;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use futures::{task, Async, Poll};
+use futures::task::Task;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+
+/// Creates a pair of connected in-memory streams.
+///
+/// Data written to one of the returned streams can be read from the other,
+/// which makes the pair a stand-in for a connected socket when testing
+/// protocol code. Each direction buffers at most `capacity` bytes, after
+/// which writes return `WouldBlock` until the other end has read some data.
+///
+/// Shutting down the write half of one end, or dropping it, makes the other
+/// end read EOF once all buffered data has been consumed. Writing to an end
+/// whose peer has been dropped fails with `BrokenPipe`.
+///
+/// Reads and writes which would block schedule the current task to be
+/// notified once they can make progress, so they must be performed from
+/// within a task.
+///
+/// # Panics
+///
+/// This function panics if `capacity` is zero.
+pub fn duplex(capacity: usize) -> (DuplexStream, DuplexStream) {
+    assert!(capacity > 0, "duplex streams need a nonzero capacity");
+    let a = Arc::new(Mutex::new(Pipe::new(capacity)));
+    let b = Arc::new(Mutex::new(Pipe::new(capacity)));
+    let one = DuplexStream { read: a.clone(), write: b.clone() };
+    let two = DuplexStream { read: b, write: a };
+    (one, two)
+}
+
+/// One end of an in-memory stream pair created by `duplex`.
+pub struct DuplexStream {
+    read: Arc<Mutex<Pipe>>,
+    write: Arc<Mutex<Pipe>>,
+}
+
+struct Pipe {
+    buf: VecDeque<u8>,
+    capacity: usize,
+    closed: bool,
+    reader: Option<Task>,
+    writer: Option<Task>,
+}
+
+impl Pipe {
+    fn new(capacity: usize) -> Pipe {
+        Pipe {
+            buf: VecDeque::new(),
+            capacity: capacity,
+            closed: false,
+            reader: None,
+            writer: None,
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        if let Some(task) = self.reader.take() {
+            task.notify();
+        }
+        if let Some(task) = self.writer.take() {
+            task.notify();
+        }
+    }
+}
+
+impl Read for DuplexStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut pipe = self.read.lock().unwrap();
+        if pipe.buf.is_empty() {
+            if pipe.closed || buf.is_empty() {
+                return Ok(0)
+            }
+            pipe.reader = Some(task::current());
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+
+        let n = cmp::min(buf.len(), pipe.buf.len());
+        for (dst, src) in buf.iter_mut().zip(pipe.buf.drain(..n)) {
+            *dst = src;
+        }
+        if let Some(task) = pipe.writer.take() {
+            task.notify();
+        }
+        Ok(n)
+    }
+}
+
+impl Write for DuplexStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut pipe = self.write.lock().unwrap();
+        if pipe.closed {
+            return Err(io::ErrorKind::BrokenPipe.into())
+        }
+        if buf.is_empty() {
+            return Ok(0)
+        }
+
+        let n = cmp::min(buf.len(), pipe.capacity - pipe.buf.len());
+        if n == 0 {
+            pipe.writer = Some(task::current());
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        pipe.buf.extend(&buf[..n]);
+        if let Some(task) = pipe.reader.take() {
+            task.notify();
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncRead for DuplexStream {}
+
+impl AsyncWrite for DuplexStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.write.lock().unwrap().close();
+        Ok(Async::Ready(()))
+    }
+}
+
+#[allow(deprecated)]
+impl ::io::Io for DuplexStream {}
+
+impl Drop for DuplexStream {
+    fn drop(&mut self) {
+        self.read.lock().unwrap().close();
+        self.write.lock().unwrap().close();
+    }
+}
+
+impl fmt::Debug for DuplexStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DuplexStream")
+         .field("readable", &self.read.lock().unwrap().buf.len())
+         .field("writable", &{
+             let pipe = self.write.lock().unwrap();
+             pipe.capacity - pipe.buf.len()
+         })
+         .finish()
+    }
+}
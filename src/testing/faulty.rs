@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use futures::{task, Poll};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use testing::rng::XorShift;
+
+/// A fault which `Faulty` can inject into a read or write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// Perform the operation normally.
+    Pass,
+
+    /// Fail with `WouldBlock` without touching the underlying I/O object.
+    WouldBlock,
+
+    /// Read or write at most this many bytes, which must be at least one.
+    Short(usize),
+
+    /// Fail with an error of this kind without touching the underlying I/O
+    /// object.
+    Error(io::ErrorKind),
+}
+
+/// Wraps an I/O object, injecting faults into its reads and writes.
+///
+/// Faults are taken from a script, with one fault consumed per read or write
+/// call, and once a script runs out calls either pass through or, if enabled
+/// with `random`, are interrupted at random with `WouldBlock` or short reads
+/// and writes. This makes it possible to exercise the error and partial
+/// progress paths of code which would otherwise only see them under rare
+/// conditions.
+///
+/// Injected `WouldBlock` errors keep the usual readiness contract: as the
+/// underlying object wasn't actually asked, the current task is notified
+/// right away so it tries again, which means injecting them must happen from
+/// within a task.
+pub struct Faulty<T> {
+    inner: T,
+    reads: Injector,
+    writes: Injector,
+}
+
+struct Injector {
+    script: VecDeque<Fault>,
+    random: Option<(XorShift, f64)>,
+}
+
+impl<T> Faulty<T> {
+    /// Wraps `inner` without injecting any faults yet.
+    pub fn new(inner: T) -> Faulty<T> {
+        Faulty {
+            inner: inner,
+            reads: Injector::new(),
+            writes: Injector::new(),
+        }
+    }
+
+    /// Appends faults to inject into subsequent reads, one per read call.
+    pub fn script_reads<I>(&mut self, faults: I)
+        where I: IntoIterator<Item = Fault>,
+    {
+        self.reads.script.extend(faults);
+    }
+
+    /// Appends faults to inject into subsequent writes, one per write call.
+    pub fn script_writes<I>(&mut self, faults: I)
+        where I: IntoIterator<Item = Fault>,
+    {
+        self.writes.script.extend(faults);
+    }
+
+    /// Interrupts reads and writes which aren't covered by a script with
+    /// probability `probability`, using a generator seeded with `seed`.
+    ///
+    /// Interrupted calls either fail with `WouldBlock` or transfer fewer bytes
+    /// than they could, but never fail with an error, so code which handles
+    /// partial progress correctly still sees all of the data.
+    pub fn random(&mut self, seed: u64, probability: f64) {
+        self.reads.random = Some((XorShift::new(seed), probability));
+        self.writes.random = Some((XorShift::new(!seed), probability));
+    }
+
+    /// Returns a reference to the underlying I/O object.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying I/O object.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Consumes this wrapper, returning the underlying I/O object.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl Injector {
+    fn new() -> Injector {
+        Injector {
+            script: VecDeque::new(),
+            random: None,
+        }
+    }
+
+    /// Decides what to do with a call transferring up to `len` bytes, and
+    /// returns how many bytes it may transfer.
+    fn next(&mut self, len: usize) -> io::Result<usize> {
+        let fault = match self.script.pop_front() {
+            Some(fault) => fault,
+            None => match self.random {
+                Some((ref mut rng, p)) if len > 0 => {
+                    if !rng.chance(p) {
+                        Fault::Pass
+                    } else if rng.next_u64() % 2 == 0 || len == 1 {
+                        Fault::WouldBlock
+                    } else {
+                        Fault::Short(1 + (rng.next_u64() % (len as u64 - 1)) as usize)
+                    }
+                }
+                _ => Fault::Pass,
+            },
+        };
+
+        match fault {
+            Fault::Pass => Ok(len),
+            Fault::Short(n) => Ok(::std::cmp::min(n, len)),
+            Fault::WouldBlock => {
+                task::current().notify();
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+            Fault::Error(kind) => Err(io::Error::new(kind, "injected fault")),
+        }
+    }
+}
+
+impl<T: Read> Read for Faulty<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = try!(self.reads.next(buf.len()));
+        self.inner.read(&mut buf[..n])
+    }
+}
+
+impl<T: Write> Write for Faulty<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = try!(self.writes.next(buf.len()));
+        self.inner.write(&buf[..n])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for Faulty<T> {}
+
+impl<T: AsyncWrite> AsyncWrite for Faulty<T> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.inner.shutdown()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Faulty<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Faulty")
+         .field("inner", &self.inner)
+         .field("scripted_reads", &self.reads.script.len())
+         .field("scripted_writes", &self.writes.script.len())
+         .finish()
+    }
+}
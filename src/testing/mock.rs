@@ -0,0 +1,176 @@
+use std::cmp;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use mio::{self, Evented, Poll, PollOpt, Ready, Registration, SetReadiness, Token};
+
+/// An in-memory I/O object whose readiness is controlled by the test.
+///
+/// A `MockEvented` is registered with an event loop like any other I/O
+/// object, typically through `PollEvented::new`, and is driven through the
+/// `MockControl` returned alongside it. The control can be moved to another
+/// thread, and any change it makes wakes up the task waiting on the mock.
+///
+/// Reading returns the data queued with `MockControl::push_read`, or
+/// `WouldBlock` if there is none, after which the object is no longer
+/// readable until more data is queued. Writing appends to a buffer which can
+/// be inspected with `MockControl::take_written`, and fails with `WouldBlock`
+/// whenever the object isn't writable. A new mock is writable but not
+/// readable.
+pub struct MockEvented {
+    registration: Registration,
+    control: MockControl,
+}
+
+/// Controls the readiness and contents of a `MockEvented`.
+#[derive(Clone)]
+pub struct MockControl {
+    set_readiness: SetReadiness,
+    shared: Arc<Mutex<Shared>>,
+}
+
+struct Shared {
+    input: VecDeque<u8>,
+    eof: bool,
+    output: Vec<u8>,
+}
+
+impl MockEvented {
+    /// Creates a new mock I/O object along with the handle controlling it.
+    pub fn new() -> (MockEvented, MockControl) {
+        let (registration, set_readiness) = Registration::new2();
+        let control = MockControl {
+            set_readiness: set_readiness,
+            shared: Arc::new(Mutex::new(Shared {
+                input: VecDeque::new(),
+                eof: false,
+                output: Vec::new(),
+            })),
+        };
+        control.set_readiness(Ready::writable());
+        let mock = MockEvented {
+            registration: registration,
+            control: control.clone(),
+        };
+        (mock, control)
+    }
+}
+
+impl MockControl {
+    /// Returns the current readiness of the mock.
+    pub fn readiness(&self) -> Ready {
+        self.set_readiness.readiness()
+    }
+
+    /// Sets the readiness of the mock, waking up any task waiting for it.
+    pub fn set_readiness(&self, ready: Ready) {
+        // This only fails if the reactor has gone away, in which case there
+        // is nobody left to notify.
+        let _ = self.set_readiness.set_readiness(ready);
+    }
+
+    /// Queues `data` to be read from the mock and marks it readable.
+    pub fn push_read(&self, data: &[u8]) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.input.extend(data);
+        self.set_readiness(self.readiness() | Ready::readable());
+    }
+
+    /// Makes reads return end of file once all queued data has been read, and
+    /// marks the mock readable.
+    pub fn close_read(&self) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.eof = true;
+        self.set_readiness(self.readiness() | Ready::readable());
+    }
+
+    /// Sets whether the mock accepts writes, waking up any task waiting to
+    /// write when it becomes writable.
+    pub fn set_writable(&self, writable: bool) {
+        // Readiness is only ever updated with the lock held so concurrent
+        // updates don't clobber each other.
+        let _shared = self.shared.lock().unwrap();
+        let mut ready = self.readiness();
+        if writable {
+            ready.insert(Ready::writable());
+        } else {
+            ready.remove(Ready::writable());
+        }
+        self.set_readiness(ready);
+    }
+
+    /// Returns all data written to the mock since the last call.
+    pub fn take_written(&self) -> Vec<u8> {
+        let mut shared = self.shared.lock().unwrap();
+        ::std::mem::replace(&mut shared.output, Vec::new())
+    }
+}
+
+impl Read for MockEvented {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut shared = self.control.shared.lock().unwrap();
+        if shared.input.is_empty() {
+            if shared.eof {
+                return Ok(0)
+            }
+            let mut ready = self.control.readiness();
+            ready.remove(Ready::readable());
+            self.control.set_readiness(ready);
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+
+        let n = cmp::min(buf.len(), shared.input.len());
+        for (dst, src) in buf.iter_mut().zip(shared.input.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for MockEvented {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.control.readiness().is_writable() {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        self.control.shared.lock().unwrap().output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Evented for MockEvented {
+    fn register(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+                -> io::Result<()> {
+        self.registration.register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &Poll, token: Token, interest: Ready, opts: PollOpt)
+                  -> io::Result<()> {
+        self.registration.reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &Poll) -> io::Result<()> {
+        mio::Evented::deregister(&self.registration, poll)
+    }
+}
+
+impl fmt::Debug for MockEvented {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MockEvented")
+         .field("readiness", &self.control.readiness())
+         .finish()
+    }
+}
+
+impl fmt::Debug for MockControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MockControl")
+         .field("readiness", &self.readiness())
+         .finish()
+    }
+}
@@ -0,0 +1,17 @@
+//! Utilities for testing code built on `tokio-core`.
+//!
+//! The types in this module stand in for real I/O resources in tests, so
+//! custom I/O wrappers and protocols can be exercised deterministically
+//! without sockets or sleeps.
+
+mod duplex;
+mod faulty;
+mod mock;
+mod net;
+mod rng;
+
+pub use self::duplex::{duplex, DuplexStream};
+pub use self::faulty::{Faulty, Fault};
+pub use self::mock::{MockEvented, MockControl};
+pub use self::net::{SimNet, SimUdpSocket, SimTcpListener, SimIncoming, SimTcpConnect};
+pub use self::net::SimTcpStream;
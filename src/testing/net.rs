@@ -0,0 +1,753 @@
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
+
+use futures::{task, Async, Future, Poll, Stream};
+use futures::task::Task;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use reactor::{Handle, Timeout};
+use testing::rng::XorShift;
+
+/// A simulated network for deterministic integration tests.
+///
+/// A `SimNet` stands in for the network between any number of hosts, each
+/// identified by an IP address. Sockets bound on it behave like their real
+/// counterparts, but every packet sent between them is delivered by the event
+/// loop the network was created on, after the configured latency. Datagrams
+/// can be dropped at random with a configurable probability, and pairs of
+/// hosts can be partitioned from each other.
+///
+/// Loss and partitions are applied the way a real network would appear to
+/// the sockets involved. Datagrams which are lost or sent across a partition
+/// are dropped, whereas TCP data, connection attempts and end of stream are
+/// held back while hosts are partitioned and delivered in order once the
+/// partition heals, as if retransmitted.
+///
+/// Random decisions are taken from a generator seeded with the seed given to
+/// `new`, so when used with a core from `Core::new_simulated` a test behaves
+/// the same way every time it's run.
+///
+/// A `SimNet` is cheaply cloneable, and clones refer to the same network.
+#[derive(Clone)]
+pub struct SimNet {
+    state: Rc<RefCell<State>>,
+}
+
+struct State {
+    me: Weak<RefCell<State>>,
+    handle: Handle,
+    latency: Duration,
+    loss: f64,
+    rng: XorShift,
+    partitions: HashSet<(IpAddr, IpAddr)>,
+    queue: BTreeMap<(Instant, u64), Packet>,
+    held: Vec<Packet>,
+    next_seq: u64,
+    next_port: u16,
+    udp: HashMap<SocketAddr, Weak<RefCell<UdpState>>>,
+    listeners: HashMap<SocketAddr, Weak<RefCell<ListenerState>>>,
+    driver: Option<Task>,
+}
+
+struct Packet {
+    src: IpAddr,
+    dst: IpAddr,
+    kind: Kind,
+}
+
+enum Kind {
+    Datagram { from: SocketAddr, to: SocketAddr, data: Vec<u8> },
+    Connect { from: SocketAddr, to: SocketAddr, connect: Rc<RefCell<ConnectState>> },
+    Data { half: Rc<RefCell<Half>>, data: Vec<u8> },
+    Fin { half: Rc<RefCell<Half>> },
+}
+
+impl Packet {
+    /// Whether this is TCP traffic, which has to be delivered in order.
+    fn is_stream(&self) -> bool {
+        match self.kind {
+            Kind::Datagram { .. } => false,
+            _ => true,
+        }
+    }
+}
+
+/// Delivers packets once they're due.
+struct Driver {
+    state: Weak<RefCell<State>>,
+    handle: Handle,
+    timeout: Option<Timeout>,
+}
+
+const EPHEMERAL_PORTS: u16 = 49152;
+
+impl SimNet {
+    /// Creates a new simulated network driven by the event loop `handle` is
+    /// associated with, taking random decisions from a generator seeded with
+    /// `seed`.
+    ///
+    /// The network starts out without any latency, loss or partitions.
+    pub fn new(handle: &Handle, seed: u64) -> SimNet {
+        let state = Rc::new(RefCell::new(State {
+            me: Weak::new(),
+            handle: handle.clone(),
+            latency: Duration::from_millis(0),
+            loss: 0.0,
+            rng: XorShift::new(seed),
+            partitions: HashSet::new(),
+            queue: BTreeMap::new(),
+            held: Vec::new(),
+            next_seq: 0,
+            next_port: EPHEMERAL_PORTS,
+            udp: HashMap::new(),
+            listeners: HashMap::new(),
+            driver: None,
+        }));
+        state.borrow_mut().me = Rc::downgrade(&state);
+        handle.spawn(Driver {
+            state: Rc::downgrade(&state),
+            handle: handle.clone(),
+            timeout: None,
+        });
+        SimNet { state: state }
+    }
+
+    /// Sets how long packets take to reach their destination.
+    ///
+    /// This only affects packets sent after the call.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.borrow_mut().latency = latency;
+    }
+
+    /// Sets the probability, between 0 and 1, with which each datagram is
+    /// dropped.
+    ///
+    /// This only affects datagrams sent after the call.
+    pub fn set_loss(&self, loss: f64) {
+        self.state.borrow_mut().loss = loss;
+    }
+
+    /// Partitions hosts `a` and `b` from each other, in both directions.
+    pub fn partition(&self, a: IpAddr, b: IpAddr) {
+        self.state.borrow_mut().partitions.insert(pair(a, b));
+    }
+
+    /// Heals a partition between `a` and `b` created with `partition`.
+    ///
+    /// Any TCP traffic which was held back by the partition is delivered right
+    /// away, in order, and ahead of any TCP traffic between the two hosts
+    /// which is still in flight.
+    pub fn heal(&self, a: IpAddr, b: IpAddr) {
+        let mut st = self.state.borrow_mut();
+        st.partitions.remove(&pair(a, b));
+
+        let (retry, held) = st.held.drain(..).partition::<Vec<_>, _>(|p| {
+            pair(p.src, p.dst) == pair(a, b)
+        });
+        st.held = held;
+
+        // Traffic still in flight was sent after the held back traffic, so
+        // it's queued again behind it.
+        let in_flight = st.queue.iter()
+            .filter(|&(_, p)| p.is_stream() && pair(p.src, p.dst) == pair(a, b))
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        let in_flight = in_flight.into_iter()
+            .map(|key| (key.0, st.queue.remove(&key).unwrap()))
+            .collect::<Vec<_>>();
+
+        let now = st.handle.now();
+        for packet in retry {
+            st.send_at(now, packet);
+        }
+        for (at, packet) in in_flight {
+            st.send_at(cmp::max(at, now), packet);
+        }
+    }
+
+    /// Creates a UDP socket bound to `addr` on this network.
+    ///
+    /// If the port of `addr` is 0 then an unused port is picked.
+    pub fn bind_udp(&self, addr: &SocketAddr) -> io::Result<SimUdpSocket> {
+        let mut st = self.state.borrow_mut();
+        let st = &mut *st;
+        let addr = {
+            let udp = &st.udp;
+            try!(pick_addr(&mut st.next_port, addr, |a| {
+                udp.get(a).and_then(|s| s.upgrade()).is_some()
+            }))
+        };
+        let socket = Rc::new(RefCell::new(UdpState {
+            queue: VecDeque::new(),
+            task: None,
+        }));
+        st.udp.insert(addr, Rc::downgrade(&socket));
+        Ok(SimUdpSocket {
+            net: self.clone(),
+            addr: addr,
+            state: socket,
+        })
+    }
+
+    /// Creates a TCP listener bound to `addr` on this network.
+    ///
+    /// If the port of `addr` is 0 then an unused port is picked.
+    pub fn bind_tcp(&self, addr: &SocketAddr) -> io::Result<SimTcpListener> {
+        let mut st = self.state.borrow_mut();
+        let st = &mut *st;
+        let addr = {
+            let listeners = &st.listeners;
+            try!(pick_addr(&mut st.next_port, addr, |a| {
+                listeners.get(a).and_then(|s| s.upgrade()).is_some()
+            }))
+        };
+        let listener = Rc::new(RefCell::new(ListenerState {
+            backlog: VecDeque::new(),
+            task: None,
+        }));
+        st.listeners.insert(addr, Rc::downgrade(&listener));
+        Ok(SimTcpListener {
+            net: self.clone(),
+            addr: addr,
+            state: listener,
+        })
+    }
+
+    /// Opens a TCP connection from host `local` to the listener at `addr`.
+    ///
+    /// The returned future resolves once the connection request has reached
+    /// `addr`, and fails with `ConnectionRefused` if there's no listener
+    /// there.
+    pub fn connect_tcp(&self, local: IpAddr, addr: &SocketAddr) -> SimTcpConnect {
+        let connect = Rc::new(RefCell::new(ConnectState {
+            result: None,
+            task: None,
+        }));
+        let mut st = self.state.borrow_mut();
+        let port = ephemeral_port(&mut st.next_port);
+        st.send(Packet {
+            src: local,
+            dst: addr.ip(),
+            kind: Kind::Connect {
+                from: SocketAddr::new(local, port),
+                to: *addr,
+                connect: connect.clone(),
+            },
+        });
+        SimTcpConnect { state: connect }
+    }
+}
+
+impl fmt::Debug for SimNet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let st = self.state.borrow();
+        f.debug_struct("SimNet")
+         .field("latency", &st.latency)
+         .field("loss", &st.loss)
+         .field("partitions", &st.partitions)
+         .field("in_flight", &st.queue.len())
+         .field("held", &st.held.len())
+         .finish()
+    }
+}
+
+fn pair(a: IpAddr, b: IpAddr) -> (IpAddr, IpAddr) {
+    (cmp::min(a, b), cmp::max(a, b))
+}
+
+fn ephemeral_port(next_port: &mut u16) -> u16 {
+    let port = *next_port;
+    *next_port = if port == u16::max_value() {
+        EPHEMERAL_PORTS
+    } else {
+        port + 1
+    };
+    port
+}
+
+/// Returns `addr`, or `addr` with an unused port if its port is 0.
+fn pick_addr<F>(next_port: &mut u16, addr: &SocketAddr, taken: F) -> io::Result<SocketAddr>
+    where F: Fn(&SocketAddr) -> bool,
+{
+    if addr.port() != 0 {
+        if taken(addr) {
+            return Err(io::ErrorKind::AddrInUse.into())
+        }
+        return Ok(*addr)
+    }
+    for _ in EPHEMERAL_PORTS..u16::max_value() {
+        let candidate = SocketAddr::new(addr.ip(), ephemeral_port(next_port));
+        if !taken(&candidate) {
+            return Ok(candidate)
+        }
+    }
+    Err(io::ErrorKind::AddrInUse.into())
+}
+
+impl State {
+    fn send(&mut self, packet: Packet) {
+        let at = self.handle.now() + self.latency;
+        self.send_at(at, packet);
+    }
+
+    fn send_at(&mut self, at: Instant, packet: Packet) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.queue.insert((at, seq), packet);
+        if let Some(task) = self.driver.take() {
+            task.notify();
+        }
+    }
+
+    fn deliver(&mut self, packet: Packet) {
+        if packet.src != packet.dst && self.partitions.contains(&pair(packet.src, packet.dst)) {
+            match packet.kind {
+                Kind::Datagram { .. } => {}
+                _ => self.held.push(packet),
+            }
+            return
+        }
+
+        match packet.kind {
+            Kind::Datagram { from, to, data } => {
+                if let Some(socket) = self.udp.get(&to).and_then(|s| s.upgrade()) {
+                    let mut socket = socket.borrow_mut();
+                    socket.queue.push_back((data, from));
+                    notify(&mut socket.task);
+                }
+            }
+            Kind::Connect { from, to, connect } => {
+                // Nobody is waiting for the connection anymore.
+                if Rc::strong_count(&connect) == 1 {
+                    return
+                }
+
+                let result = match self.listeners.get(&to).and_then(|l| l.upgrade()) {
+                    Some(listener) => {
+                        let net = SimNet { state: self.me.upgrade().unwrap() };
+                        let (client, server) = SimTcpStream::pair(net, from, to);
+                        let mut listener = listener.borrow_mut();
+                        listener.backlog.push_back((server, from));
+                        notify(&mut listener.task);
+                        Ok(client)
+                    }
+                    None => Err(io::ErrorKind::ConnectionRefused.into()),
+                };
+                let mut connect = connect.borrow_mut();
+                connect.result = Some(result);
+                notify(&mut connect.task);
+            }
+            Kind::Data { half, data } => {
+                let mut half = half.borrow_mut();
+                half.buf.extend(data);
+                notify(&mut half.task);
+            }
+            Kind::Fin { half } => {
+                let mut half = half.borrow_mut();
+                half.eof = true;
+                notify(&mut half.task);
+            }
+        }
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        // Let the driver know it can stop.
+        if let Some(task) = self.driver.take() {
+            task.notify();
+        }
+    }
+}
+
+fn notify(task: &mut Option<Task>) {
+    if let Some(task) = task.take() {
+        task.notify();
+    }
+}
+
+impl Future for Driver {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<(), ()> {
+        loop {
+            let state = match self.state.upgrade() {
+                Some(state) => state,
+                None => return Ok(Async::Ready(())),
+            };
+
+            let next = {
+                let mut st = state.borrow_mut();
+                st.driver = Some(task::current());
+                let now = st.handle.now();
+                loop {
+                    let key = match st.queue.keys().next() {
+                        Some(&key) if key.0 <= now => key,
+                        _ => break,
+                    };
+                    let packet = st.queue.remove(&key).unwrap();
+                    st.deliver(packet);
+                }
+                st.queue.keys().next().map(|key| key.0)
+            };
+
+            let at = match next {
+                Some(at) => at,
+                None => {
+                    self.timeout = None;
+                    return Ok(Async::NotReady)
+                }
+            };
+            match self.timeout {
+                Some(ref mut timeout) => timeout.reset(at),
+                None => self.timeout = Some(Timeout::new_at(at, &self.handle).unwrap()),
+            }
+            match self.timeout.as_mut().unwrap().poll() {
+                Ok(Async::Ready(())) => {}
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => {
+                    debug!("simulated network timer failed: {}", e);
+                    return Err(())
+                }
+            }
+        }
+    }
+}
+
+/// A UDP socket bound on a `SimNet`.
+pub struct SimUdpSocket {
+    net: SimNet,
+    addr: SocketAddr,
+    state: Rc<RefCell<UdpState>>,
+}
+
+struct UdpState {
+    queue: VecDeque<(Vec<u8>, SocketAddr)>,
+    task: Option<Task>,
+}
+
+impl SimUdpSocket {
+    /// Returns the local address that this socket is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    /// Sends a datagram to the given address.
+    ///
+    /// This always succeeds immediately, like sending on a real socket does,
+    /// even if the datagram is going to be lost.
+    pub fn send_to(&self, buf: &[u8], target: &SocketAddr) -> io::Result<usize> {
+        let mut st = self.net.state.borrow_mut();
+        let loss = st.loss;
+        if st.rng.chance(loss) {
+            return Ok(buf.len())
+        }
+        st.send(Packet {
+            src: self.addr.ip(),
+            dst: target.ip(),
+            kind: Kind::Datagram {
+                from: self.addr,
+                to: *target,
+                data: buf.to_vec(),
+            },
+        });
+        Ok(buf.len())
+    }
+
+    /// Receives a datagram from the socket, returning the number of bytes
+    /// read and the address it came from.
+    ///
+    /// If no datagram has arrived yet then `WouldBlock` is returned and the
+    /// current task is notified once one does. Datagrams larger than `buf`
+    /// are truncated.
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        let mut state = self.state.borrow_mut();
+        match state.queue.pop_front() {
+            Some((data, from)) => {
+                let n = cmp::min(buf.len(), data.len());
+                buf[..n].copy_from_slice(&data[..n]);
+                Ok((n, from))
+            }
+            None => {
+                state.task = Some(task::current());
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+        }
+    }
+
+    /// Test whether a datagram is ready to be received.
+    ///
+    /// If not then the current task is notified once one arrives.
+    pub fn poll_read(&self) -> Async<()> {
+        let mut state = self.state.borrow_mut();
+        if state.queue.is_empty() {
+            state.task = Some(task::current());
+            Async::NotReady
+        } else {
+            Async::Ready(())
+        }
+    }
+}
+
+impl Drop for SimUdpSocket {
+    fn drop(&mut self) {
+        self.net.state.borrow_mut().udp.remove(&self.addr);
+    }
+}
+
+impl fmt::Debug for SimUdpSocket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SimUdpSocket")
+         .field("addr", &self.addr)
+         .finish()
+    }
+}
+
+/// A TCP listener bound on a `SimNet`.
+pub struct SimTcpListener {
+    net: SimNet,
+    addr: SocketAddr,
+    state: Rc<RefCell<ListenerState>>,
+}
+
+struct ListenerState {
+    backlog: VecDeque<(SimTcpStream, SocketAddr)>,
+    task: Option<Task>,
+}
+
+/// Stream of connections accepted by a `SimTcpListener`, returned by
+/// `SimTcpListener::incoming`.
+#[must_use = "streams do nothing unless polled"]
+pub struct SimIncoming {
+    inner: SimTcpListener,
+}
+
+impl SimTcpListener {
+    /// Returns the local address that this listener is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    /// Accepts a new incoming connection to this listener.
+    ///
+    /// If no connection is pending then `WouldBlock` is returned and the
+    /// current task is notified once one arrives.
+    pub fn accept(&mut self) -> io::Result<(SimTcpStream, SocketAddr)> {
+        let mut state = self.state.borrow_mut();
+        match state.backlog.pop_front() {
+            Some(conn) => Ok(conn),
+            None => {
+                state.task = Some(task::current());
+                Err(io::ErrorKind::WouldBlock.into())
+            }
+        }
+    }
+
+    /// Consumes this listener, returning a stream of the connections it
+    /// accepts.
+    pub fn incoming(self) -> SimIncoming {
+        SimIncoming { inner: self }
+    }
+}
+
+impl Drop for SimTcpListener {
+    fn drop(&mut self) {
+        self.net.state.borrow_mut().listeners.remove(&self.addr);
+    }
+}
+
+impl fmt::Debug for SimTcpListener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SimTcpListener")
+         .field("addr", &self.addr)
+         .finish()
+    }
+}
+
+impl Stream for SimIncoming {
+    type Item = (SimTcpStream, SocketAddr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, io::Error> {
+        Ok(Async::Ready(Some(try_nb!(self.inner.accept()))))
+    }
+}
+
+impl fmt::Debug for SimIncoming {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+/// Future returned by `SimNet::connect_tcp`, which resolves to a
+/// `SimTcpStream` once connected.
+#[must_use = "futures do nothing unless polled"]
+pub struct SimTcpConnect {
+    state: Rc<RefCell<ConnectState>>,
+}
+
+struct ConnectState {
+    result: Option<io::Result<SimTcpStream>>,
+    task: Option<Task>,
+}
+
+impl Future for SimTcpConnect {
+    type Item = SimTcpStream;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<SimTcpStream, io::Error> {
+        let mut state = self.state.borrow_mut();
+        match state.result.take() {
+            Some(result) => result.map(Async::Ready),
+            None => {
+                state.task = Some(task::current());
+                Ok(Async::NotReady)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for SimTcpConnect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SimTcpConnect").finish()
+    }
+}
+
+/// A TCP connection over a `SimNet`.
+///
+/// Writes never block: data is buffered without limit until it's delivered
+/// to the other end. Shutting down the write half, or dropping the stream,
+/// makes the other end read EOF once everything written before has arrived.
+pub struct SimTcpStream {
+    net: SimNet,
+    local: SocketAddr,
+    peer: SocketAddr,
+    rx: Rc<RefCell<Half>>,
+    tx: Rc<RefCell<Half>>,
+    shutdown: bool,
+}
+
+/// One direction of a connection, as seen by the receiving end.
+struct Half {
+    buf: VecDeque<u8>,
+    eof: bool,
+    task: Option<Task>,
+}
+
+impl SimTcpStream {
+    fn pair(net: SimNet, client: SocketAddr, server: SocketAddr)
+            -> (SimTcpStream, SimTcpStream) {
+        let half = || Rc::new(RefCell::new(Half {
+            buf: VecDeque::new(),
+            eof: false,
+            task: None,
+        }));
+        let (up, down) = (half(), half());
+        let a = SimTcpStream {
+            net: net.clone(),
+            local: client,
+            peer: server,
+            rx: down.clone(),
+            tx: up.clone(),
+            shutdown: false,
+        };
+        let b = SimTcpStream {
+            net: net,
+            local: server,
+            peer: client,
+            rx: up,
+            tx: down,
+            shutdown: false,
+        };
+        (a, b)
+    }
+
+    /// Returns the local address that this stream is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    /// Returns the remote address that this stream is connected to.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.peer)
+    }
+
+    fn send(&self, kind: Kind) {
+        self.net.state.borrow_mut().send(Packet {
+            src: self.local.ip(),
+            dst: self.peer.ip(),
+            kind: kind,
+        });
+    }
+}
+
+impl Read for SimTcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut rx = self.rx.borrow_mut();
+        if rx.buf.is_empty() {
+            if rx.eof || buf.is_empty() {
+                return Ok(0)
+            }
+            rx.task = Some(task::current());
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+
+        let n = cmp::min(buf.len(), rx.buf.len());
+        for (dst, src) in buf.iter_mut().zip(rx.buf.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for SimTcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.shutdown {
+            return Err(io::ErrorKind::BrokenPipe.into())
+        }
+        if !buf.is_empty() {
+            self.send(Kind::Data { half: self.tx.clone(), data: buf.to_vec() });
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AsyncRead for SimTcpStream {}
+
+impl AsyncWrite for SimTcpStream {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        if !self.shutdown {
+            self.shutdown = true;
+            self.send(Kind::Fin { half: self.tx.clone() });
+        }
+        Ok(Async::Ready(()))
+    }
+}
+
+impl Drop for SimTcpStream {
+    fn drop(&mut self) {
+        if !self.shutdown {
+            self.send(Kind::Fin { half: self.tx.clone() });
+        }
+    }
+}
+
+impl fmt::Debug for SimTcpStream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SimTcpStream")
+         .field("local", &self.local)
+         .field("peer", &self.peer)
+         .finish()
+    }
+}
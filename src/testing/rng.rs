@@ -0,0 +1,33 @@
+/// A small, seedable pseudo-random number generator, so tests using it are
+/// reproducible.
+///
+/// This is xorshift64*, which is plenty for deciding which packets to drop
+/// but is of course not suitable for anything security related.
+#[derive(Clone, Debug)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // The generator gets stuck at zero, so avoid that state.
+        XorShift { state: seed ^ 0x9e37_79b9_7f4a_7c15 | 1 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Returns `true` with probability `p`.
+    pub fn chance(&mut self, p: f64) -> bool {
+        if p <= 0.0 {
+            return false
+        }
+        // Use the top 53 bits to get a uniformly distributed float in [0, 1).
+        let f = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        f < p
+    }
+}
@@ -0,0 +1,49 @@
+extern crate futures;
+extern crate tokio_core;
+
+use std::sync::mpsc;
+
+use futures::future;
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn runs_and_shuts_down() {
+    let (remote, background) = t!(Core::background());
+
+    let (tx, rx) = mpsc::channel();
+    remote.spawn(move |_| {
+        tx.send(1).unwrap();
+        Ok(())
+    });
+    assert_eq!(t!(rx.recv()), 1);
+
+    // A future which never completes is dropped along with the event loop.
+    let (tx, rx) = mpsc::channel::<()>();
+    remote.spawn(move |_| {
+        future::poll_fn(move || {
+            let _tx = &tx;
+            Ok(futures::Async::NotReady)
+        })
+    });
+    drop(background);
+    assert!(rx.recv().is_err());
+}
+
+#[test]
+fn shutdown_returns_panics() {
+    let (remote, background) = t!(Core::background());
+    let (tx, rx) = mpsc::channel();
+    remote.spawn(move |_| -> Result<(), ()> {
+        tx.send(()).unwrap();
+        panic!("event loop failure")
+    });
+    t!(rx.recv());
+    assert!(background.shutdown().is_err());
+}
@@ -0,0 +1,135 @@
+extern crate bytes;
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::{self, Write};
+use std::net;
+use std::sync::mpsc;
+use std::thread;
+
+use bytes::BytesMut;
+use futures::{future, Async, Future, Stream};
+use tokio_core::buf_pool::{BufPool, PooledFramedRead};
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::Core;
+use tokio_io::codec::Decoder;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+struct LineCodec;
+
+impl Decoder for LineCodec {
+    type Item = String;
+    type Error = io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> io::Result<Option<String>> {
+        match buf.iter().position(|&b| b == b'\n') {
+            Some(i) => {
+                let line = buf.split_to(i + 1);
+                Ok(Some(String::from_utf8_lossy(&line[..i]).into_owned()))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[test]
+fn buffers_are_recycled() {
+    drop(env_logger::init());
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let pool = BufPool::new(1024, 4);
+
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(listener.local_addr());
+
+    let t = thread::spawn(move || {
+        for _ in 0..2 {
+            let mut s = t!(net::TcpStream::connect(&addr));
+            t!(s.write_all(b"a\nb\n"));
+        }
+    });
+
+    let frames = listener.incoming().take(2).map(|(socket, _)| {
+        PooledFramedRead::new(socket, LineCodec, pool.clone()).collect()
+    }).buffered(1).collect();
+    let frames = t!(core.run(frames));
+    t.join().unwrap();
+
+    assert_eq!(frames, vec![vec!["a", "b"], vec!["a", "b"]]);
+
+    // The connections were read one after the other, so both read through
+    // the same buffer, which is back in the pool now that they are gone.
+    assert_eq!(pool.idle(), 1);
+}
+
+#[test]
+fn idle_connections_hold_no_buffer() {
+    drop(env_logger::init());
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let pool = BufPool::new(1024, 4);
+
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(listener.local_addr());
+
+    let (tx, rx) = mpsc::channel::<()>();
+    let t = thread::spawn(move || {
+        let mut s = t!(net::TcpStream::connect(&addr));
+        t!(s.write_all(b"a\n"));
+        assert!(rx.recv().is_err());
+    });
+
+    let (socket, _) = t!(core.run(listener.incoming().into_future()
+                                          .map_err(|(e, _)| e)))
+        .0.unwrap();
+    let mut lines = PooledFramedRead::new(socket, LineCodec, pool.clone());
+    let line = t!(core.run(future::poll_fn(|| lines.poll())));
+    assert_eq!(line, Some("a".to_string()));
+    assert_eq!(pool.idle(), 0);
+
+    // Once the transport waits for more data it gives its buffer back.
+    t!(core.run(future::poll_fn(|| {
+        assert_eq!(t!(lines.poll()), Async::NotReady);
+        Ok::<_, ()>(Async::Ready(()))
+    })));
+    assert_eq!(pool.idle(), 1);
+
+    drop(tx);
+    t.join().unwrap();
+}
+
+#[test]
+fn full_pool_drops_buffers() {
+    let pool = BufPool::new(64, 1);
+    let a = pool.get();
+    let b = pool.get();
+    pool.put(a);
+    pool.put(b);
+    assert_eq!(pool.idle(), 1);
+    assert!(pool.get().capacity() >= 64);
+    assert_eq!(pool.idle(), 0);
+}
+
+#[test]
+fn grown_buffers_are_dropped() {
+    let pool = BufPool::new(64, 4);
+    let mut a = pool.get();
+    a.reserve(64 * 4);
+    pool.put(a);
+    assert_eq!(pool.idle(), 0);
+
+    let mut b = pool.get();
+    b.extend_from_slice(&[0; 64]);
+    pool.put(b);
+    assert_eq!(pool.idle(), 1);
+    let b = pool.get();
+    assert!(b.is_empty());
+}
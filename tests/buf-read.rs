@@ -0,0 +1,76 @@
+#[macro_use]
+extern crate futures;
+extern crate tokio_core;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net;
+use std::thread;
+use std::time::Duration;
+
+use futures::{future, Async, Future, Stream};
+use tokio_core::buf_read::AsyncBufRead;
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn parse_in_place() {
+    let mut core = t!(Core::new());
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &core.handle()));
+    let addr = t!(listener.local_addr());
+
+    let t = thread::spawn(move || {
+        let mut s = t!(net::TcpStream::connect(&addr));
+        t!(s.write_all(b"one\ntw"));
+        thread::sleep(Duration::from_millis(20));
+        t!(s.write_all(b"o\nthree\n"));
+    });
+
+    let stream = match t!(core.run(listener.incoming().into_future().map_err(|(e, _)| e))) {
+        (Some((stream, _)), _) => stream,
+        (None, _) => panic!("no connection"),
+    };
+    let mut reader = BufReader::with_capacity(4, stream);
+
+    // Counts the bytes of each line without copying it anywhere.
+    let mut lengths = Vec::new();
+    let mut current = 0;
+    t!(core.run(future::poll_fn(|| {
+        loop {
+            let used = {
+                let buf = try_ready!(reader.poll_fill_buf());
+                if buf.is_empty() {
+                    return Ok::<_, io::Error>(Async::Ready(()))
+                }
+                match buf.iter().position(|&b| b == b'\n') {
+                    Some(end) => {
+                        lengths.push(current + end);
+                        current = 0;
+                        end + 1
+                    }
+                    None => {
+                        current += buf.len();
+                        buf.len()
+                    }
+                }
+            };
+            reader.consume(used);
+        }
+    })));
+    assert_eq!(lengths, [3, 3, 5]);
+    t.join().unwrap();
+}
+
+#[test]
+fn slices_are_buffered() {
+    let mut data: &[u8] = b"abc";
+    assert_eq!(t!(data.poll_fill_buf()), Async::Ready(&b"abc"[..]));
+    data.consume(2);
+    assert_eq!(t!(data.poll_fill_buf()), Async::Ready(&b"c"[..]));
+}
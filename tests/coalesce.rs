@@ -0,0 +1,157 @@
+extern crate futures;
+extern crate tokio_core;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use futures::{future, Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
+use futures::executor::{self, Notify};
+use futures::sync::{mpsc, oneshot};
+use tokio_core::coalesce::Coalesce;
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+/// Records the items sent to it, and how many times it was flushed with
+/// items pending.
+#[derive(Clone, Default)]
+struct Record(Rc<RefCell<Inner>>);
+
+#[derive(Default)]
+struct Inner {
+    items: Vec<u32>,
+    unflushed: usize,
+    flushes: usize,
+}
+
+impl Record {
+    fn flushed(&self) -> (Vec<u32>, usize) {
+        let inner = self.0.borrow();
+        (inner.items.clone(), inner.flushes)
+    }
+}
+
+impl Sink for Record {
+    type SinkItem = u32;
+    type SinkError = ();
+
+    fn start_send(&mut self, item: u32) -> StartSend<u32, ()> {
+        let mut inner = self.0.borrow_mut();
+        inner.items.push(item);
+        inner.unflushed += 1;
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), ()> {
+        let mut inner = self.0.borrow_mut();
+        if inner.unflushed > 0 {
+            inner.unflushed = 0;
+            inner.flushes += 1;
+        }
+        Ok(Async::Ready(()))
+    }
+
+    fn close(&mut self) -> Poll<(), ()> {
+        self.poll_complete()
+    }
+}
+
+struct Count(AtomicUsize);
+
+impl Notify for Count {
+    fn notify(&self, _id: usize) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn flush_is_deferred_to_next_poll() {
+    let record = Record::default();
+    let notify = Arc::new(Count(AtomicUsize::new(0)));
+    let mut sink = executor::spawn(Coalesce::new(record.clone()));
+
+    assert_eq!(sink.start_send_notify(1, &notify, 0), Ok(AsyncSink::Ready));
+    assert_eq!(sink.start_send_notify(2, &notify, 0), Ok(AsyncSink::Ready));
+    assert_eq!(sink.poll_flush_notify(&notify, 0), Ok(Async::NotReady));
+    assert_eq!(notify.0.load(Ordering::SeqCst), 1);
+    assert_eq!(record.flushed().1, 0);
+
+    assert_eq!(sink.start_send_notify(3, &notify, 0), Ok(AsyncSink::Ready));
+    assert_eq!(sink.poll_flush_notify(&notify, 0), Ok(Async::Ready(())));
+    assert_eq!(record.flushed(), (vec![1, 2, 3], 1));
+
+    // Nothing left to flush, so there's nothing to defer.
+    assert_eq!(sink.poll_flush_notify(&notify, 0), Ok(Async::Ready(())));
+    assert_eq!(notify.0.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn corked_sink_flushes_once_uncorked() {
+    let record = Record::default();
+    let notify = Arc::new(Count(AtomicUsize::new(0)));
+    let mut sink = executor::spawn(Coalesce::new(record.clone()));
+
+    sink.get_mut().cork();
+    assert!(sink.get_ref().is_corked());
+    for i in 0..3 {
+        assert_eq!(sink.start_send_notify(i, &notify, 0), Ok(AsyncSink::Ready));
+        assert_eq!(sink.poll_flush_notify(&notify, 0), Ok(Async::NotReady));
+    }
+    assert_eq!(notify.0.load(Ordering::SeqCst), 0);
+    assert_eq!(record.flushed().1, 0);
+
+    sink.get_mut().uncork();
+    assert_eq!(notify.0.load(Ordering::SeqCst), 1);
+    assert_eq!(sink.poll_flush_notify(&notify, 0), Ok(Async::Ready(())));
+    assert_eq!(record.flushed(), (vec![0, 1, 2], 1));
+}
+
+#[test]
+fn close_flushes_corked_sink() {
+    let record = Record::default();
+    let notify = Arc::new(Count(AtomicUsize::new(0)));
+    let mut sink = executor::spawn(Coalesce::new(record.clone()));
+
+    sink.get_mut().cork();
+    assert_eq!(sink.start_send_notify(1, &notify, 0), Ok(AsyncSink::Ready));
+    assert_eq!(sink.poll_flush_notify(&notify, 0), Ok(Async::NotReady));
+    assert_eq!(sink.close_notify(&notify, 0), Ok(Async::Ready(())));
+    assert!(!sink.get_ref().is_corked());
+    assert_eq!(record.flushed(), (vec![1], 1));
+}
+
+#[test]
+fn items_sent_in_one_turn_are_flushed_together() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let record = Record::default();
+    let (tx, rx) = mpsc::unbounded();
+
+    // The first item is received on its own, before the other two are sent
+    // later in the same turn, but all three are flushed at once.
+    let first = tx.clone();
+    handle.spawn(future::lazy(move || {
+        t!(first.unbounded_send(1));
+        Ok(())
+    }));
+    let (done_tx, done_rx) = oneshot::channel();
+    handle.spawn(rx.forward(Coalesce::new(record.clone())).then(|res| {
+        assert!(res.is_ok());
+        done_tx.send(()).map_err(|_| ())
+    }));
+    handle.spawn(future::lazy(move || {
+        t!(tx.unbounded_send(2));
+        t!(tx.unbounded_send(3));
+        Ok(())
+    }));
+
+    t!(core.run(done_rx));
+    assert_eq!(record.flushed(), (vec![1, 2, 3], 1));
+}
@@ -0,0 +1,129 @@
+#![cfg(feature = "std-future")]
+
+extern crate futures;
+extern crate futures_io;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::future;
+use std::io::{Read, Write};
+use std::net;
+use std::pin::Pin;
+use std::thread;
+use std::time::Duration;
+
+use futures::{Future, Stream};
+use futures::sync::oneshot;
+use futures_io::{AsyncRead, AsyncWrite};
+use tokio_core::compat::{Compat01As03, Compat03As01};
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::{Core, Timeout};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn block_on_01_io() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(listener.local_addr());
+    let t = thread::spawn(move || {
+        t!(net::TcpStream::connect(&addr));
+    });
+
+    let accept = listener.incoming().into_future().map_err(|(e, _)| e);
+    let (conn, _) = t!(core.block_on(Compat01As03::new(accept)));
+    let (_socket, peer) = conn.unwrap();
+    assert_eq!(peer.ip(), addr.ip());
+    t.join().unwrap();
+}
+
+#[test]
+fn spawn_std_future() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let (tx, rx) = oneshot::channel();
+
+    let timeout = t!(Timeout::new(Duration::from_millis(10), &handle));
+    let waiter = Compat01As03::new(timeout);
+    handle.spawn(Compat03As01::new(waiter).then(move |res| {
+        res.unwrap();
+        tx.send(3).unwrap();
+        Ok(())
+    }));
+
+    assert_eq!(t!(core.run(rx)), 3);
+}
+
+#[test]
+fn tokio_io_as_futures_io() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(listener.local_addr());
+    let t = thread::spawn(move || {
+        let mut s = t!(net::TcpStream::connect(&addr));
+        t!(s.write_all(b"ping"));
+        let mut buf = [0; 4];
+        t!(s.read_exact(&mut buf));
+        assert_eq!(&buf, b"pong");
+    });
+
+    let accept = listener.incoming().into_future().map_err(|(e, _)| e);
+    let (socket, _) = t!(core.run(accept)).0.unwrap();
+    let mut socket = Compat01As03::new(socket);
+
+    let mut buf = [0; 4];
+    let mut read = 0;
+    let n = t!(core.block_on(future::poll_fn(|cx| {
+        while read < buf.len() {
+            match Pin::new(&mut socket).poll_read(cx, &mut buf[read..]) {
+                std::task::Poll::Ready(Ok(n)) => read += n,
+                other => return other,
+            }
+        }
+        std::task::Poll::Ready(Ok(read))
+    })));
+    assert_eq!(&buf[..n], b"ping");
+
+    let n = t!(core.block_on(future::poll_fn(|cx| {
+        Pin::new(&mut socket).poll_write(cx, b"pong")
+    })));
+    assert_eq!(n, 4);
+    t!(core.block_on(future::poll_fn(|cx| Pin::new(&mut socket).poll_close(cx))));
+    t.join().unwrap();
+}
+
+#[test]
+fn futures_io_as_tokio_io() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(listener.local_addr());
+    let t = thread::spawn(move || {
+        let mut s = t!(net::TcpStream::connect(&addr));
+        let mut buf = [0; 4];
+        t!(s.read_exact(&mut buf));
+        t!(s.write_all(&buf));
+    });
+
+    let accept = listener.incoming().into_future().map_err(|(e, _)| e);
+    let (socket, _) = t!(core.run(accept)).0.unwrap();
+    // A futures-io object, bridged back into a tokio-io one.
+    let socket = Compat03As01::new(Compat01As03::new(socket));
+
+    let echo = tokio_io::io::write_all(socket, b"echo").and_then(|(socket, _)| {
+        tokio_io::io::read_exact(socket, [0; 4])
+    });
+    let (_, buf) = t!(core.run(echo));
+    assert_eq!(&buf, b"echo");
+    t.join().unwrap();
+}
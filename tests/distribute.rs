@@ -0,0 +1,190 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::{self, Read};
+use std::net;
+use std::sync::Mutex;
+use std::sync::mpsc::channel;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures::Future;
+use futures::future::Either;
+use futures::stream::Stream;
+use futures::sync::{mpsc, oneshot};
+use tokio_core::net::{Balance, TcpListener};
+use tokio_core::reactor::{Core, Error, Remote};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+/// Starts event loops on `n` threads, returning their remotes along with a
+/// way to stop each of them.
+fn workers(n: usize) -> (Vec<Remote>, Vec<(oneshot::Sender<()>, thread::JoinHandle<()>)>) {
+    let mut remotes = Vec::new();
+    let mut workers = Vec::new();
+    for _ in 0..n {
+        let (tx, rx) = channel();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let t = thread::spawn(move || {
+            let mut core = t!(Core::new());
+            tx.send(core.remote()).unwrap();
+            let _ = core.run(stop_rx);
+        });
+        remotes.push(rx.recv().unwrap());
+        workers.push((stop_tx, t));
+    }
+    (remotes, workers)
+}
+
+#[test]
+fn round_robin() {
+    drop(env_logger::init());
+    let mut core = t!(Core::new());
+    let (remotes, workers) = workers(2);
+
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &core.handle()));
+    let addr = t!(listener.local_addr());
+
+    let (tx, rx) = mpsc::unbounded();
+    let tx = Mutex::new(tx);
+    let srv = listener.distribute(remotes, Balance::RoundRobin, move |socket, _, _| {
+        tx.lock().unwrap().unbounded_send(thread::current().id()).unwrap();
+        drop(socket);
+        Ok(())
+    });
+
+    let clients = thread::spawn(move || {
+        for _ in 0..4 {
+            let mut s = t!(net::TcpStream::connect(&addr));
+            // wait for the connection to be handled and closed
+            t!(s.read(&mut [0]));
+        }
+    });
+
+    let ids = match core.run(rx.take(4).collect().select2(srv)) {
+        Ok(Either::A((ids, _))) => ids,
+        _ => panic!("server failed"),
+    };
+    clients.join().unwrap();
+
+    assert_eq!(ids[0], ids[2]);
+    assert_eq!(ids[1], ids[3]);
+    assert!(ids[0] != ids[1]);
+
+    for (stop, t) in workers {
+        let _ = stop.send(());
+        t.join().unwrap();
+    }
+}
+
+/// Runs `srv` until it has handed off another connection, returning the
+/// thread which processes it.
+fn next_id<S, F>(core: &mut Core, ids: &mut S, srv: &mut F) -> thread::ThreadId
+    where S: Stream<Item = thread::ThreadId, Error = ()>,
+          F: Future<Item = (), Error = io::Error>,
+{
+    match core.run(ids.into_future().select2(srv)) {
+        Ok(Either::A(((Some(id), _), _))) => id,
+        _ => panic!("server failed"),
+    }
+}
+
+#[test]
+fn least_loaded() {
+    drop(env_logger::init());
+    let mut core = t!(Core::new());
+    let (remotes, workers) = workers(2);
+
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &core.handle()));
+    let addr = t!(listener.local_addr());
+
+    // Each connection is processed until the client closes it.
+    let (tx, mut rx) = mpsc::unbounded();
+    let tx = Mutex::new(tx);
+    let mut srv = listener.distribute(remotes, Balance::LeastLoaded, move |socket, _, _| {
+        tx.lock().unwrap().unbounded_send(thread::current().id()).unwrap();
+        tokio_io::io::read_to_end(socket, Vec::new()).then(|_| Ok(()))
+    });
+
+    let first = t!(net::TcpStream::connect(&addr));
+    let a = next_id(&mut core, &mut rx, &mut srv);
+    assert_eq!(srv.loads(), [1, 0]);
+    let _second = t!(net::TcpStream::connect(&addr));
+    let b = next_id(&mut core, &mut rx, &mut srv);
+    assert_eq!(srv.loads(), [1, 1]);
+    assert!(a != b);
+
+    drop(first);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while srv.loads() != [0, 1] {
+        assert!(Instant::now() < deadline, "connection never finished");
+        thread::sleep(Duration::from_millis(10));
+    }
+    let _third = t!(net::TcpStream::connect(&addr));
+    assert_eq!(next_id(&mut core, &mut rx, &mut srv), a);
+    assert_eq!(srv.loads(), [1, 1]);
+
+    for (stop, t) in workers {
+        let _ = stop.send(());
+        t.join().unwrap();
+    }
+}
+
+#[test]
+fn dropped_event_loops_are_skipped() {
+    drop(env_logger::init());
+    let mut core = t!(Core::new());
+    let (remotes, mut workers) = workers(2);
+
+    // The event loop which would be picked first is gone.
+    let (stop, t) = workers.remove(0);
+    let _ = stop.send(());
+    t.join().unwrap();
+
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &core.handle()));
+    let addr = t!(listener.local_addr());
+
+    let (tx, rx) = mpsc::unbounded();
+    let tx = Mutex::new(tx);
+    let mut srv = listener.distribute(remotes, Balance::LeastLoaded, move |socket, _, _| {
+        tx.lock().unwrap().unbounded_send(thread::current().id()).unwrap();
+        drop(socket);
+        Ok(())
+    });
+
+    let clients = thread::spawn(move || {
+        for _ in 0..2 {
+            let mut s = t!(net::TcpStream::connect(&addr));
+            // wait for the connection to be handled and closed
+            t!(s.read(&mut [0]));
+        }
+        addr
+    });
+
+    let ids = match core.run(rx.take(2).collect().select2(&mut srv)) {
+        Ok(Either::A((ids, _))) => ids,
+        _ => panic!("server failed"),
+    };
+    let addr = clients.join().unwrap();
+    let (stop, t) = workers.remove(0);
+    assert_eq!(ids, [t.thread().id(), t.thread().id()]);
+
+    // Once no event loop is left the server fails.
+    let _ = stop.send(());
+    t.join().unwrap();
+    let _client = t!(net::TcpStream::connect(&addr));
+    match core.run(srv) {
+        Err(e) => match Error::from(e) {
+            Error::ReactorGone => {}
+            other => panic!("unexpected error: {:?}", other),
+        },
+        Ok(()) => panic!("server completed"),
+    }
+}
@@ -0,0 +1,112 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::Write;
+use std::net;
+use std::time::Duration;
+
+use futures::{Future, Stream};
+use futures::sync::oneshot;
+use tokio_core::net::{GuardedIncoming, TcpListener};
+use tokio_core::reactor::Core;
+use tokio_io::io::read_to_end;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+fn listen(core: &Core) -> (GuardedIncoming, net::SocketAddr) {
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &core.handle()));
+    let addr = t!(listener.local_addr());
+    (listener.incoming_guarded(), addr)
+}
+
+#[test]
+fn drains_open_connections() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let (incoming, addr) = listen(&core);
+    let mut client = t!(net::TcpStream::connect(&addr));
+
+    let ((stream, _, guard), incoming) = match core.run(incoming.into_future()) {
+        Ok((Some(conn), incoming)) => (conn, incoming),
+        _ => panic!("accept failed"),
+    };
+    assert_eq!(incoming.open_connections(), 1);
+    handle.spawn(read_to_end(stream, Vec::new()).then(move |_| {
+        drop(guard);
+        Ok(())
+    }));
+
+    let drain = t!(incoming.drain(handle.now() + Duration::from_secs(10), &handle));
+    // The listener is closed while the accepted connection is still served.
+    assert!(net::TcpStream::connect(&addr).is_err());
+    t!(client.write_all(b"bye"));
+    drop(client);
+    assert_eq!(t!(core.run(drain)), 0);
+}
+
+#[test]
+fn drain_deadline() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let (incoming, addr) = listen(&core);
+    let _client = t!(net::TcpStream::connect(&addr));
+
+    let (_conn, incoming) = match core.run(incoming.into_future()) {
+        Ok((Some(conn), incoming)) => (conn, incoming),
+        _ => panic!("accept failed"),
+    };
+    let drain = t!(incoming.drain(handle.now() + Duration::from_millis(20), &handle));
+    assert_eq!(t!(core.run(drain)), 1);
+}
+
+#[test]
+fn drain_without_connections() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let (incoming, _) = listen(&core);
+    let drain = t!(incoming.drain(handle.now() + Duration::from_secs(10), &handle));
+    assert_eq!(t!(core.run(drain)), 0);
+}
+
+#[test]
+fn drain_while_driven_by_for_each() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let (incoming, addr) = listen(&core);
+    let drainer = incoming.drain_handle();
+
+    let (done_tx, done_rx) = oneshot::channel();
+    let server = handle.clone();
+    handle.spawn(incoming.for_each(move |(stream, _, guard)| {
+        server.spawn(read_to_end(stream, Vec::new()).then(move |_| {
+            drop(guard);
+            Ok(())
+        }));
+        Ok(())
+    }).then(|res| {
+        t!(res);
+        done_tx.send(()).map_err(|_| ())
+    }));
+
+    let mut client = t!(net::TcpStream::connect(&addr));
+    while drainer.open_connections() == 0 {
+        core.turn(None);
+    }
+
+    let drain = t!(drainer.drain(handle.now() + Duration::from_secs(10), &handle));
+    // Draining ends the stream, and the listener is closed along with it,
+    // while the accepted connection is still served.
+    t!(core.run(done_rx));
+    assert!(net::TcpStream::connect(&addr).is_err());
+    assert_eq!(drainer.open_connections(), 1);
+
+    t!(client.write_all(b"bye"));
+    drop(client);
+    assert_eq!(t!(core.run(drain)), 0);
+}
@@ -0,0 +1,55 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::{self, Write};
+
+use futures::Future;
+use tokio_core::testing::duplex;
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn transfer_more_than_capacity() {
+    let mut core = t!(Core::new());
+    let (a, b) = duplex(4);
+
+    let data = (0..100u8).collect::<Vec<_>>();
+    let write = tokio_io::io::write_all(a, data.clone()).and_then(|(a, _)| {
+        tokio_io::io::shutdown(a)
+    });
+    let read = tokio_io::io::read_to_end(b, Vec::new());
+
+    let (_, (_, buf)) = t!(core.run(write.join(read)));
+    assert_eq!(buf, data);
+}
+
+#[test]
+fn both_directions() {
+    let mut core = t!(Core::new());
+    let (a, b) = duplex(16);
+
+    let ping = tokio_io::io::write_all(a, b"ping").and_then(|(a, _)| {
+        tokio_io::io::read_exact(a, [0; 4])
+    });
+    let pong = tokio_io::io::read_exact(b, [0; 4]).and_then(|(b, buf)| {
+        assert_eq!(&buf, b"ping");
+        tokio_io::io::write_all(b, b"pong")
+    });
+
+    let ((_, buf), _) = t!(core.run(ping.join(pong)));
+    assert_eq!(&buf, b"pong");
+}
+
+#[test]
+fn write_after_peer_dropped() {
+    let (mut a, b) = duplex(16);
+    drop(b);
+    assert_eq!(a.write(b"x").unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+}
@@ -0,0 +1,74 @@
+extern crate futures;
+extern crate tokio;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::Write;
+use std::net;
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::Duration;
+
+use futures::{Future, Stream};
+use futures::future::Executor;
+use futures::sync::oneshot;
+use tokio::reactor;
+use tokio::runtime::Runtime;
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::{Error, Handle, Timeout};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn io_on_tokio_runtime() {
+    let mut rt = t!(Runtime::new());
+    let handle = Handle::from_tokio(&reactor::Handle::default(), rt.executor());
+
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(listener.local_addr());
+    let t = thread::spawn(move || {
+        let mut s = t!(net::TcpStream::connect(&addr));
+        t!(s.write_all(b"hello"));
+    });
+
+    let read = listener.incoming().into_future().map_err(|(e, _)| e).and_then(|(conn, _)| {
+        let (socket, _) = conn.unwrap();
+        tokio_io::io::read_to_end(socket, Vec::new())
+    });
+    let (_, buf) = t!(rt.block_on(read));
+    assert_eq!(buf, b"hello");
+    t.join().unwrap();
+}
+
+#[test]
+fn timeout_on_tokio_runtime() {
+    let mut rt = t!(Runtime::new());
+    let handle = Handle::from_tokio(&reactor::Handle::default(), rt.executor());
+
+    let timeout = t!(Timeout::new(Duration::from_millis(10), &handle));
+    t!(rt.block_on(timeout));
+}
+
+#[test]
+fn remote_of_tokio_handle() {
+    let mut rt = t!(Runtime::new());
+    let handle = Handle::from_tokio(&reactor::Handle::default(), rt.executor());
+    let remote = handle.remote().clone();
+
+    match remote.try_spawn(|_| Ok(())) {
+        Err(Error::NoEventLoop) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let spawned = panic::catch_unwind(AssertUnwindSafe(|| remote.spawn(|_| Ok(()))));
+    assert!(spawned.is_err(), "`Remote::spawn` didn't panic");
+
+    // `Send` futures still run, on the runtime's executor.
+    let (tx, rx) = oneshot::channel();
+    t!(remote.execute(futures::lazy(move || tx.send(()))));
+    t!(rt.block_on(rx));
+}
@@ -0,0 +1,123 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::net;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use futures::{future, Async, Future, Stream};
+use futures::stream::futures_unordered;
+use tokio_core::net::{TcpStream, UdpSocket};
+use tokio_core::reactor::{Core, Timeout};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+/// Spawns a task sending datagrams for as long as the socket lets it,
+/// counting them.
+fn spawn_sender(core: &Core) -> Rc<Cell<usize>> {
+    let handle = core.handle();
+    let receiver = t!(UdpSocket::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(receiver.local_addr());
+    let sender = t!(UdpSocket::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let sent = Rc::new(Cell::new(0));
+    let sent2 = sent.clone();
+    handle.spawn(future::poll_fn(move || -> Result<Async<()>, ()> {
+        let _receiver = &receiver;
+        loop {
+            match sender.send_to(b"x", &addr) {
+                Ok(_) => sent2.set(sent2.get() + 1),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    return Ok(Async::NotReady)
+                }
+                Err(e) => panic!("send failed: {}", e),
+            }
+        }
+    }));
+    sent
+}
+
+#[test]
+fn busy_socket_does_not_starve_timers() {
+    let mut core = t!(Core::new());
+    let sent = spawn_sender(&core);
+    let timeout = t!(Timeout::new(Duration::from_millis(50), &core.handle()));
+    t!(core.run(timeout));
+    assert!(sent.get() > 0);
+}
+
+#[test]
+fn io_budget_per_turn() {
+    let mut core = t!(Core::new());
+    core.set_turn_budget(10, 128);
+    let sent = spawn_sender(&core);
+
+    while sent.get() == 0 {
+        core.turn(Some(Duration::from_millis(10)));
+    }
+    let before = sent.get();
+    core.turn(Some(Duration::from_millis(0)));
+    assert_eq!(sent.get() - before, 10);
+}
+
+#[test]
+fn message_budget_per_turn() {
+    let mut core = t!(Core::new());
+    core.set_turn_budget(128, 5);
+    let remote = core.remote();
+    let ran = Arc::new(AtomicUsize::new(0));
+    for _ in 0..12 {
+        let ran = ran.clone();
+        remote.spawn(move |_| {
+            ran.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        });
+    }
+
+    core.turn(Some(Duration::from_millis(0)));
+    assert_eq!(ran.load(Ordering::SeqCst), 5);
+    core.turn(Some(Duration::from_millis(0)));
+    assert_eq!(ran.load(Ordering::SeqCst), 10);
+    core.turn(Some(Duration::from_millis(0)));
+    assert_eq!(ran.load(Ordering::SeqCst), 12);
+}
+
+#[test]
+fn exhausted_budget_wakes_nested_tasks() {
+    let mut core = t!(Core::new());
+    core.set_turn_budget(1, 128);
+    let handle = core.handle();
+
+    let listener = t!(net::TcpListener::bind("127.0.0.1:0"));
+    let addr = t!(listener.local_addr());
+    let mut clients = Vec::new();
+    let mut children: Vec<Box<Future<Item = (), Error = io::Error>>> = Vec::new();
+    for _ in 0..2 {
+        let mut client = t!(net::TcpStream::connect(&addr));
+        t!(client.write_all(b"data"));
+        clients.push(client);
+        let (server, _) = t!(listener.accept());
+        let server = t!(TcpStream::from_stream(server, &handle));
+        children.push(Box::new(tokio_io::io::read_exact(server, [0; 4]).map(|_| ())));
+    }
+    children.push(Box::new(future::empty()));
+
+    // Both reads are ready right away, but only one can be charged to each
+    // turn, and the other has to be woken up again for the next one.
+    let reads = futures_unordered(children).take(2).collect().map(|_| true);
+    let safety = t!(Timeout::new(Duration::from_secs(2), &handle)).map(|()| false);
+    let done = match core.run(reads.select(safety)) {
+        Ok((done, _)) => done,
+        Err((e, _)) => panic!("read failed: {}", e),
+    };
+    assert!(done, "a read was never woken up again");
+}
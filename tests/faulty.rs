@@ -0,0 +1,50 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::{self, Cursor, Read};
+
+use futures::future;
+use tokio_core::reactor::Core;
+use tokio_core::testing::{Fault, Faulty};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn scripted_faults() {
+    let mut core = t!(Core::new());
+    let mut io = Faulty::new(Cursor::new(b"hello world".to_vec()));
+    io.script_reads(vec![
+        Fault::Short(2),
+        Fault::WouldBlock,
+        Fault::Pass,
+        Fault::Error(io::ErrorKind::ConnectionReset),
+    ]);
+
+    t!(core.run(future::lazy(move || {
+        let mut buf = [0; 16];
+        assert_eq!(t!(io.read(&mut buf)), 2);
+        assert_eq!(io.read(&mut buf).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(t!(io.read(&mut buf)), 9);
+        assert_eq!(io.read(&mut buf).unwrap_err().kind(), io::ErrorKind::ConnectionReset);
+        assert_eq!(t!(io.read(&mut buf)), 0);
+        Ok::<_, ()>(())
+    })));
+}
+
+#[test]
+fn random_faults_preserve_data() {
+    let mut core = t!(Core::new());
+    let data = (0..255u8).cycle().take(10_000).collect::<Vec<_>>();
+    let mut io = Faulty::new(Cursor::new(data.clone()));
+    io.random(42, 0.5);
+
+    // Injected `WouldBlock`s notify the task, so this completes.
+    let (_, buf) = t!(core.run(tokio_io::io::read_to_end(io, Vec::new())));
+    assert_eq!(buf, data);
+}
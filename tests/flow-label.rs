@@ -0,0 +1,80 @@
+#![cfg(target_os = "linux")]
+
+extern crate futures;
+#[macro_use]
+extern crate tokio_core;
+
+use std::io;
+use std::net::{self, SocketAddr};
+
+use futures::{future, Async, Future, Stream};
+use tokio_core::net::{TcpListener, TcpStream, UdpSocket};
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+/// Binds a UDP socket on the IPv6 loopback, if the system has one.
+fn bind_v6() -> Option<net::UdpSocket> {
+    match net::UdpSocket::bind("[::1]:0") {
+        Ok(socket) => Some(socket),
+        Err(ref e) if e.kind() == io::ErrorKind::AddrNotAvailable => None,
+        Err(e) => panic!("bind failed: {}", e),
+    }
+}
+
+#[test]
+fn udp_traffic_class_and_flow_label() {
+    let receiver = match bind_v6() {
+        Some(socket) => socket,
+        None => return,
+    };
+    let mut core = t!(Core::new());
+    let socket = t!(UdpSocket::bind(&t!("[::1]:0".parse()), &core.handle()));
+
+    t!(socket.set_traffic_class(0x28));
+    assert_eq!(t!(socket.traffic_class()), 0x28);
+    assert!(socket.set_traffic_class(0x100).is_err());
+
+    let dst = match t!(receiver.local_addr()) {
+        SocketAddr::V6(addr) => addr,
+        SocketAddr::V4(..) => unreachable!(),
+    };
+    assert!(socket.set_flow_label(dst.ip(), 1 << 20).is_err());
+    t!(socket.set_flow_label(dst.ip(), 0x12345));
+    let target = SocketAddr::V6(dst);
+    t!(core.run(future::poll_fn(|| {
+        let n = try_nb!(socket.send_to_with_flow_label(b"tagged", &target, 0x12345));
+        Ok::<_, io::Error>(Async::Ready(n))
+    })));
+    // Not leased
+    assert!(socket.send_to_with_flow_label(b"x", &target, 0x12346).is_err());
+
+    let mut buf = [0; 16];
+    let (n, _) = t!(receiver.recv_from(&mut buf));
+    assert_eq!(&buf[..n], b"tagged");
+}
+
+#[test]
+fn tcp_connect_with_flow() {
+    if bind_v6().is_none() {
+        return
+    }
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let listener = t!(TcpListener::bind(&t!("[::1]:0".parse()), &handle));
+    let addr = t!(listener.local_addr());
+
+    let connect = TcpStream::connect_with_flow(&addr, 0x54321, 0xb8, &handle);
+    let accept = listener.incoming().into_future().map_err(|(e, _)| e);
+    let (stream, (accepted, _)) = t!(core.run(connect.join(accept)));
+    assert!(accepted.is_some());
+    assert_eq!(t!(stream.traffic_class()), 0xb8);
+
+    t!(stream.set_traffic_class(0));
+    assert_eq!(t!(stream.traffic_class()), 0);
+}
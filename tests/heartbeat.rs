@@ -0,0 +1,57 @@
+extern crate futures;
+extern crate tokio_core;
+
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+
+use futures::{Future, Sink, Stream};
+use futures::sync::mpsc;
+use tokio_core::reactor::{Core, Heartbeat};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+fn advance_secs(l: &mut Core, secs: u64) {
+    for _ in 0..secs {
+        l.advance(Duration::from_secs(1));
+        // Let the receiving end catch up as well.
+        l.turn(Some(Duration::from_millis(0)));
+    }
+}
+
+#[test]
+fn heartbeats_only_when_idle() {
+    let mut l = t!(Core::new_simulated());
+    let handle = l.handle();
+    let start = l.now();
+    let log = Rc::new(RefCell::new(Vec::new()));
+
+    let (tx, rx) = mpsc::unbounded::<u32>();
+    let tx = tx.sink_map_err(|_| io::Error::new(io::ErrorKind::Other, "receiver gone"));
+    let heartbeat = t!(Heartbeat::new(tx, 0, Duration::from_secs(10), &handle));
+
+    let (in_tx, in_rx) = mpsc::unbounded::<u32>();
+    let in_rx = in_rx.map_err(|_| io::Error::new(io::ErrorKind::Other, "input failed"));
+    handle.spawn(in_rx.forward(heartbeat).map(|_| ()).map_err(|e| panic!("{}", e)));
+
+    let log2 = log.clone();
+    let handle2 = handle.clone();
+    handle.spawn(rx.for_each(move |item| {
+        log2.borrow_mut().push((item, (handle2.now() - start).as_secs()));
+        Ok(())
+    }));
+
+    advance_secs(&mut l, 25);
+    t!(in_tx.unbounded_send(1));
+    advance_secs(&mut l, 5);
+    t!(in_tx.unbounded_send(2));
+    advance_secs(&mut l, 15);
+
+    assert_eq!(*log.borrow(), vec![(0, 10), (0, 20), (1, 26), (2, 31), (0, 41)]);
+}
@@ -0,0 +1,82 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::{self, Read, Write};
+use std::net::{self, SocketAddr};
+use std::thread;
+
+use futures::Future;
+use tokio_core::net::http_proxy;
+use tokio_core::reactor::Core;
+use tokio_io::io::read_to_end;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+/// Runs a single-connection proxy which answers with `response` and returns
+/// the request it received.
+fn proxy(response: &'static [u8]) -> (SocketAddr, thread::JoinHandle<String>) {
+    let listener = t!(net::TcpListener::bind("127.0.0.1:0"));
+    let addr = t!(listener.local_addr());
+    let t = thread::spawn(move || {
+        let mut s = t!(listener.accept()).0;
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            t!(s.read_exact(&mut byte));
+            request.push(byte[0]);
+        }
+        t!(s.write_all(response));
+        String::from_utf8(request).unwrap()
+    });
+    (addr, t)
+}
+
+#[test]
+fn tunnel_keeps_data_after_response() {
+    let mut core = t!(Core::new());
+    let (addr, t) = proxy(b"HTTP/1.1 200 Connection established\r\n\r\nbanner");
+
+    let stream = http_proxy::connect(&addr, "example.com", 443, &core.handle());
+    let (_, data) = t!(core.run(stream.and_then(|s| read_to_end(s, Vec::new()))));
+    assert_eq!(data, b"banner");
+
+    let request = t.join().unwrap();
+    assert!(request.starts_with("CONNECT example.com:443 HTTP/1.1\r\n"));
+    assert!(request.contains("\r\nHost: example.com:443\r\n"));
+    assert!(!request.contains("Proxy-Authorization"));
+}
+
+#[test]
+fn tunnel_with_basic_auth() {
+    let mut core = t!(Core::new());
+    let (addr, t) = proxy(b"HTTP/1.0 200 OK\r\nVia: test\r\n\r\n");
+
+    let stream = http_proxy::connect_with_basic_auth(&addr,
+                                                     "::1",
+                                                     22,
+                                                     "user",
+                                                     "secret",
+                                                     &core.handle());
+    t!(core.run(stream));
+
+    let request = t.join().unwrap();
+    assert!(request.starts_with("CONNECT [::1]:22 HTTP/1.1\r\n"));
+    assert!(request.contains("\r\nProxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n"));
+}
+
+#[test]
+fn tunnel_refused() {
+    let mut core = t!(Core::new());
+    let (addr, t) = proxy(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n");
+
+    let stream = http_proxy::connect(&addr, "example.com", 443, &core.handle());
+    let err = core.run(stream).err().expect("tunnel was established");
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    t.join().unwrap();
+}
@@ -0,0 +1,59 @@
+extern crate futures;
+extern crate tokio_core;
+
+use std::time::Duration;
+
+use futures::Stream;
+use tokio_core::reactor::{Core, IdleTracker};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn expires_idle_keys() {
+    let mut l = t!(Core::new_simulated());
+    let start = l.now();
+    let timeout = Duration::from_secs(10);
+    let mut tracker = t!(IdleTracker::with_resolution(timeout,
+                                                      Duration::from_secs(1),
+                                                      &l.handle()));
+    tracker.insert(1);
+    tracker.insert(2);
+    tracker.insert(3);
+    assert_eq!(tracker.len(), 3);
+    assert!(tracker.remove(&3));
+
+    l.advance(Duration::from_secs(5));
+    assert!(tracker.touch(&1));
+
+    let (key, tracker) = match l.run(tracker.into_future()) {
+        Ok(item) => item,
+        Err(_) => panic!("tracker failed"),
+    };
+    assert_eq!(key, Some(2));
+    let elapsed = l.now() - start;
+    assert!(elapsed >= timeout && elapsed <= timeout + Duration::from_secs(1));
+    assert!(!tracker.contains(&2));
+
+    let (key, mut tracker) = match l.run(tracker.into_future()) {
+        Ok(item) => item,
+        Err(_) => panic!("tracker failed"),
+    };
+    assert_eq!(key, Some(1));
+    let elapsed = l.now() - start;
+    assert!(elapsed >= Duration::from_secs(15) && elapsed <= Duration::from_secs(16));
+    assert!(tracker.is_empty());
+    assert!(!tracker.touch(&1));
+
+    // An empty tracker picks up keys inserted later.
+    tracker.insert(4);
+    let (key, _) = match l.run(tracker.into_future()) {
+        Ok(item) => item,
+        Err(_) => panic!("tracker failed"),
+    };
+    assert_eq!(key, Some(4));
+}
@@ -0,0 +1,44 @@
+extern crate futures;
+extern crate tokio_core;
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use futures::{future, Stream};
+use tokio_core::net::{self, InterfaceMonitor};
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[cfg(unix)]
+#[test]
+fn lists_loopback() {
+    let interfaces = t!(net::interfaces());
+    let lo = interfaces.iter().find(|i| i.is_loopback()).expect("no loopback interface");
+    assert!(lo.is_up());
+    assert!(lo.index() > 0);
+    assert!(lo.addresses().contains(&IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))));
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "ios",
+          target_os = "freebsd"))]
+#[test]
+fn monitor_waits_for_changes() {
+    let mut core = t!(Core::new());
+    let mut monitor = t!(InterfaceMonitor::new(&core.handle()));
+
+    let ready = t!(core.run(future::lazy(|| monitor.poll())));
+    assert!(ready.is_not_ready());
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios",
+              target_os = "freebsd")))]
+#[test]
+fn monitor_unsupported() {
+    let core = t!(Core::new());
+    assert!(InterfaceMonitor::new(&core.handle()).is_err());
+}
@@ -0,0 +1,74 @@
+extern crate futures;
+extern crate tokio_core;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+
+use futures::Future;
+use tokio_core::reactor::{Core, LoopData};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+/// Not `Send`, and records on drop which thread it was dropped on.
+struct Context {
+    calls: Rc<RefCell<u32>>,
+    dropped_on: mpsc::Sender<thread::ThreadId>,
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        self.dropped_on.send(thread::current().id()).unwrap();
+    }
+}
+
+#[test]
+fn access_from_other_thread() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let (tx, dropped_on) = mpsc::channel();
+    let data = LoopData::new(Context {
+        calls: Rc::new(RefCell::new(0)),
+        dropped_on: tx,
+    }, &handle);
+
+    let calls = data.with_local(&handle, |ctx| {
+        *ctx.calls.borrow_mut() += 1;
+        *ctx.calls.borrow()
+    });
+    assert_eq!(calls, Some(1));
+
+    let (done_tx, done_rx) = futures::sync::oneshot::channel();
+    let t = thread::spawn(move || {
+        let loop_thread = data.with(|ctx| {
+            *ctx.calls.borrow_mut() += 1;
+            thread::current().id()
+        }).wait().unwrap();
+        let calls = data.with(|ctx| *ctx.calls.borrow()).wait().unwrap();
+        drop(data);
+        done_tx.send(()).unwrap();
+        (loop_thread, calls)
+    });
+    t!(core.run(done_rx));
+    // Let the drop reach the event loop.
+    core.turn(Some(Default::default()));
+
+    let (loop_thread, calls) = t.join().unwrap();
+    assert_eq!(loop_thread, thread::current().id());
+    assert_eq!(calls, 2);
+    assert_eq!(t!(dropped_on.try_recv()), thread::current().id());
+}
+
+#[test]
+fn fails_once_loop_is_gone() {
+    let core = t!(Core::new());
+    let data = LoopData::new(5, &core.handle());
+    drop(core);
+    assert!(data.with(|n| *n).wait().is_err());
+}
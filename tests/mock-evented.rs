@@ -0,0 +1,151 @@
+extern crate futures;
+extern crate mio;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use futures::{future, Async, Future, Stream};
+use futures::executor::{self, Notify};
+use futures::stream::futures_unordered;
+use mio::Ready;
+use tokio_core::reactor::{Core, PollEvented, Timeout};
+use tokio_core::testing::{MockControl, MockEvented};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn read_wakes_up() {
+    let mut core = t!(Core::new());
+    let (mock, control) = MockEvented::new();
+    let io = t!(PollEvented::new(mock, &core.handle()));
+
+    let t = thread::spawn(move || {
+        control.push_read(b"hel");
+        control.push_read(b"lo");
+        control.close_read();
+    });
+
+    let (_, buf) = t!(core.run(tokio_io::io::read_to_end(io, Vec::new())));
+    assert_eq!(buf, b"hello");
+    t.join().unwrap();
+}
+
+#[test]
+fn write_waits_for_writable() {
+    let mut core = t!(Core::new());
+    let (mock, control) = MockEvented::new();
+    control.set_writable(false);
+    let io = t!(PollEvented::new(mock, &core.handle()));
+
+    let mut write = tokio_io::io::write_all(io, b"hello");
+    assert!(t!(core.run(future::lazy(|| write.poll()))).is_not_ready());
+    assert!(control.take_written().is_empty());
+
+    control.set_writable(true);
+    t!(core.run(write));
+    assert_eq!(control.take_written(), b"hello");
+}
+
+#[test]
+fn read_into_uninitialized_buffer() {
+    let mut core = t!(Core::new());
+    let (mock, control) = MockEvented::new();
+    let mut io = t!(PollEvented::new(mock, &core.handle()));
+    control.push_read(b"hello");
+
+    let mut buf = Vec::with_capacity(64 * 1024);
+    // `MockEvented` only copies into the buffers it reads into.
+    let n = t!(core.run(future::poll_fn(|| unsafe { io.read_buf_uninit(&mut buf) })));
+    assert_eq!(n, 5);
+    assert_eq!(buf, b"hello");
+}
+
+/// Raises a new read readiness event and gives the reactor time to deliver
+/// it, so that the next `need_read` finds the mock ready again.
+fn read_event(control: &MockControl) {
+    control.set_readiness(Ready::empty());
+    control.set_readiness(Ready::readable());
+    thread::sleep(Duration::from_millis(20));
+}
+
+struct Count(AtomicUsize);
+
+impl Notify for Count {
+    fn notify(&self, _id: usize) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn need_read_notifies_once_until_polled() {
+    let core = t!(Core::new());
+    let (mock, control) = MockEvented::new();
+    let io = t!(PollEvented::new(mock, &core.handle()));
+    read_event(&control);
+
+    let count = Arc::new(Count(AtomicUsize::new(0)));
+    let mut task = executor::spawn(future::empty::<(), ()>());
+    task.poll_fn_notify(&count, 0, |_| {
+        assert!(io.poll_read().is_ready());
+        read_event(&control);
+        io.need_read();
+        read_event(&control);
+        io.need_read();
+    });
+    assert_eq!(count.0.load(Ordering::SeqCst), 1);
+
+    // Polling the mock again means the task has to be notified again.
+    task.poll_fn_notify(&count, 0, |_| {
+        assert!(io.poll_read().is_ready());
+        read_event(&control);
+        io.need_read();
+    });
+    assert_eq!(count.0.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn need_read_wakes_nested_tasks() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+
+    // Each child finds its mock ready again whenever it gives up on it, and
+    // has to be woken up for that every time, even when `FuturesUnordered`
+    // polls it several times without returning to the event loop.
+    let mut children: Vec<Box<Future<Item = (), Error = ()>>> = Vec::new();
+    for _ in 0..2 {
+        let (mock, control) = MockEvented::new();
+        let io = t!(PollEvented::new(mock, &handle));
+        read_event(&control);
+        let mut polls = 0;
+        children.push(Box::new(future::poll_fn(move || {
+            if io.poll_read().is_not_ready() {
+                return Ok(Async::NotReady)
+            }
+            polls += 1;
+            if polls == 3 {
+                return Ok(Async::Ready(()))
+            }
+            read_event(&control);
+            io.need_read();
+            Ok(Async::NotReady)
+        })));
+    }
+    children.push(Box::new(future::empty()));
+
+    let done = futures_unordered(children).take(2).collect().map(|_| true);
+    let safety = t!(Timeout::new(Duration::from_secs(2), &handle)).map(|()| false);
+    let done = match core.run(done.select(safety.map_err(|_| ()))) {
+        Ok((done, _)) => done,
+        Err(_) => panic!("polling failed"),
+    };
+    assert!(done, "a task was never woken up again");
+}
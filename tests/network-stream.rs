@@ -0,0 +1,57 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::{Read, Write};
+use std::net::{self, Shutdown, SocketAddr};
+use std::thread;
+
+use futures::{Future, Stream};
+use tokio_core::net::{Listener, NetworkStream, TcpListener};
+use tokio_core::reactor::Core;
+use tokio_io::io::{read_to_end, write_all};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+/// Accepts a single connection and greets the peer, independent of the
+/// transport.
+fn greet_one<L>(listener: L) -> Box<Future<Item = L::Stream, Error = std::io::Error>>
+    where L: Listener + 'static,
+          L::Stream: 'static,
+{
+    Box::new(listener.incoming().into_future().map_err(|(e, _)| e).and_then(|(conn, _)| {
+        let (stream, peer) = conn.unwrap();
+        assert_eq!(format!("{:?}", t!(stream.peer_addr())), format!("{:?}", peer));
+        write_all(stream, b"hello").map(|(stream, _)| {
+            t!(stream.shutdown(Shutdown::Write));
+            stream
+        })
+    }))
+}
+
+#[test]
+fn generic_tcp_server() {
+    let mut core = t!(Core::new());
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &core.handle()));
+    let addr = t!(Listener::local_addr(&listener));
+
+    let t = thread::spawn(move || {
+        let mut s = t!(net::TcpStream::connect(&addr));
+        let mut greeting = String::new();
+        t!(s.read_to_string(&mut greeting));
+        t!(s.write_all(b"bye"));
+        greeting
+    });
+
+    let stream = t!(core.run(greet_one(listener)));
+    let stream: Box<NetworkStream<Addr = SocketAddr>> = Box::new(stream);
+    assert_eq!(t!(stream.local_addr()), addr);
+    let (_, reply) = t!(core.run(read_to_end(stream, Vec::new())));
+    assert_eq!(reply, b"bye");
+    assert_eq!(t.join().unwrap(), "hello");
+}
@@ -0,0 +1,105 @@
+extern crate futures;
+extern crate tokio_core;
+
+use std::net;
+use std::thread;
+use std::time::Duration;
+
+use futures::future;
+use tokio_core::net::pool::Pool;
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+/// Accepts `n` connections, passing them to the returned receiver.
+fn server(n: usize) -> (net::SocketAddr, thread::JoinHandle<Vec<net::TcpStream>>) {
+    let listener = t!(net::TcpListener::bind("127.0.0.1:0"));
+    let addr = t!(listener.local_addr());
+    let t = thread::spawn(move || {
+        (0..n).map(|_| t!(listener.accept()).0).collect()
+    });
+    (addr, t)
+}
+
+#[test]
+fn reuses_idle_connections() {
+    let (addr, server) = server(2);
+    let mut core = t!(Core::new());
+    let pool = t!(Pool::new(1, Duration::from_secs(60), &core.handle()));
+
+    let a = t!(core.run(pool.connect(&addr)));
+    let b = t!(core.run(pool.connect(&addr)));
+    assert!(!a.is_reused());
+    let local = t!(a.local_addr());
+    drop(a);
+    // Only one connection is kept for each address.
+    drop(b);
+    assert_eq!(pool.idle(&addr), 1);
+
+    let c = t!(core.run(pool.connect(&addr)));
+    assert!(c.is_reused());
+    assert_eq!(t!(c.local_addr()), local);
+    assert_eq!(pool.idle(&addr), 0);
+
+    // Detached connections don't return to the pool.
+    drop(c.detach());
+    assert_eq!(pool.idle(&addr), 0);
+    server.join().unwrap();
+}
+
+#[test]
+fn closed_connections_are_not_reused() {
+    let (addr, server) = server(1);
+    let mut core = t!(Core::new());
+    let pool = t!(Pool::new(4, Duration::from_secs(60), &core.handle()));
+
+    drop(t!(core.run(pool.connect(&addr))));
+    assert_eq!(pool.idle(&addr), 1);
+    drop(server.join().unwrap());
+    // Give the FIN time to arrive.
+    thread::sleep(Duration::from_millis(50));
+
+    assert!(t!(core.run(pool.checkout(addr))).is_none());
+    assert_eq!(pool.idle(&addr), 0);
+}
+
+#[test]
+fn closed_connections_are_detected_without_io_budget() {
+    let (addr, server) = server(2);
+    let mut core = t!(Core::new());
+    core.set_turn_budget(1, 128);
+    let pool = t!(Pool::new(4, Duration::from_secs(60), &core.handle()));
+
+    let busy = t!(core.run(pool.connect(&addr))).detach();
+    drop(t!(core.run(pool.connect(&addr))));
+    assert_eq!(pool.idle(&addr), 1);
+    drop(server.join().unwrap());
+    thread::sleep(Duration::from_millis(50));
+
+    // The turn's budget is used up before the idle connection is checked.
+    let checkout = future::lazy(|| {
+        assert!(busy.poll_write().is_ready());
+        pool.checkout(addr)
+    });
+    assert!(t!(core.run(checkout)).is_none());
+}
+
+#[test]
+fn idle_connections_time_out() {
+    let (addr, server) = server(1);
+    let mut core = t!(Core::new_simulated());
+    let pool = t!(Pool::new(4, Duration::from_secs(60), &core.handle()));
+
+    drop(t!(core.run(pool.connect(&addr))));
+    assert_eq!(pool.idle(&addr), 1);
+    core.advance(Duration::from_secs(30));
+    assert_eq!(pool.idle(&addr), 1);
+    core.advance(Duration::from_secs(40));
+    assert_eq!(pool.idle(&addr), 0);
+    server.join().unwrap();
+}
@@ -0,0 +1,51 @@
+extern crate futures;
+extern crate tokio_core;
+
+use std::net;
+use std::time::{Duration, Instant};
+
+use futures::Stream;
+use tokio_core::net::TcpListener;
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn limits_accept_rate() {
+    let mut l = t!(Core::new_simulated());
+    let handle = l.handle();
+    let srv = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(srv.local_addr());
+
+    // Connected through the listener's backlog before any is accepted.
+    let _clients = (0..5).map(|_| t!(net::TcpStream::connect(&addr))).collect::<Vec<_>>();
+
+    let start = l.now();
+    let real_start = Instant::now();
+    let incoming = srv.incoming().rate_limited(2, 2, &handle);
+    // Two right away, then one every half second.
+    let accepted = t!(l.run(incoming.take(5).collect()));
+    assert_eq!(accepted.len(), 5);
+    let elapsed = l.now() - start;
+    assert!(elapsed >= Duration::from_millis(1500), "accepted too fast: {:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(1600), "accepted too slow: {:?}", elapsed);
+    assert!(real_start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn very_high_rate() {
+    let mut l = t!(Core::new_simulated());
+    let handle = l.handle();
+    let srv = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(srv.local_addr());
+    let _clients = (0..3).map(|_| t!(net::TcpStream::connect(&addr))).collect::<Vec<_>>();
+
+    let incoming = srv.incoming().rate_limited(u32::max_value(), 1, &handle);
+    let accepted = t!(l.run(incoming.take(3).collect()));
+    assert_eq!(accepted.len(), 3);
+}
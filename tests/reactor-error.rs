@@ -0,0 +1,108 @@
+extern crate futures;
+extern crate tokio_core;
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future;
+use futures::executor::{self, Notify};
+use tokio_core::net::UdpSocket;
+use tokio_core::reactor::{Core, Error, LoopData, Timeout};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn wrong_reactor() {
+    let a = t!(Core::new());
+    let b = t!(Core::new());
+    let data = LoopData::new(1, &a.handle());
+
+    match data.try_with_local(&b.handle(), |n| *n) {
+        Err(Error::WrongReactor) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    assert_eq!(t!(data.try_with_local(&a.handle(), |n| *n)), 1);
+
+    match a.remote().try_handle() {
+        Err(Error::WrongReactor) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn reactor_gone() {
+    let core = t!(Core::new());
+    let handle = core.handle();
+    let remote = core.remote();
+    t!(remote.try_spawn(|_| Ok(())));
+    drop(core);
+
+    match handle.try_spawn(future::ok(())) {
+        Err(Error::ReactorGone) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+    match remote.try_spawn(|_| Ok(())) {
+        Err(Error::ReactorGone) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn registration_full_from_io_error() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    core.set_registration_limit(Some(0));
+    let addr = t!("127.0.0.1:0".parse());
+
+    let e = UdpSocket::bind(&addr, &handle).err().expect("limit not enforced");
+    assert_eq!(e.kind(), io::ErrorKind::Other);
+    match Error::from(e) {
+        Error::RegistrationFull(e) => assert_eq!(e.limit(), 0),
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+#[test]
+fn io_error_round_trip() {
+    let e: io::Error = Error::ReactorGone.into();
+    assert_eq!(e.kind(), io::ErrorKind::Other);
+    match Error::from(e) {
+        Error::ReactorGone => {}
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let e = io::Error::new(io::ErrorKind::ConnectionReset, "reset");
+    match Error::from(e) {
+        Error::Io(e) => {
+            let e: io::Error = Error::Io(e).into();
+            assert_eq!(e.kind(), io::ErrorKind::ConnectionReset);
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+struct Noop;
+
+impl Notify for Noop {
+    fn notify(&self, _id: usize) {}
+}
+
+#[test]
+fn timeout_after_reactor_gone() {
+    let core = t!(Core::new());
+    let timeout = t!(Timeout::new(Duration::from_secs(60), &core.handle()));
+    drop(core);
+
+    let mut timeout = executor::spawn(timeout);
+    let e = timeout.poll_future_notify(&Arc::new(Noop), 0).unwrap_err();
+    match Error::from(e) {
+        Error::ReactorGone => {}
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
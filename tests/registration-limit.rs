@@ -0,0 +1,83 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io;
+use std::net;
+use std::thread;
+
+use futures::{future, Stream};
+use tokio_core::net::{TcpListener, UdpSocket};
+use tokio_core::reactor::{Core, RegistrationLimitExceeded};
+use tokio_io::io::read_to_end;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+fn limit_exceeded(e: &io::Error) -> Option<usize> {
+    e.get_ref()
+     .and_then(|e| e.downcast_ref::<RegistrationLimitExceeded>())
+     .map(|e| e.limit())
+}
+
+#[test]
+fn limits_sockets() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    core.set_registration_limit(Some(2));
+    let addr = t!("127.0.0.1:0".parse());
+
+    let a = t!(UdpSocket::bind(&addr, &handle));
+    let _b = t!(UdpSocket::bind(&addr, &handle));
+    match UdpSocket::bind(&addr, &handle) {
+        Ok(_) => panic!("registration limit not enforced"),
+        Err(e) => assert_eq!(limit_exceeded(&e), Some(2)),
+    }
+    let usage = core.registration_usage();
+    assert_eq!(usage.registered(), 2);
+    assert_eq!(usage.limit(), Some(2));
+    assert_eq!(usage.rejected(), 1);
+
+    drop(a);
+    assert_eq!(handle.remote().registration_usage().registered(), 1);
+    t!(UdpSocket::bind(&addr, &handle));
+
+    core.set_registration_limit(None);
+    assert_eq!(core.registration_usage().limit(), None);
+}
+
+#[test]
+fn limits_accepted_streams() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    core.set_registration_limit(Some(1));
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(listener.local_addr());
+
+    let t = thread::spawn(move || {
+        let a = t!(net::TcpStream::connect(&addr));
+        let b = t!(net::TcpStream::connect(&addr));
+        drop(b);
+        a
+    });
+
+    let streams = t!(core.run(listener.incoming().take(2).collect()));
+    let a = t.join().unwrap();
+    let mut streams = streams.into_iter().map(|(s, _)| s);
+    let first = streams.next().unwrap();
+    let second = streams.next().unwrap();
+
+    // The listener has been dropped along with `Incoming`, and accepted
+    // streams are only registered once they're first polled, so the second
+    // one goes over the limit.
+    drop(a);
+    let (_first, _) = t!(core.run(read_to_end(first, Vec::new())));
+    match core.run(future::lazy(|| read_to_end(second, Vec::new()))) {
+        Ok(_) => panic!("registration limit not enforced"),
+        Err(e) => assert_eq!(limit_exceeded(&e), Some(1)),
+    }
+}
@@ -0,0 +1,56 @@
+extern crate futures;
+extern crate tokio_core;
+
+use futures::{future, Stream};
+use tokio_core::net::{DefaultGatewayMonitor, RouteMonitor};
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn monitor_waits_for_changes() {
+    let mut core = t!(Core::new());
+    let mut monitor = t!(RouteMonitor::new(&core.handle()));
+
+    // Existing routes aren't reported, so nothing is ready until the routing
+    // table changes.
+    let ready = t!(core.run(future::lazy(|| monitor.poll())));
+    assert!(ready.is_not_ready());
+}
+
+#[cfg(not(target_os = "linux"))]
+#[test]
+fn monitor_unsupported() {
+    let core = t!(Core::new());
+    assert!(RouteMonitor::new(&core.handle()).is_err());
+    assert!(DefaultGatewayMonitor::new(&core.handle()).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn default_gateways_reported_up_front() {
+    let mut core = t!(Core::new());
+    let monitor = t!(DefaultGatewayMonitor::new(&core.handle()));
+
+    // The current gateways are yielded as soon as the routing table has been
+    // read, whether or not there are any.
+    let (gateways, _monitor) = match core.run(monitor.into_future()) {
+        Ok((Some(gateways), monitor)) => (gateways, monitor),
+        Ok((None, _)) => panic!("monitor ended"),
+        Err((e, _)) => panic!("monitor failed: {}", e),
+    };
+    if let Some(route) = gateways.ipv4() {
+        assert!(route.is_default());
+        assert!(route.destination().is_ipv4());
+    }
+    if let Some(route) = gateways.ipv6() {
+        assert!(route.is_default());
+        assert!(route.destination().is_ipv6());
+    }
+}
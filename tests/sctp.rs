@@ -0,0 +1,56 @@
+#![cfg(target_os = "linux")]
+
+extern crate futures;
+extern crate libc;
+#[macro_use]
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io;
+
+use futures::{future, Async, Future, Stream};
+use tokio_core::net::{SctpListener, SctpStream};
+use tokio_core::reactor::Core;
+use tokio_io::io::{read_exact, write_all};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn echo_on_streams() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+
+    // Not every kernel is built with SCTP support.
+    let listener = match SctpListener::bind(&t!("127.0.0.1:0".parse()), &handle) {
+        Ok(listener) => listener,
+        Err(ref e) if e.raw_os_error() == Some(libc::EPROTONOSUPPORT) => return,
+        Err(e) => panic!("bind failed: {}", e),
+    };
+    let addr = t!(listener.local_addr());
+
+    let connect = SctpStream::connect_with_streams(&addr, 4, &handle);
+    let accept = listener.incoming().into_future().map_err(|(e, _)| e);
+    let (client, (accepted, _)) = t!(core.run(connect.join(accept)));
+    let (server, peer) = accepted.unwrap();
+    assert_eq!(t!(client.local_addr()), peer);
+    assert_eq!(t!(client.peer_addr()), addr);
+
+    let (client, _) = t!(core.run(write_all(client, b"hello")));
+    let (server, buf) = t!(core.run(read_exact(server, [0; 5])));
+    assert_eq!(&buf, b"hello");
+
+    t!(core.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(client.send_on(3, b"world"))))
+    })));
+    let mut buf = [0; 16];
+    let (n, stream) = t!(core.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(server.recv_from_stream(&mut buf))))
+    })));
+    assert_eq!(&buf[..n], b"world");
+    assert_eq!(stream, 3);
+}
@@ -0,0 +1,96 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::{self, Read, Write};
+use std::net;
+use std::thread;
+use std::time::Duration;
+
+use futures::{future, Future, Stream};
+use tokio_core::net::{TcpListener, TcpStream};
+use tokio_core::reactor::Core;
+use tokio_io::io::{read_exact, write_all};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+/// A stand-in for a TLS handshake which sends a greeting and expects the
+/// peer to send the same one.
+fn greet(stream: TcpStream) -> Box<Future<Item = TcpStream, Error = io::Error>> {
+    Box::new(write_all(stream, *b"hi").and_then(|(stream, _)| {
+        read_exact(stream, [0; 2])
+    }).and_then(|(stream, buf)| {
+        if &buf == b"hi" {
+            Ok(stream)
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "bad greeting"))
+        }
+    }))
+}
+
+#[test]
+fn connect_secured() {
+    let mut core = t!(Core::new());
+    let srv = t!(net::TcpListener::bind("127.0.0.1:0"));
+    let addr = t!(srv.local_addr());
+    let t = thread::spawn(move || {
+        let mut s = t!(srv.accept()).0;
+        let mut buf = [0; 2];
+        t!(s.read_exact(&mut buf));
+        t!(s.write_all(&buf));
+    });
+
+    let stream = TcpStream::connect_secured(&addr, greet, Duration::from_secs(10), &core.handle());
+    let stream = t!(core.run(stream));
+    assert_eq!(t!(stream.peer_addr()), addr);
+    t.join().unwrap();
+}
+
+#[test]
+fn connect_secured_times_out() {
+    let mut core = t!(Core::new());
+    let srv = t!(net::TcpListener::bind("127.0.0.1:0"));
+    let addr = t!(srv.local_addr());
+
+    let stall = |_: TcpStream| future::empty::<TcpStream, io::Error>();
+    let stream = TcpStream::connect_secured(&addr, stall, Duration::from_millis(50), &core.handle());
+    let err = core.run(stream).err().expect("handshake completed");
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    drop(srv);
+}
+
+#[test]
+fn incoming_secured_drops_failed_handshakes() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(listener.local_addr());
+
+    let t = thread::spawn(move || {
+        // The first client answers with the wrong greeting, the second stalls
+        // until the handshake times out and the third gets through.
+        let mut bad = t!(net::TcpStream::connect(&addr));
+        t!(bad.write_all(b"no"));
+        let stalled = t!(net::TcpStream::connect(&addr));
+        let mut good = t!(net::TcpStream::connect(&addr));
+        t!(good.write_all(b"hi"));
+        let mut buf = [0; 2];
+        t!(good.read_exact(&mut buf));
+        (t!(good.local_addr()), stalled)
+    });
+
+    let secured = listener.incoming().secured(greet, Duration::from_millis(200), 4, &handle);
+    let (stream, peer) = match core.run(secured.into_future()) {
+        Ok((Some(item), _)) => item,
+        Ok((None, _)) => panic!("listener closed"),
+        Err((e, _)) => panic!("accept failed: {}", e),
+    };
+    let (good_addr, _stalled) = t.join().unwrap();
+    assert_eq!(peer, good_addr);
+    assert_eq!(t!(stream.peer_addr()), good_addr);
+}
@@ -0,0 +1,157 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use futures::{future, Future, Stream};
+use tokio_core::reactor::Core;
+use tokio_core::testing::SimNet;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+fn ip(s: &str) -> IpAddr {
+    t!(s.parse())
+}
+
+fn addr(s: &str) -> SocketAddr {
+    t!(s.parse())
+}
+
+#[test]
+fn tcp_echo_with_latency() {
+    drop(env_logger::init());
+    let mut core = t!(Core::new_simulated());
+    let net = SimNet::new(&core.handle(), 1);
+    let latency = Duration::from_millis(50);
+    net.set_latency(latency);
+
+    let listener = t!(net.bind_tcp(&addr("10.0.0.1:80")));
+    let server = listener.incoming().into_future().map_err(|e| e.0).and_then(|(conn, _)| {
+        let (socket, peer) = conn.unwrap();
+        assert_eq!(peer.ip(), ip("10.0.0.2"));
+        tokio_io::io::read_exact(socket, [0; 5]).and_then(|(socket, buf)| {
+            tokio_io::io::write_all(socket, buf)
+        })
+    });
+    core.handle().spawn(server.map(|_| ()).map_err(|e| panic!("{}", e)));
+
+    let start = core.now();
+    let client = net.connect_tcp(ip("10.0.0.2"), &addr("10.0.0.1:80")).and_then(|socket| {
+        tokio_io::io::write_all(socket, b"hello")
+    }).and_then(|(socket, _)| {
+        tokio_io::io::read_exact(socket, [0; 5])
+    });
+    let (_, buf) = t!(core.run(client));
+
+    assert_eq!(&buf, b"hello");
+    assert!(core.now() - start >= latency * 3);
+}
+
+#[test]
+fn tcp_connect_refused() {
+    let mut core = t!(Core::new_simulated());
+    let net = SimNet::new(&core.handle(), 1);
+    let err = core.run(net.connect_tcp(ip("10.0.0.2"), &addr("10.0.0.1:80"))).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+}
+
+fn received_with_loss(seed: u64) -> usize {
+    let mut core = t!(Core::new_simulated());
+    let net = SimNet::new(&core.handle(), seed);
+    net.set_loss(0.5);
+
+    let a = t!(net.bind_udp(&addr("10.0.0.1:1000")));
+    let b = t!(net.bind_udp(&addr("10.0.0.2:1000")));
+    for i in 0..100u8 {
+        t!(a.send_to(&[i], &t!(b.local_addr())));
+    }
+    core.advance(Duration::from_millis(1));
+
+    t!(core.run(future::lazy(|| {
+        let mut buf = [0; 1];
+        let mut n = 0;
+        loop {
+            match b.recv_from(&mut buf) {
+                Ok((_, from)) => {
+                    assert_eq!(from, t!(a.local_addr()));
+                    n += 1;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok::<_, ()>(n),
+                Err(e) => panic!("{}", e),
+            }
+        }
+    })))
+}
+
+#[test]
+fn udp_loss_is_deterministic() {
+    let n = received_with_loss(7);
+    assert!(n > 0 && n < 100, "received {}", n);
+    assert_eq!(received_with_loss(7), n);
+}
+
+#[test]
+fn partition_holds_tcp_data() {
+    let mut core = t!(Core::new_simulated());
+    let net = SimNet::new(&core.handle(), 1);
+    net.set_latency(Duration::from_millis(10));
+
+    let mut listener = t!(net.bind_tcp(&addr("10.0.0.1:80")));
+    let (mut client, mut server) = t!(core.run(
+        net.connect_tcp(ip("10.0.0.2"), &addr("10.0.0.1:80")).map(|client| {
+            (client, listener.accept().unwrap().0)
+        })
+    ));
+
+    net.partition(ip("10.0.0.1"), ip("10.0.0.2"));
+    t!(client.write_all(b"x"));
+    core.advance(Duration::from_secs(10));
+    let mut buf = [0; 1];
+    let res = t!(core.run(future::lazy(|| Ok::<_, ()>(server.read(&mut buf)))));
+    assert_eq!(res.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+
+    net.heal(ip("10.0.0.1"), ip("10.0.0.2"));
+    core.advance(Duration::from_millis(10));
+    let res = t!(core.run(future::lazy(|| Ok::<_, ()>(server.read(&mut buf)))));
+    assert_eq!(t!(res), 1);
+    assert_eq!(&buf, b"x");
+}
+
+#[test]
+fn heal_keeps_tcp_data_in_order() {
+    let mut core = t!(Core::new_simulated());
+    let net = SimNet::new(&core.handle(), 1);
+    let latency = Duration::from_millis(100);
+    net.set_latency(latency);
+
+    let mut listener = t!(net.bind_tcp(&addr("10.0.0.1:80")));
+    let (mut client, mut server) = t!(core.run(
+        net.connect_tcp(ip("10.0.0.2"), &addr("10.0.0.1:80")).map(|client| {
+            (client, listener.accept().unwrap().0)
+        })
+    ));
+
+    // The first write is held back by the partition, while the second one is
+    // still in flight when it heals.
+    net.partition(ip("10.0.0.1"), ip("10.0.0.2"));
+    t!(client.write_all(b"a"));
+    core.advance(latency);
+    t!(client.write_all(b"b"));
+    core.advance(latency / 2);
+    net.heal(ip("10.0.0.1"), ip("10.0.0.2"));
+    core.advance(latency);
+
+    let mut buf = [0; 2];
+    let res = t!(core.run(future::lazy(|| Ok::<_, ()>(server.read(&mut buf)))));
+    assert_eq!(t!(res), 2);
+    assert_eq!(&buf, b"ab");
+}
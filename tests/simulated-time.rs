@@ -0,0 +1,53 @@
+extern crate env_logger;
+extern crate futures;
+extern crate tokio_core;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use futures::{Future, Stream};
+use tokio_core::reactor::{Core, Interval, Timeout};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn advance_fires_timeouts() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new_simulated());
+    let real_start = Instant::now();
+    let fired = Rc::new(Cell::new(false));
+
+    let fired2 = fired.clone();
+    let timeout = t!(Timeout::new(Duration::from_secs(3600), &l.handle()));
+    l.handle().spawn(timeout.then(move |_| {
+        fired2.set(true);
+        Ok(())
+    }));
+
+    l.advance(Duration::from_secs(3599));
+    assert!(!fired.get());
+    l.advance(Duration::from_secs(1));
+    assert!(fired.get());
+    assert!(real_start.elapsed() < Duration::from_secs(60));
+}
+
+#[test]
+fn run_skips_ahead() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new_simulated());
+    let real_start = Instant::now();
+    let start = l.now();
+
+    let dur = Duration::from_secs(60);
+    let interval = t!(Interval::new(dur, &l.handle()));
+    t!(l.run(interval.take(3).collect()));
+
+    assert!(l.now() - start >= dur * 3);
+    assert!(real_start.elapsed() < dur);
+}
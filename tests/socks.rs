@@ -0,0 +1,109 @@
+extern crate futures;
+extern crate tokio_core;
+extern crate tokio_io;
+
+use std::io::{self, Read, Write};
+use std::net::{self, SocketAddr};
+use std::thread;
+
+use futures::Future;
+use tokio_core::net::socks;
+use tokio_core::reactor::Core;
+use tokio_io::io::{read_to_end, write_all};
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+fn read_vec(s: &mut net::TcpStream, n: usize) -> Vec<u8> {
+    let mut buf = vec![0; n];
+    t!(s.read_exact(&mut buf));
+    buf
+}
+
+/// Runs a single-connection SOCKS5 proxy which expects the given handshake,
+/// answers the connect request with `reply` and then echoes "hello" back as
+/// "world".
+fn proxy(auth: Option<(&'static str, &'static str)>, reply: u8)
+         -> (SocketAddr, thread::JoinHandle<Vec<u8>>) {
+    let listener = t!(net::TcpListener::bind("127.0.0.1:0"));
+    let addr = t!(listener.local_addr());
+    let t = thread::spawn(move || {
+        let mut s = t!(listener.accept()).0;
+        let greeting = read_vec(&mut s, 2);
+        assert_eq!(greeting[0], 5);
+        let methods = read_vec(&mut s, greeting[1] as usize);
+        match auth {
+            Some((user, pass)) => {
+                assert!(methods.contains(&2));
+                t!(s.write_all(&[5, 2]));
+                let mut expected = vec![1, user.len() as u8];
+                expected.extend_from_slice(user.as_bytes());
+                expected.push(pass.len() as u8);
+                expected.extend_from_slice(pass.as_bytes());
+                assert_eq!(read_vec(&mut s, expected.len()), expected);
+                t!(s.write_all(&[1, 0]));
+            }
+            None => {
+                assert_eq!(methods, [0]);
+                t!(s.write_all(&[5, 0]));
+            }
+        }
+
+        let request = read_vec(&mut s, 5);
+        assert_eq!(&request[..4], &[5, 1, 0, 3]);
+        let mut target = read_vec(&mut s, request[4] as usize + 2);
+        t!(s.write_all(&[5, reply, 0, 1, 127, 0, 0, 1, 0x1f, 0x90]));
+        if reply == 0 {
+            assert_eq!(read_vec(&mut s, 5), b"hello");
+            t!(s.write_all(b"world"));
+        }
+        target.truncate(request[4] as usize);
+        target
+    });
+    (addr, t)
+}
+
+#[test]
+fn connect_without_auth() {
+    let mut core = t!(Core::new());
+    let (addr, t) = proxy(None, 0);
+
+    let stream = socks::connect(&addr, ("example.com", 80), &core.handle());
+    let data = stream.and_then(|s| write_all(s, b"hello"))
+                     .and_then(|(s, _)| read_to_end(s, Vec::new()));
+    let (_, data) = t!(core.run(data));
+    assert_eq!(data, b"world");
+    assert_eq!(t.join().unwrap(), b"example.com");
+}
+
+#[test]
+fn connect_with_password() {
+    let mut core = t!(Core::new());
+    let (addr, t) = proxy(Some(("user", "secret")), 0);
+
+    let stream = socks::connect_with_password(&addr,
+                                              ("example.com", 80),
+                                              "user",
+                                              "secret",
+                                              &core.handle());
+    let data = stream.and_then(|s| write_all(s, b"hello"))
+                     .and_then(|(s, _)| read_to_end(s, Vec::new()));
+    let (_, data) = t!(core.run(data));
+    assert_eq!(data, b"world");
+    t.join().unwrap();
+}
+
+#[test]
+fn connect_refused_by_proxy() {
+    let mut core = t!(Core::new());
+    let (addr, t) = proxy(None, 5);
+
+    let stream = socks::connect(&addr, ("example.com", 80), &core.handle());
+    let err = core.run(stream).err().expect("connect succeeded");
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    t.join().unwrap();
+}
@@ -4,12 +4,16 @@ extern crate env_logger;
 extern crate futures;
 
 use std::any::Any;
+use std::cell::Cell;
+use std::cmp;
+use std::rc::Rc;
 use std::sync::mpsc;
 use std::thread;
 use std::time::Duration;
 
 use futures::{Future, Poll};
 use futures::future;
+use futures::stream::{self, Stream};
 use futures::sync::oneshot;
 use tokio_core::reactor::{Core, Timeout};
 
@@ -226,3 +230,76 @@ fn spawn_in_drop() {
 
     lp.run(rx).unwrap();
 }
+
+#[test]
+fn spawn_stream_stops_on_error() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+    let handle = lp.handle();
+
+    let (tx, rx) = futures::sync::mpsc::unbounded();
+    let items = stream::iter_result(vec![Ok(1), Err(()), Ok(2)]);
+    let tx2 = tx.clone();
+    handle.spawn_stream(items, move |i| {
+        tx2.unbounded_send(i).unwrap();
+        Ok(())
+    });
+    let items = stream::iter_result(vec![Ok(3), Err(()), Ok(4)]);
+    handle.spawn_stream_with(items, 1, move |i| {
+        tx.unbounded_send(i).unwrap();
+        Ok(())
+    }, |()| true);
+
+    let mut got = lp.run(rx.collect()).unwrap();
+    got.sort();
+    assert_eq!(got, vec![1, 3, 4]);
+}
+
+#[test]
+fn spawn_stream_yields_after_kept_error() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+    let handle = lp.handle();
+
+    // A stream which fails every time it's polled, like a listener out of
+    // file descriptors, mustn't keep the event loop from running.
+    let errors = Rc::new(Cell::new(0));
+    let errors2 = errors.clone();
+    handle.spawn_stream_with(stream::poll_fn(|| -> Poll<Option<()>, ()> { Err(()) }),
+                             1,
+                             |()| Ok(()),
+                             move |()| {
+        errors2.set(errors2.get() + 1);
+        true
+    });
+
+    lp.run(Timeout::new(Duration::from_millis(20), &handle).unwrap()).unwrap();
+    assert!(errors.get() > 0);
+}
+
+#[test]
+fn spawn_stream_bounds_concurrency() {
+    drop(env_logger::init());
+    let mut lp = Core::new().unwrap();
+    let handle = lp.handle();
+
+    let active = Rc::new(Cell::new(0));
+    let max_active = Rc::new(Cell::new(0));
+    let (tx, rx) = futures::sync::mpsc::unbounded();
+    let (active2, max_active2, handle2) = (active.clone(), max_active.clone(), handle.clone());
+    handle.spawn_stream_with(stream::iter_ok::<_, ()>(0..6), 2, move |i| {
+        active2.set(active2.get() + 1);
+        max_active2.set(cmp::max(max_active2.get(), active2.get()));
+        let active = active2.clone();
+        let tx = tx.clone();
+        Timeout::new(Duration::from_millis(10), &handle2).unwrap().then(move |_| {
+            active.set(active.get() - 1);
+            tx.unbounded_send(i).unwrap();
+            Ok(())
+        })
+    }, |()| false);
+
+    let got = lp.run(rx.take(6).collect()).unwrap();
+    assert_eq!(got.len(), 6);
+    assert_eq!(max_active.get(), 2);
+}
@@ -1,6 +1,7 @@
 extern crate env_logger;
 extern crate futures;
 extern crate tokio_core;
+extern crate tokio_io;
 
 use std::net;
 use std::sync::mpsc::channel;
@@ -126,3 +127,107 @@ fn accept_2() {
     assert_eq!(t!(mine.local_addr()), t!(theirs.peer_addr()));
     assert_eq!(t!(theirs.local_addr()), t!(mine.peer_addr()));
 }
+
+#[cfg(unix)]
+#[test]
+fn bind_reuse_port() {
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let first = t!(TcpListener::bind_reuse_port(&t!("127.0.0.1:0".parse()), &l.handle()));
+    let addr = t!(first.local_addr());
+    let second = t!(TcpListener::bind_reuse_port(&addr, &l.handle()));
+    assert_eq!(t!(second.local_addr()), addr);
+
+    let t = thread::spawn(move || {
+        net::TcpStream::connect(&addr).unwrap()
+    });
+
+    let either = first.incoming().into_future()
+        .select(second.incoming().into_future())
+        .map(|(first, _)| first.0)
+        .map_err(|e| (e.0).0);
+    let mine = t!(l.run(either)).unwrap().0;
+    let theirs = t.join().unwrap();
+
+    assert_eq!(t!(mine.peer_addr()), t!(theirs.local_addr()));
+}
+
+#[test]
+fn from_std_without_handle() {
+    drop(env_logger::init());
+    let srv = t!(net::TcpListener::bind("127.0.0.1:0"));
+    let addr = t!(srv.local_addr());
+
+    // Neither socket is associated with an event loop until it's polled.
+    let listener = t!(TcpListener::from_std(srv));
+    let client = t!(net::TcpStream::connect(&addr));
+    let client = t!(TcpStream::from_std(client));
+
+    let mut l = t!(Core::new());
+    let server = listener.incoming().into_future().map_err(|e| e.0);
+    let (conn, _) = t!(l.run(server));
+    let (conn, peer) = conn.unwrap();
+
+    assert_eq!(peer, t!(client.local_addr()));
+    assert_eq!(t!(conn.local_addr()), t!(client.peer_addr()));
+}
+
+#[cfg(unix)]
+#[test]
+fn raw_fd_round_trip() {
+    use std::io::Read;
+    use std::os::unix::prelude::*;
+
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let srv = t!(net::TcpListener::bind("127.0.0.1:0"));
+    let addr = t!(srv.local_addr());
+    let t = thread::spawn(move || {
+        let mut buf = Vec::new();
+        t!(t!(srv.accept()).0.read_to_end(&mut buf));
+        buf
+    });
+
+    let stream = t!(l.run(TcpStream::connect(&addr, &l.handle())));
+    let local = t!(stream.local_addr());
+    let fd = stream.as_raw_fd();
+    assert_eq!(stream.into_raw_fd(), fd);
+
+    let stream = unsafe { TcpStream::from_raw_fd(fd) };
+    assert_eq!(t!(stream.local_addr()), local);
+    let write = tokio_io::io::write_all(stream, b"hello");
+    drop(t!(l.run(write)));
+    assert_eq!(t.join().unwrap(), b"hello");
+}
+
+#[test]
+fn incoming_hook() {
+    use std::io::{self, Write};
+
+    drop(env_logger::init());
+    let mut l = t!(Core::new());
+    let srv = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &l.handle()));
+    let addr = t!(srv.local_addr());
+    let t = thread::spawn(move || {
+        for b in b"axb" {
+            let mut s = t!(net::TcpStream::connect(&addr));
+            t!(s.write_all(&[*b]));
+        }
+    });
+
+    // Reject connections which start with an `x`.
+    let hooked = srv.incoming().hook(1, |socket, _| {
+        tokio_io::io::read_exact(socket, [0; 1]).and_then(|(socket, buf)| {
+            if buf[0] == b'x' {
+                Err(io::Error::new(io::ErrorKind::Other, "rejected"))
+            } else {
+                Ok((socket, buf[0]))
+            }
+        })
+    });
+    let accepted = t!(l.run(hooked.take(2).collect()));
+    t.join().unwrap();
+
+    let firsts = accepted.iter().map(|&(_, b)| b).collect::<Vec<_>>();
+    assert_eq!(firsts, b"ab");
+}
@@ -0,0 +1,92 @@
+#![cfg(target_os = "linux")]
+
+extern crate futures;
+#[macro_use]
+extern crate tokio_core;
+
+use std::io;
+use std::net;
+use std::thread;
+
+use futures::{future, Future, Stream};
+use tokio_core::net::{TcpListener, TcpStream, UdpSocket};
+use tokio_core::reactor::Core;
+
+macro_rules! t {
+    ($e:expr) => (match $e {
+        Ok(e) => e,
+        Err(e) => panic!("{} failed with {:?}", stringify!($e), e),
+    })
+}
+
+#[test]
+fn original_destination_without_redirect() {
+    let mut core = t!(Core::new());
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &core.handle()));
+    let addr = t!(listener.local_addr());
+    let t = thread::spawn(move || t!(net::TcpStream::connect(&addr)));
+
+    let (stream, _) = match core.run(listener.incoming().into_future()) {
+        Ok((Some(item), _)) => item,
+        _ => panic!("accept failed"),
+    };
+    assert_eq!(t!(stream.original_destination()), addr);
+    t.join().unwrap();
+}
+
+#[test]
+fn bind_transparent_to_foreign_address() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+
+    // Binding to an address which isn't assigned to any interface only works
+    // because of IP_TRANSPARENT, which needs CAP_NET_ADMIN.
+    let foreign = t!("198.51.100.7:0".parse());
+    match TcpListener::bind_transparent(&foreign, &handle) {
+        Ok(listener) => assert_eq!(t!(listener.local_addr()).ip(), foreign.ip()),
+        Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("bind_transparent failed: {}", e),
+    }
+
+    let listener = t!(TcpListener::bind(&t!("127.0.0.1:0".parse()), &handle));
+    let addr = t!(listener.local_addr());
+    let source = t!("127.0.0.1:0".parse());
+    let connect = TcpStream::connect_transparent(&source, &addr, &handle);
+    let accept = listener.incoming().into_future().map_err(|(e, _)| e);
+    let (stream, (accepted, _)) = t!(core.run(connect.join(accept)));
+    let (_, peer) = accepted.unwrap();
+    assert_eq!(t!(stream.local_addr()), peer);
+}
+
+#[test]
+fn udp_original_destination() {
+    let mut core = t!(Core::new());
+    let handle = core.handle();
+
+    let socket = match UdpSocket::bind_transparent(&t!("127.0.0.1:0".parse()), &handle) {
+        Ok(socket) => socket,
+        Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("bind_transparent failed: {}", e),
+    };
+    let addr = t!(socket.local_addr());
+    let client = t!(net::UdpSocket::bind("127.0.0.1:0"));
+    t!(client.send_to(b"query", &addr));
+
+    let mut buf = [0; 16];
+    let (n, from, original) = t!(core.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(futures::Async::Ready(try_nb!(socket.recv_from_original(&mut buf))))
+    })));
+    assert_eq!(&buf[..n], b"query");
+    assert_eq!(from, t!(client.local_addr()));
+    assert_eq!(original, addr);
+
+    // Replies are sent from a second socket bound to the original
+    // destination.
+    let reply = t!(UdpSocket::bind_transparent(&original, &handle));
+    t!(core.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(futures::Async::Ready(try_nb!(reply.send_to(b"answer", &from))))
+    })));
+    let (n, reply_from) = t!(client.recv_from(&mut buf));
+    assert_eq!(&buf[..n], b"answer");
+    assert_eq!(reply_from, original);
+}
@@ -1,3 +1,4 @@
+extern crate bytes;
 extern crate futures;
 #[macro_use]
 extern crate tokio_core;
@@ -245,3 +246,180 @@ fn send_framed() {
     }
 }
 
+
+#[cfg(target_os = "linux")]
+#[test]
+fn icmp_errors() {
+    use futures::{future, Async};
+
+    let mut l = t!(Core::new());
+    let a = t!(UdpSocket::bind(&t!("127.0.0.1:0".parse()), &l.handle()));
+    t!(a.set_recv_icmp_errors(true));
+
+    // Nothing listens on the port of a socket which has just been closed, so
+    // the datagram is answered with "port unreachable".
+    let closed = {
+        let b = t!(std::net::UdpSocket::bind("127.0.0.1:0"));
+        t!(b.local_addr())
+    };
+    let sent = t!(l.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(a.send_to_with_ttl(b"probe", &closed, 8))))
+    })));
+    assert_eq!(sent, 5);
+
+    let mut buf = [0; 16];
+    let (n, err) = t!(l.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(a.recv_icmp_error(&mut buf))))
+    })));
+    assert_eq!(&buf[..n], b"probe");
+    assert_eq!(err.destination(), Some(closed));
+    assert_eq!(err.offender(), Some(closed.ip()));
+    assert_eq!(err.error().kind(), io::ErrorKind::ConnectionRefused);
+    assert!(!err.is_time_exceeded());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn paced_and_segmented_sends() {
+    use std::time::{Duration, Instant};
+    use futures::{future, Async};
+
+    let mut l = t!(Core::new());
+    let a = t!(UdpSocket::bind(&t!("127.0.0.1:0".parse()), &l.handle()));
+    let b = t!(std::net::UdpSocket::bind("127.0.0.1:0"));
+    let b_addr = t!(b.local_addr());
+    t!(a.enable_txtime());
+
+    let at = Instant::now() + Duration::from_millis(1);
+    let sent = t!(l.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(a.send_to_at(b"paced", &b_addr, at))))
+    })));
+    assert_eq!(sent, 5);
+    let mut buf = [0; 16];
+    assert_eq!(t!(b.recv(&mut buf)), 5);
+    assert_eq!(&buf[..5], b"paced");
+
+    let sent = t!(l.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(a.send_to_segmented(b"aaaabbbbcc", &b_addr, 4))))
+    })));
+    assert_eq!(sent, 10);
+    assert_eq!(t!(b.recv(&mut buf)), 4);
+    assert_eq!(&buf[..4], b"aaaa");
+    assert_eq!(t!(b.recv(&mut buf)), 4);
+    assert_eq!(&buf[..4], b"bbbb");
+    assert_eq!(t!(b.recv(&mut buf)), 2);
+    assert_eq!(&buf[..2], b"cc");
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[test]
+fn bind_to_interface() {
+    use futures::{future, Async};
+
+    let loopback = t!(tokio_core::net::interfaces()).into_iter()
+        .find(|i| i.is_loopback())
+        .expect("no loopback interface");
+
+    let mut l = t!(Core::new());
+    let addr = t!("127.0.0.1:0".parse());
+    let a = match UdpSocket::bind_interface(&addr, loopback.name(), &l.handle()) {
+        Ok(a) => a,
+        // Needs CAP_NET_RAW on Linux
+        Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => return,
+        Err(e) => panic!("bind_interface failed: {}", e),
+    };
+    assert_eq!(t!(a.interface()), Some(loopback.name().to_string()));
+
+    let b = t!(std::net::UdpSocket::bind("127.0.0.1:0"));
+    t!(b.send_to(b"pinned", &t!(a.local_addr())));
+    let mut buf = [0; 16];
+    let (n, _) = t!(l.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(a.recv_from(&mut buf))))
+    })));
+    assert_eq!(&buf[..n], b"pinned");
+
+    t!(a.set_interface(None));
+    assert_eq!(t!(a.interface()), None);
+    assert!(a.set_interface(Some("no-such-interface")).is_err());
+}
+
+#[test]
+fn recv_into_uninitialized_buffer() {
+    use futures::{future, Async};
+
+    let mut l = t!(Core::new());
+    let a = t!(UdpSocket::bind(&t!("127.0.0.1:0".parse()), &l.handle()));
+    let a_addr = t!(a.local_addr());
+    let b = t!(std::net::UdpSocket::bind("127.0.0.1:0"));
+    t!(b.send_to(b"first", &a_addr));
+    t!(b.send_to(b"second", &a_addr));
+
+    let mut buf = bytes::BytesMut::with_capacity(64 * 1024);
+    let (n, from) = t!(l.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(a.recv_buf_from(&mut buf))))
+    })));
+    assert_eq!(n, 5);
+    assert_eq!(from, t!(b.local_addr()));
+    t!(a.connect(&from));
+    let n = t!(l.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(a.recv_buf(&mut buf))))
+    })));
+    assert_eq!(n, 6);
+    assert_eq!(&buf[..], b"firstsecond");
+}
+
+#[cfg(unix)]
+#[test]
+fn recv_truncated() {
+    use futures::{future, Async};
+
+    let mut l = t!(Core::new());
+    let a = t!(UdpSocket::bind(&t!("127.0.0.1:0".parse()), &l.handle()));
+    let a_addr = t!(a.local_addr());
+    let b = t!(std::net::UdpSocket::bind("127.0.0.1:0"));
+    let b_addr = t!(b.local_addr());
+    t!(b.send_to(&[7; 100], &a_addr));
+    t!(b.send_to(&[8; 4], &a_addr));
+
+    let mut buf = [0; 10];
+    let r = l.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(a.recv_from_checked(&mut buf))))
+    }));
+    if cfg!(target_os = "linux") {
+        assert_eq!(t!(r), (10, 100, b_addr));
+        assert_eq!(buf, [7; 10]);
+    } else {
+        assert_eq!(r.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    let r = t!(l.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(a.recv_from_checked(&mut buf))))
+    })));
+    assert_eq!(r, (4, 4, b_addr));
+    assert_eq!(&buf[..4], &[8; 4]);
+}
+
+#[cfg(unix)]
+#[test]
+fn send_chained_buffers() {
+    use bytes::{Buf, Bytes, IntoBuf};
+    use futures::{future, Async};
+
+    let mut l = t!(Core::new());
+    let a = t!(UdpSocket::bind(&t!("127.0.0.1:0".parse()), &l.handle()));
+    let b = t!(std::net::UdpSocket::bind("127.0.0.1:0"));
+    let b_addr = t!(b.local_addr());
+
+    let header = Bytes::from_static(b"head");
+    let payload = Bytes::from_static(b"payload");
+    let mut buf = header.into_buf().chain(payload.into_buf());
+    let n = t!(l.run(future::poll_fn(|| {
+        Ok::<_, io::Error>(Async::Ready(try_nb!(a.send_buf_to(&mut buf, &b_addr))))
+    })));
+    assert_eq!(n, 11);
+    assert_eq!(buf.remaining(), 0);
+
+    let mut dgram = [0; 64];
+    let (n, _) = t!(b.recv_from(&mut dgram));
+    assert_eq!(&dgram[..n], b"headpayload");
+}